@@ -51,6 +51,7 @@ pub mod prelude;
 pub mod mem;
 
 pub mod parser;
+pub mod remap;
 
 mod fmt;
 
@@ -364,10 +365,19 @@ impl Alloc {
         action(trace.last())
     }
 
+    /// Trace UID accessor.
+    pub fn trace_uid(&self) -> Trace {
+        self.trace
+    }
+
     /// Labels accessor.
     pub fn labels(&self) -> Arc<Vec<Str>> {
         self.labels.get()
     }
+    /// Labels UID accessor.
+    pub fn labels_uid(&self) -> Labels {
+        self.labels
+    }
     /// Time of creation accessor.
     pub fn toc(&self) -> time::SinceStart {
         self.toc
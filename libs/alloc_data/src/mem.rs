@@ -155,6 +155,18 @@ impl<'a> Factory<'a> {
     pub fn register_str(&mut self, s: &str) -> Str {
         self.str.get_uid(s)
     }
+    /// Registers the file of a location in the string factory.
+    ///
+    /// Applies the global path remapping first, see the [`remap`] module.
+    ///
+    /// [`remap`]: ../remap/index.html (The remap module)
+    #[inline]
+    pub fn register_file(&mut self, file: &str) -> Str {
+        match crate::remap::apply(file) {
+            Some(file) => self.str.get_uid(&file),
+            None => self.str.get_uid(file),
+        }
+    }
     /// Registers a label in the label factory.
     #[inline]
     pub fn register_labels(&mut self, labels: Vec<Str>) -> Labels {
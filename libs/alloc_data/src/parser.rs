@@ -89,6 +89,11 @@ peg::parser! {
         = "`" s: $( (!['`'] [_])* ) "`" { f.register_str(s) }
         / expected!("string (delimited by backquotes)")
 
+        /// A shared string for the file of a location, remapped by the global path remapping.
+        pub rule file(f: &mut Factory) -> Str
+        = "`" s: $( (!['`'] [_])* ) "`" { f.register_file(s) }
+        / expected!("file path (delimited by backquotes)")
+
         /// A whitespace-separated list of strings.
         pub rule string_list() -> Vec<String>
         = "[" list: ( (_ s: string() { s.to_string() })* ) _ "]" { list }
@@ -101,7 +106,7 @@ peg::parser! {
 
         /// Parses a location.
         pub rule loc(f: &mut Factory) -> Loc
-        = file: str(f)
+        = file: file(f)
             _ ":"
             _ line: usize()
             _ ":"
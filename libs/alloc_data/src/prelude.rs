@@ -23,6 +23,7 @@ pub use crate::parser::{self, Parseable};
 pub use crate::{
     err::{self, bail, Res, ResExt},
     mem::{self, labels::Labels, str::Str, trace::Trace},
+    remap,
     Alloc, AllocKind, BigUint, Builder, CLoc, Diff, Init, Loc, Span,
 };
 
@@ -0,0 +1,262 @@
+/*<LICENSE>
+    This file is part of Memthol.
+
+    Copyright (C) 2020 OCamlPro.
+
+    Memthol is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Memthol is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Memthol.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Source path remapping.
+//!
+//! The files of the locations of a trace are paths on the machine that built the program, often
+//! relative to a build directory (`_build/default/...`) or inside a sandbox. Remapping rules
+//! rewrite these paths when locations are interned, see [`Factory::register_file`].
+//!
+//! When a source root is set, the text of the line of a location can be retrieved with
+//! [`source_line`], provided the (remapped) file exists under the source root. At most
+//! [`MAX_SOURCES`] files are kept in memory, and a file is read again when it is modified.
+//!
+//! [`Factory::register_file`]: ../mem/struct.Factory.html#method.register_file
+//! (The register_file method on Factory)
+//! [`source_line`]: fn.source_line.html (The source_line function)
+//! [`MAX_SOURCES`]: constant.MAX_SOURCES.html (The MAX_SOURCES constant)
+
+prelude! {}
+
+use std::path::{Component, Path, PathBuf};
+
+/// Maximum number of source files kept in memory.
+pub const MAX_SOURCES: usize = 64;
+
+/// A remapping rule, replaces a prefix of a path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    /// Prefix to replace.
+    pub from: String,
+    /// Replacement, empty to strip the prefix.
+    pub to: String,
+}
+impl Rule {
+    /// Rule stripping a prefix.
+    pub fn strip(prefix: impl Into<String>) -> Self {
+        Self {
+            from: prefix.into(),
+            to: String::new(),
+        }
+    }
+
+    /// Rule replacing a prefix.
+    pub fn map(from: impl Into<String>, to: impl Into<String>) -> Self {
+        Self {
+            from: from.into(),
+            to: to.into(),
+        }
+    }
+
+    /// Parses a `<from>=<to>` rule.
+    pub fn parse(s: &str) -> Res<Self> {
+        let mut split = s.splitn(2, '=');
+        match (split.next(), split.next()) {
+            (Some(from), Some(to)) if !from.is_empty() => Ok(Self::map(from, to)),
+            _ => bail!("illegal remapping rule `{}`, expected `<from>=<to>`", s),
+        }
+    }
+
+    /// Applies the rule to a path, `None` if the rule does not apply.
+    pub fn apply(&self, path: &str) -> Option<String> {
+        path.strip_prefix(&self.from)
+            .map(|suffix| format!("{}{}", self.to, suffix))
+    }
+}
+impl fmt::Display for Rule {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}={}", self.from, self.to)
+    }
+}
+
+/// Remapping rules and source root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Remap {
+    /// Remapping rules, only the first rule that applies to a path is used.
+    rules: Vec<Rule>,
+    /// Directory relative paths are resolved in when retrieving source lines.
+    ///
+    /// Source lines are only retrieved if this is set.
+    source_root: Option<PathBuf>,
+}
+impl Remap {
+    /// Constructor.
+    pub fn new(rules: Vec<Rule>, source_root: Option<PathBuf>) -> Self {
+        Self { rules, source_root }
+    }
+
+    /// Remapping that does nothing, and does not retrieve source lines.
+    pub fn empty() -> Self {
+        Self::new(vec![], None)
+    }
+
+    /// Remapping rules.
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+    /// Source root, if any.
+    pub fn source_root(&self) -> Option<&Path> {
+        self.source_root.as_ref().map(PathBuf::as_path)
+    }
+
+    /// Applies the first rule that applies to a path, `None` if no rule applies.
+    pub fn apply(&self, path: &str) -> Option<String> {
+        self.rules.iter().find_map(|rule| rule.apply(path))
+    }
+
+    /// Local path of a (remapped) file under the source root.
+    ///
+    /// `None` if there is no source root, or if the file is not under it: absolute paths must start
+    /// with the source root, and paths cannot go up with `..`.
+    pub fn local_path(&self, file: &str) -> Option<PathBuf> {
+        let root = self.source_root.as_ref()?;
+        let file = Path::new(file);
+        let file = if file.is_absolute() {
+            file.strip_prefix(root).ok()?
+        } else {
+            file
+        };
+        let is_under_root = file.components().all(|component| match component {
+            Component::Normal(_) | Component::CurDir => true,
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => false,
+        });
+        if is_under_root {
+            Some(root.join(file))
+        } else {
+            None
+        }
+    }
+}
+impl fmt::Display for Remap {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        for (idx, rule) in self.rules.iter().enumerate() {
+            if idx > 0 {
+                write!(fmt, " ")?
+            }
+            rule.fmt(fmt)?
+        }
+        Ok(())
+    }
+}
+
+crate::prelude::lazy_static! {
+    /// Global remapping.
+    static ref REMAP: sync::RwLock<Remap> = sync::RwLock::new(Remap::empty());
+    /// Source files read so far.
+    static ref SOURCES: sync::Mutex<HMap<PathBuf, Source>> = sync::Mutex::new(HMap::new());
+}
+
+/// A source file read by [`source_line`].
+///
+/// [`source_line`]: fn.source_line.html (The source_line function)
+struct Source {
+    /// Modification time of the file when it was read, if available.
+    modified: Option<std::time::SystemTime>,
+    /// Time at which the file was read.
+    read: time::Instant,
+    /// Lines of the file, `None` if it could not be read.
+    lines: Option<Arc<Vec<String>>>,
+}
+impl Source {
+    /// Reads a file.
+    fn read(path: &Path, modified: Option<std::time::SystemTime>) -> Self {
+        let lines = std::fs::read_to_string(path)
+            .ok()
+            .map(|text| Arc::new(text.lines().map(str::to_string).collect()));
+        Self {
+            modified,
+            read: time::Instant::now(),
+            lines,
+        }
+    }
+
+    /// Text of a line, `index` starts at `0`.
+    fn line(&self, index: usize) -> Option<String> {
+        self.lines
+            .as_ref()?
+            .get(index)
+            .map(|line| line.trim().to_string())
+    }
+}
+
+/// Sets the global remapping.
+///
+/// Must be called before any trace is loaded, locations interned before are not remapped.
+pub fn set(remap: Remap) -> Res<()> {
+    let mut global = REMAP
+        .write()
+        .map_err(|e| format!("while setting path remapping: {}", e))?;
+    *global = remap;
+    SOURCES
+        .lock()
+        .map_err(|e| format!("while setting path remapping: {}", e))?
+        .clear();
+    Ok(())
+}
+
+/// Applies some action to the global remapping.
+///
+/// Panics if the global remapping has been poisoned.
+pub fn remap_do<Res>(action: impl FnOnce(&Remap) -> Res) -> Res {
+    let remap = REMAP
+        .read()
+        .expect("fatal error: the global path remapping has been poisoned");
+    action(&remap)
+}
+
+/// Applies the global remapping to a path, `None` if no rule applies.
+pub fn apply(path: &str) -> Option<String> {
+    remap_do(|remap| remap.apply(path))
+}
+
+/// Text of the line of a location, if the source root is set and the file can be read.
+///
+/// The file is read again if it was modified since the last time it was read. When
+/// [`MAX_SOURCES`] files are in memory already, reading a new one drops the oldest one.
+///
+/// [`MAX_SOURCES`]: constant.MAX_SOURCES.html (The MAX_SOURCES constant)
+pub fn source_line(loc: &Loc) -> Option<String> {
+    let path = loc.file.str_do(|file| remap_do(|remap| remap.local_path(file)))?;
+    // Lines start at `1`.
+    let index = loc.line.checked_sub(1)?;
+    let modified = std::fs::metadata(&path)
+        .and_then(|meta| meta.modified())
+        .ok();
+
+    if let Some(source) = SOURCES.lock().ok()?.get(&path) {
+        if source.modified == modified {
+            return source.line(index);
+        }
+    }
+
+    // Not holding the lock while reading the file.
+    let source = Source::read(&path, modified);
+    let line = source.line(index);
+
+    let mut sources = SOURCES.lock().ok()?;
+    while !sources.contains_key(&path) && sources.len() >= MAX_SOURCES {
+        let oldest = sources
+            .iter()
+            .min_by_key(|(_, source)| source.read)
+            .map(|(path, _)| path.clone())?;
+        sources.remove(&oldest);
+    }
+    sources.insert(path, source);
+    line
+}
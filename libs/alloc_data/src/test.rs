@@ -232,3 +232,34 @@ fn diff_0() {
     assert_eq! { diff.new.len(), 164 }
     assert_eq! { diff.dead.len(), 21 }
 }
+
+#[test]
+fn remap_rules() {
+    let remap = remap::Remap::new(
+        vec![
+            unwrap!(remap::Rule::parse("/build/sandbox/=/home/me/repo/")),
+            remap::Rule::strip("_build/default/"),
+        ],
+        Some("/home/me/repo".into()),
+    );
+    assert_eq! { remap.apply("_build/default/src/test.ml"), Some("src/test.ml".into()) }
+    assert_eq! {
+        remap.apply("/build/sandbox/src/test.ml"),
+        Some("/home/me/repo/src/test.ml".into())
+    }
+    assert_eq! { remap.apply("src/test.ml"), None }
+    assert_eq! {
+        remap.local_path("src/test.ml"),
+        Some("/home/me/repo/src/test.ml".into())
+    }
+    assert_eq! {
+        remap.local_path("/home/me/repo/src/test.ml"),
+        Some("/home/me/repo/src/test.ml".into())
+    }
+    assert_eq! { remap.local_path("/etc/passwd"), None }
+    assert_eq! { remap.local_path("/home/me/repo/../../etc/passwd"), None }
+    assert_eq! { remap.local_path("src/../../secret.ml"), None }
+    assert_eq! { remap::Remap::empty().local_path("src/test.ml"), None }
+    assert! { remap::Rule::parse("no_equal_sign").is_err() }
+    assert! { remap::Rule::parse("=empty/prefix").is_err() }
+}
@@ -30,6 +30,12 @@ pub mod settings;
 pub mod time;
 pub use spec::ChartSpec;
 
+/// Maximum number of x-axis ticks of the points a chart keeps to patch them.
+///
+/// Each tick costs one map from the lines of the chart to their value. Charts with more ticks than
+/// this do not keep the points the client has, and always send all of their points on reload.
+pub const MAX_SENT_TICKS: usize = 4096;
+
 /// A chart with no UID.
 #[cfg(any(test, feature = "server"))]
 pub enum RawChart {
@@ -45,11 +51,12 @@ impl RawChart {
         filters: &mut Filters,
         init: bool,
         resolution: settings::Resolution,
+        downsampling: settings::Downsampling,
         time_windopt: &TimeWindopt,
     ) -> Res<Option<Points>> {
         match self {
             Self::Time(time_chart) => {
-                time_chart.new_points(filters, init, resolution, time_windopt)
+                time_chart.new_points(filters, init, resolution, downsampling, time_windopt)
             }
         }
     }
@@ -90,7 +97,29 @@ pub struct Chart {
     /// This typically happens server-side, as the server needs the actual resolution of the chart
     /// (which only the client-side knows) before it can send the initial points.
     still_init: bool,
+    /// Inputs of the points the client has for this chart, and these points if they have at most
+    /// [`MAX_SENT_TICKS`] x-axis ticks.
+    ///
+    /// Used to skip reloads that would not change anything, and to only send what changed
+    /// otherwise. Every chart of every session, detached ones included, keeps its points here, see
+    /// [`MAX_SENT_TICKS`] for the memory cost.
+    ///
+    /// [`MAX_SENT_TICKS`]: constant.MAX_SENT_TICKS.html (The MAX_SENT_TICKS constant)
+    sent: Option<(Inputs, Option<Points>)>,
 }
+
+/// Inputs the points of a chart depend on, besides the filters and the data.
+#[cfg(any(test, feature = "server"))]
+#[derive(Debug, Clone, PartialEq)]
+struct Inputs {
+    /// Resolution of the chart.
+    resolution: Option<settings::Resolution>,
+    /// Downsampling strategy of the chart.
+    downsampling: settings::Downsampling,
+    /// Time window of the chart.
+    time_window: TimeWindopt,
+}
+
 #[cfg(any(test, feature = "server"))]
 impl Chart {
     /// Creates a chart.
@@ -108,6 +137,7 @@ impl Chart {
             settings,
             chart,
             still_init: true,
+            sent: None,
         };
         Ok(slf)
     }
@@ -125,6 +155,7 @@ impl Chart {
             settings,
             chart,
             still_init: true,
+            sent: None,
         })
     }
 
@@ -170,10 +201,17 @@ impl Chart {
         time_windopt: &TimeWindopt,
     ) -> Res<Option<Points>> {
         self.still_init = self.still_init || init;
+        let time_windopt = self.settings.time_windopt(time_windopt);
         if let Some(resolution) = self.settings.resolution() {
             let res = self
                 .chart
-                .new_points(filters, self.still_init, resolution, time_windopt);
+                .new_points(
+                    filters,
+                    self.still_init,
+                    resolution,
+                    self.settings.downsampling(),
+                    time_windopt,
+                );
             self.still_init = false;
             res
         } else {
@@ -185,4 +223,67 @@ impl Chart {
     pub fn reset(&mut self, filters: &filter::Filters) {
         self.chart.reset(filters)
     }
+
+    /// Inputs of the points of the chart, given the global time window.
+    fn inputs(&self, time_windopt: &TimeWindopt) -> Inputs {
+        Inputs {
+            resolution: self.settings.resolution(),
+            downsampling: self.settings.downsampling(),
+            time_window: *self.settings.time_windopt(time_windopt),
+        }
+    }
+
+    /// True if the client has points for this chart, generated with the current settings.
+    ///
+    /// Does not account for the filters, the caller is responsible for checking them.
+    pub fn is_up_to_date(&self, time_windopt: &TimeWindopt) -> bool {
+        match &self.sent {
+            Some((inputs, _)) => inputs == &self.inputs(time_windopt),
+            None => false,
+        }
+    }
+
+    /// Registers some points as sent to the client.
+    ///
+    /// If `overwrite`, the points replace the ones the client had, otherwise they are appended to
+    /// them.
+    pub fn register_sent(
+        &mut self,
+        points: &Points,
+        overwrite: bool,
+        time_windopt: &TimeWindopt,
+    ) -> Res<()> {
+        let inputs = self.inputs(time_windopt);
+        let sent = match self.sent.take() {
+            Some((_, Some(mut sent))) if !overwrite => {
+                sent.extend(&mut points.clone())?;
+                Some(sent)
+            }
+            // The client has too many points for us to keep them.
+            Some((_, None)) if !overwrite => None,
+            _ => Some(points.clone()),
+        };
+        self.sent = Some((inputs, sent.filter(|sent| sent.len() <= MAX_SENT_TICKS)));
+        Ok(())
+    }
+
+    /// Patch turning the points the client has into `points`, which are registered as sent.
+    ///
+    /// Returns `None` if the client already has these points.
+    pub fn patch_sent(
+        &mut self,
+        points: Points,
+        time_windopt: &TimeWindopt,
+    ) -> Option<point::PointsPatch> {
+        let inputs = self.inputs(time_windopt);
+        let patch = match &self.sent {
+            Some((_, Some(sent))) if !sent.is_empty() => sent.patch_to(&points),
+            _ => Some(point::PointsPatch::Overwrite(points.clone())),
+        };
+        self.sent = Some((
+            inputs,
+            Some(points).filter(|sent| sent.len() <= MAX_SENT_TICKS),
+        ));
+        patch
+    }
 }
@@ -95,13 +95,76 @@ impl fmt::Display for DisplayMode {
     }
 }
 
+/// A time chart's downsampling strategy.
+///
+/// Charts have far less pixels than there are events in a trace, so points are aggregated in one
+/// bucket per pixel on the x-axis. The strategy decides which points represent a bucket.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+pub enum Downsampling {
+    /// Keeps the minimum, maximum and last value of each line in each bucket.
+    ///
+    /// Peaks are never lost, whatever the zoom level.
+    MinMax,
+    /// Keeps the last value of each line in each bucket.
+    ///
+    /// Produces the fewest points, but peaks shorter than a pixel are lost.
+    Last,
+    /// Largest-triangle-three-buckets, keeps the point of each bucket that best preserves the shape
+    /// of the lines.
+    Lttb,
+}
+impl Default for Downsampling {
+    fn default() -> Self {
+        Self::MinMax
+    }
+}
+impl Downsampling {
+    /// Short description of the downsampling strategy.
+    pub fn desc(self) -> &'static str {
+        match self {
+            Self::MinMax => "min/max",
+            Self::Last => "last",
+            Self::Lttb => "lttb",
+        }
+    }
+
+    /// List of all the downsampling strategies.
+    pub fn all() -> Vec<Self> {
+        base::debug_do! {
+            // If you get an error here, it means the definition of `Downsampling` changed. You need
+            // to update the following `match` statement, as well as the list returned by this
+            // function (below).
+            match Self::MinMax {
+                Self::MinMax
+                | Self::Last
+                | Self::Lttb => ()
+            }
+        }
+        vec![Self::MinMax, Self::Last, Self::Lttb]
+    }
+
+    /// An identifier-like name for a downsampling strategy.
+    pub fn to_uname(self) -> &'static str {
+        match self {
+            Self::MinMax => "min_max",
+            Self::Last => "last",
+            Self::Lttb => "lttb",
+        }
+    }
+}
+impl fmt::Display for Downsampling {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        self.desc().fmt(fmt)
+    }
+}
+
 /// Resolution.
 ///
 /// Mostly used so that the client can send the resolution of each chart to the server. The reason
 /// is that resolution is taken into account when extracting the actual points to send to the
 /// client. This extraction will typically compress points that are too close together, for both
 /// readability and performance.
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Resolution {
     /// Width.
     pub width: u32,
@@ -137,6 +200,10 @@ pub struct Chart {
     y_log: bool,
     /// Resolution of the chart, if it is known.
     resolution: Option<Resolution>,
+    /// Downsampling strategy.
+    downsampling: Downsampling,
+    /// Local time window of the chart, `None` if the chart uses the global time window.
+    time_window: Option<TimeWindopt>,
 }
 impl Chart {
     /// Constructor.
@@ -149,6 +216,8 @@ impl Chart {
             x_log: false,
             y_log: false,
             resolution: None,
+            downsampling: Downsampling::default(),
+            time_window: None,
         }
     }
 
@@ -181,6 +250,16 @@ impl Chart {
                 self.set_resolution(resolution);
                 true
             }
+            SetDownsampling(downsampling) => {
+                let changed = self.downsampling != downsampling;
+                self.downsampling = downsampling;
+                changed
+            }
+            SetTimeWindow(time_window) => {
+                let changed = self.time_window != time_window;
+                self.time_window = time_window;
+                changed
+            }
         }
     }
 
@@ -273,6 +352,20 @@ impl Chart {
         self.resolution
     }
 
+    /// Downsampling strategy of the chart.
+    pub fn downsampling(&self) -> Downsampling {
+        self.downsampling
+    }
+
+    /// Local time window of the chart, `None` if the chart uses the global time window.
+    pub fn local_time_windopt(&self) -> Option<&TimeWindopt> {
+        self.time_window.as_ref()
+    }
+    /// Time window of the chart, given the global time window.
+    pub fn time_windopt<'a>(&'a self, global: &'a TimeWindopt) -> &'a TimeWindopt {
+        self.time_window.as_ref().unwrap_or(global)
+    }
+
     /// Sets the x-axis-log setting.
     pub fn set_x_log(&mut self, x_log: bool) {
         self.x_log = x_log
@@ -291,6 +384,8 @@ pub struct Charts {
     /// This only impacts actual point generation: only points that are in this window will be
     /// generated.
     time_window: TimeWindopt,
+    /// User annotations on the time axis of the charts, sorted by time.
+    annotations: Vec<marker::Marker>,
 }
 impl Default for Charts {
     fn default() -> Self {
@@ -302,6 +397,7 @@ impl Charts {
     pub fn new() -> Self {
         Self {
             time_window: TimeWindopt::new(None, None),
+            annotations: vec![],
         }
     }
 
@@ -320,7 +416,10 @@ impl Charts {
         // Exhaustive deconstruction to create errors when new fields are added to `Self`.
         //
         // DO NOT USE `..` here.
-        let Self { time_window } = self;
+        let Self {
+            time_window,
+            annotations,
+        } = self;
 
         match time_window {
             Range {
@@ -339,6 +438,17 @@ impl Charts {
             _ => (),
         }
 
+        for annotation in annotations {
+            if annotation.label.trim().is_empty() {
+                let error = error_mut!();
+                error.push_str(&format!(
+                    "⚠ illegal annotation at {}\n\
+                    ↪ annotations cannot have an empty label",
+                    annotation.time,
+                ));
+            }
+        }
+
         error
     }
 
@@ -358,16 +468,43 @@ impl Charts {
             .unwrap_or_else(time::SinceStart::zero, || current_time)
     }
 
+    /// User annotations, sorted by time.
+    pub fn annotations(&self) -> &[marker::Marker] {
+        &self.annotations
+    }
+    /// Adds an annotation.
+    pub fn add_annotation(&mut self, annotation: marker::Marker) {
+        let index = self
+            .annotations
+            .partition_point(|other| other.time <= annotation.time);
+        self.annotations.insert(index, annotation)
+    }
+    /// Removes an annotation, `None` if the index is illegal.
+    pub fn remove_annotation(&mut self, index: usize) -> Option<marker::Marker> {
+        if index < self.annotations.len() {
+            Some(self.annotations.remove(index))
+        } else {
+            None
+        }
+    }
+
     /// Overwrites itself with a new value.
     ///
-    /// Returns `true` if a reload of the points is necessary.
-    pub fn overwrite(&mut self, Self { time_window }: Self) -> bool {
+    /// Returns `true` if a reload of the points is necessary, annotations do not impact points.
+    pub fn overwrite(
+        &mut self,
+        Self {
+            time_window,
+            annotations,
+        }: Self,
+    ) -> bool {
         let mut reload = false;
 
         if self.time_window != time_window {
             self.time_window = time_window;
             reload = true
         }
+        self.annotations = annotations;
 
         reload
     }
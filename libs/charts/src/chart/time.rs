@@ -21,6 +21,8 @@
 
 prelude! {}
 
+#[cfg(any(test, feature = "server"))]
+pub mod downsample;
 pub mod size;
 
 pub use size::TimeSize;
@@ -47,11 +49,12 @@ impl TimeChart {
         filters: &mut Filters,
         init: bool,
         resolution: chart::settings::Resolution,
+        downsampling: chart::settings::Downsampling,
         time_windopt: &TimeWindopt,
     ) -> Res<Option<Points>> {
         match self {
             Self::Size(time_size_chart) => {
-                time_size_chart.new_points(filters, init, resolution, time_windopt)
+                time_size_chart.new_points(filters, init, resolution, downsampling, time_windopt)
             }
         }
     }
@@ -0,0 +1,480 @@
+/*<LICENSE>
+    This file is part of Memthol.
+
+    Copyright (C) 2020 OCamlPro.
+
+    Memthol is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Memthol is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Memthol.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Downsampling of time points.
+//!
+//! Events are aggregated in buckets, one for each pixel of the x-axis of the chart. The
+//! [`Downsampling`] strategy of the chart decides which points represent a bucket.
+//!
+//! [`Downsampling`]: ../../settings/enum.Downsampling.html (The Downsampling enum)
+
+prelude! {}
+
+use chart::settings::Downsampling;
+use point::{Size, TimeSizePoints};
+
+/// A new value for a line, at some point in time.
+type Sample = (time::SinceStart, uid::Line, Size);
+
+/// Events falling in the same bucket.
+#[derive(Debug, Clone)]
+struct Bucket {
+    /// Index of the bucket.
+    index: u64,
+    /// New values of the lines in the bucket, in chronological order.
+    samples: Vec<Sample>,
+}
+impl Bucket {
+    /// Constructor.
+    fn new(index: u64) -> Self {
+        Self {
+            index,
+            samples: Vec::with_capacity(16),
+        }
+    }
+
+    /// Time of the first sample.
+    fn start(&self) -> Option<time::SinceStart> {
+        self.samples.first().map(|(time, _, _)| *time)
+    }
+    /// Time of the last sample.
+    fn end(&self) -> Option<time::SinceStart> {
+        self.samples.last().map(|(time, _, _)| *time)
+    }
+
+    /// Lines that change in the bucket.
+    fn lines(&self) -> BTSet<uid::Line> {
+        self.samples.iter().map(|(_, line, _)| *line).collect()
+    }
+}
+
+/// Downsamples the events of a time chart.
+///
+/// Events must be [`push`]ed in chronological order, the sampler produces points whenever a bucket
+/// is complete. Calling [`flush`] produces the points for the buckets that are not complete yet.
+///
+/// [`push`]: #method.push (The push method)
+/// [`flush`]: #method.flush (The flush method)
+pub struct Sampler {
+    /// Downsampling strategy.
+    mode: Downsampling,
+    /// Start of the first bucket.
+    lbound: time::SinceStart,
+    /// Width of the buckets in nanoseconds.
+    span: u128,
+    /// Values of all the lines before the first bucket that was not processed yet.
+    base: BTMap<uid::Line, Size>,
+    /// Bucket being filled.
+    current: Option<Bucket>,
+    /// Complete bucket waiting for the next one, only used by LTTB.
+    pending: Option<Bucket>,
+    /// Time and values of all the lines of the last point produced, only used by LTTB.
+    anchor: (time::SinceStart, BTMap<uid::Line, Size>),
+}
+
+impl Sampler {
+    /// Constructor.
+    ///
+    /// - `width` is the width of the chart in pixels;
+    /// - `last` is the last point produced so far, which must have a value for all the lines.
+    pub fn new(
+        mode: Downsampling,
+        time_window: &TimeWindow,
+        width: u32,
+        last: &Point<time::SinceStart, Size>,
+    ) -> Self {
+        let window = (time_window.ubound - time_window.lbound).as_nanos();
+        let span = std::cmp::max(1, window / std::cmp::max(1, width as u128));
+        Self {
+            mode,
+            lbound: time_window.lbound,
+            span,
+            base: last.vals.map.clone(),
+            current: None,
+            pending: None,
+            anchor: (last.key, last.vals.map.clone()),
+        }
+    }
+
+    /// Index of the bucket some time falls in.
+    fn index_of(&self, time: time::SinceStart) -> u64 {
+        if time <= self.lbound {
+            0
+        } else {
+            ((time - self.lbound).as_nanos() / self.span) as u64
+        }
+    }
+
+    /// Registers the new values of some lines at some time.
+    pub fn push(
+        &mut self,
+        time: time::SinceStart,
+        vals: &[(uid::Line, Size)],
+        points: &mut TimeSizePoints,
+    ) {
+        let index = self.index_of(time);
+        if self
+            .current
+            .as_ref()
+            .map(|bucket| bucket.index != index)
+            .unwrap_or(false)
+        {
+            if let Some(bucket) = self.current.take() {
+                self.close(bucket, points)
+            }
+        }
+        self.current
+            .get_or_insert_with(|| Bucket::new(index))
+            .samples
+            .extend(vals.iter().map(|(line, val)| (time, *line, *val)))
+    }
+
+    /// Produces the points for the buckets that are not complete yet.
+    pub fn flush(&mut self, points: &mut TimeSizePoints) {
+        let current = self.current.take();
+        match self.mode {
+            Downsampling::MinMax | Downsampling::Last => {
+                if let Some(bucket) = current {
+                    self.envelope(bucket, points)
+                }
+            }
+            Downsampling::Lttb => {
+                if let Some(pending) = self.pending.take() {
+                    self.lttb(pending, current.as_ref(), points)
+                }
+                if let Some(bucket) = current {
+                    self.lttb(bucket, None, points)
+                }
+            }
+        }
+    }
+
+    /// Produces the points for a complete bucket.
+    fn close(&mut self, bucket: Bucket, points: &mut TimeSizePoints) {
+        match self.mode {
+            Downsampling::MinMax | Downsampling::Last => self.envelope(bucket, points),
+            Downsampling::Lttb => {
+                if let Some(pending) = self.pending.take() {
+                    self.lttb(pending, Some(&bucket), points)
+                }
+                self.pending = Some(bucket)
+            }
+        }
+    }
+
+    /// Pushes a point, unless it is the same as the last point.
+    fn push_point(
+        points: &mut TimeSizePoints,
+        key: time::SinceStart,
+        map: BTMap<uid::Line, Size>,
+    ) {
+        if map.is_empty() {
+            return;
+        }
+        if let Some(last) = points.last() {
+            if last.key == key && last.vals.map == map {
+                return;
+            }
+        }
+        points.push(Point::new(key, PointVal { map }))
+    }
+
+    /// Min/max and last downsampling of a bucket.
+    ///
+    /// Produces, for the lines that change in the bucket, a point with their value when the bucket
+    /// starts, followed by their minimum and maximum values in min/max mode, and their last value.
+    fn envelope(&mut self, bucket: Bucket, points: &mut TimeSizePoints) {
+        let (start, end) = match (bucket.start(), bucket.end()) {
+            (Some(start), Some(end)) => (start, end),
+            _ => return,
+        };
+
+        let (mut entry, mut min, mut max, mut last) =
+            (BTMap::new(), BTMap::new(), BTMap::new(), BTMap::new());
+        for (_, line, val) in bucket.samples {
+            let before = self.base.get(&line).cloned().unwrap_or_else(|| 0u64.into());
+            entry.entry(line).or_insert(before);
+            let min = min.entry(line).or_insert(before);
+            if val < *min {
+                *min = val
+            }
+            let max = max.entry(line).or_insert(before);
+            if val > *max {
+                *max = val
+            }
+            last.insert(line, val);
+        }
+
+        self.base.extend(last.iter().map(|(line, val)| (*line, *val)));
+
+        Self::push_point(points, start, entry);
+        if self.mode == Downsampling::MinMax {
+            Self::push_point(points, start, min);
+            Self::push_point(points, start, max);
+        }
+        Self::push_point(points, end, last);
+    }
+
+    /// Largest-triangle-three-buckets downsampling of a bucket.
+    ///
+    /// Selects the time in the bucket that forms the largest triangle with the last point produced
+    /// and the average of the next bucket, or the last values of the bucket if there is no next
+    /// bucket. Only the lines that change in the bucket are taken into account.
+    fn lttb(&mut self, bucket: Bucket, next: Option<&Bucket>, points: &mut TimeSizePoints) {
+        let lines = bucket.lines();
+        if lines.is_empty() {
+            return;
+        }
+        let to_f64 = |val: Size| val.size as f64;
+
+        // Values of the lines at the end of the bucket.
+        let mut end_vals: BTMap<uid::Line, Size> = lines
+            .iter()
+            .map(|line| (*line, self.base.get(line).cloned().unwrap_or_else(|| 0u64.into())))
+            .collect();
+        for (_, line, val) in &bucket.samples {
+            end_vals.insert(*line, *val);
+        }
+
+        // Third vertex of the triangle.
+        let (c_time, c_vals) = match next {
+            Some(next) if !next.samples.is_empty() => {
+                let mut vals = end_vals.clone();
+                let mut sums: BTMap<uid::Line, f64> =
+                    lines.iter().map(|line| (*line, 0.0)).collect();
+                let mut time_sum = 0.0;
+                for (time, line, val) in &next.samples {
+                    if let Some(prev) = vals.get_mut(line) {
+                        *prev = *val
+                    }
+                    time_sum += time.as_secs_f64();
+                    for (line, sum) in sums.iter_mut() {
+                        *sum += vals.get(line).cloned().map(to_f64).unwrap_or(0.0)
+                    }
+                }
+                let count = next.samples.len() as f64;
+                for sum in sums.values_mut() {
+                    *sum /= count
+                }
+                (time_sum / count, sums)
+            }
+            _ => {
+                let time = bucket.end().map(|time| time.as_secs_f64()).unwrap_or(0.0);
+                let vals = end_vals
+                    .iter()
+                    .map(|(line, val)| (*line, to_f64(*val)))
+                    .collect();
+                (time, vals)
+            }
+        };
+
+        // First vertex of the triangle.
+        let a_time = self.anchor.0.as_secs_f64();
+        let a_val = |line: &uid::Line| self.anchor.1.get(line).cloned().map(to_f64).unwrap_or(0.0);
+
+        let mut vals: BTMap<uid::Line, Size> = lines
+            .iter()
+            .map(|line| (*line, self.base.get(line).cloned().unwrap_or_else(|| 0u64.into())))
+            .collect();
+        let mut best: Option<(f64, time::SinceStart, BTMap<uid::Line, Size>)> = None;
+        for (idx, (time, line, val)) in bucket.samples.iter().enumerate() {
+            vals.insert(*line, *val);
+            // Only consider the values once all the samples for `time` are in.
+            if bucket
+                .samples
+                .get(idx + 1)
+                .map(|(next_time, _, _)| next_time == time)
+                .unwrap_or(false)
+            {
+                continue;
+            }
+            let b_time = time.as_secs_f64();
+            let area: f64 = vals
+                .iter()
+                .map(|(line, val)| {
+                    let (a, b, c) = (
+                        a_val(line),
+                        to_f64(*val),
+                        c_vals.get(line).cloned().unwrap_or(0.0),
+                    );
+                    ((a_time - c_time) * (b - a) - (a_time - b_time) * (c - a)).abs()
+                })
+                .sum();
+            if best
+                .as_ref()
+                .map(|(best_area, _, _)| area > *best_area)
+                .unwrap_or(true)
+            {
+                best = Some((area, *time, vals.clone()))
+            }
+        }
+
+        let (time, best_vals) = match best {
+            Some((_, time, vals)) => (time, vals),
+            None => return,
+        };
+
+        // The point has the lines whose value changed since the last point produced.
+        let mut map = BTMap::new();
+        for (line, base_val) in &self.base {
+            let val = best_vals.get(line).unwrap_or(base_val);
+            if self.anchor.1.get(line) != Some(val) {
+                map.insert(*line, *val);
+            }
+        }
+        for (line, val) in best_vals.iter() {
+            if !self.base.contains_key(line) && self.anchor.1.get(line) != Some(val) {
+                map.insert(*line, *val);
+            }
+        }
+
+        for (line, val) in map.iter() {
+            self.anchor.1.insert(*line, *val);
+        }
+        self.anchor.0 = time;
+        self.base.extend(end_vals);
+
+        Self::push_point(points, time, map)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const LINE: uid::Line = uid::Line::Everything;
+
+    /// Time from an amount of milliseconds.
+    fn at(millis: u64) -> time::SinceStart {
+        std::time::Duration::from_millis(millis).into()
+    }
+
+    /// Sampler over `[0, 1s]` with 10 pixels, *i.e.* buckets of 100ms, starting with value `0`.
+    fn new_sampler(mode: Downsampling) -> Sampler {
+        let mut map = BTMap::new();
+        map.insert(LINE, Size::new(0u64));
+        let last = Point::new(at(0), PointVal { map });
+        Sampler::new(mode, &Range::new(at(0), at(1_000)), 10, &last)
+    }
+
+    /// Pushes some `(millis, value)` events.
+    fn push(sampler: &mut Sampler, events: &[(u64, u64)], points: &mut TimeSizePoints) {
+        for (millis, val) in events {
+            sampler.push(at(*millis), &[(LINE, Size::new(*val))], points)
+        }
+    }
+
+    /// Values of the line in some points.
+    fn vals(points: &TimeSizePoints) -> Vec<u64> {
+        points
+            .iter()
+            .filter_map(|point| point.vals.map.get(&LINE).map(|val| val.size))
+            .collect()
+    }
+
+    #[test]
+    fn min_max_keeps_spikes() {
+        let events = [(110, 3), (120, 100), (130, 2), (190, 4), (250, 5)];
+
+        let mut sampler = new_sampler(Downsampling::MinMax);
+        let mut points = vec![];
+        push(&mut sampler, &events, &mut points);
+        sampler.flush(&mut points);
+        assert!(
+            vals(&points).contains(&100),
+            "spike lost: {:?}",
+            vals(&points)
+        );
+
+        let mut sampler = new_sampler(Downsampling::Last);
+        let mut points = vec![];
+        push(&mut sampler, &events, &mut points);
+        sampler.flush(&mut points);
+        assert!(
+            !vals(&points).contains(&100),
+            "spike kept: {:?}",
+            vals(&points)
+        );
+    }
+
+    #[test]
+    fn last_keeps_last_value_of_buckets() {
+        let mut sampler = new_sampler(Downsampling::Last);
+        let mut points = vec![];
+        push(
+            &mut sampler,
+            &[(110, 10), (150, 20), (190, 30), (210, 5), (290, 7)],
+            &mut points,
+        );
+        sampler.flush(&mut points);
+
+        let keys: Vec<_> = points.iter().map(|point| point.key).collect();
+        assert_eq!(keys, vec![at(110), at(190), at(210), at(290)]);
+        // Value when the bucket starts, then last value of the bucket.
+        assert_eq!(vals(&points), vec![0, 30, 30, 7]);
+    }
+
+    #[test]
+    fn lttb_one_point_per_bucket() {
+        let mut sampler = new_sampler(Downsampling::Lttb);
+        let mut points = vec![];
+        let events: Vec<(u64, u64)> = (0..100).map(|n| (n * 10 + 5, (n * 37) % 11)).collect();
+        push(&mut sampler, &events, &mut points);
+        sampler.flush(&mut points);
+
+        assert!(!points.is_empty());
+        let mut buckets = BTSet::new();
+        for point in &points {
+            let bucket = point.key.as_nanos() / at(100).as_nanos();
+            assert!(
+                buckets.insert(bucket),
+                "several points in bucket #{}",
+                bucket
+            )
+        }
+    }
+
+    #[test]
+    fn flush_produces_incomplete_bucket() {
+        for mode in Downsampling::all() {
+            let mut sampler = new_sampler(mode);
+            let mut points = vec![];
+            push(&mut sampler, &[(310, 4), (320, 6)], &mut points);
+            assert!(
+                points.is_empty(),
+                "{}: bucket produced before flush",
+                mode.desc()
+            );
+
+            sampler.flush(&mut points);
+            assert!(!points.is_empty(), "{}: bucket not produced", mode.desc());
+            for point in &points {
+                assert!(at(310) <= point.key && point.key <= at(320))
+            }
+            if mode != Downsampling::Lttb {
+                assert_eq!(vals(&points).last(), Some(&6), "{}", mode.desc());
+            }
+
+            // Nothing left to flush.
+            let len = points.len();
+            sampler.flush(&mut points);
+            assert_eq!(points.len(), len, "{}: flushed twice", mode.desc());
+        }
+    }
+}
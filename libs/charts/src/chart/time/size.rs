@@ -23,6 +23,9 @@ prelude! {}
 
 use point::{Size, TimeSizePoints};
 
+#[cfg(any(test, feature = "server"))]
+use super::downsample::Sampler;
+
 /// Initial size value.
 const INIT_SIZE_VALUE: u64 = 0;
 
@@ -59,9 +62,10 @@ impl TimeSize {
         filters: &mut Filters,
         init: bool,
         resolution: chart::settings::Resolution,
+        downsampling: chart::settings::Downsampling,
         time_windopt: &TimeWindopt,
     ) -> Res<Option<Points>> {
-        self.do_it(filters, init, resolution, time_windopt)
+        self.do_it(filters, init, resolution, downsampling, time_windopt)
             .map(|opt| opt.map(Points::from))
     }
 
@@ -99,6 +103,7 @@ impl TimeSize {
         filters: &mut Filters,
         init: bool,
         resolution: chart::settings::Resolution,
+        downsampling: chart::settings::Downsampling,
         time_windopt: &TimeWindopt,
     ) -> Res<Option<TimeSizePoints>> {
         let data = data::get()?;
@@ -108,11 +113,21 @@ impl TimeSize {
         }
 
         let time_window = time_windopt.to_time_window(|| *data.current_time());
-        let min_time_spacing = data.current_time().clone() / (resolution.width / 5);
 
         debug_assert!(self.points.is_empty());
+        // Position of the first event to iterate from, when the time index lets us skip the events
+        // before the time window.
+        let mut resume = None;
         if init {
             self.reset(filters);
+            if let Some(lbound) = time_windopt.lbound {
+                if let Some((sizes, since)) = filters.size_before(&data, lbound)? {
+                    for (line, size) in sizes {
+                        *self.size.get_mut(line)? = size.into()
+                    }
+                    resume = Some(since)
+                }
+            }
         }
 
         self.points.push(Point::new(
@@ -125,10 +140,17 @@ impl TimeSize {
             }),
             self.size.clone(),
         ));
+        // The events skipped are below the time window, make sure the last point is generated.
+        if resume.is_some() {
+            self.last_time_stamp = time_windopt.lbound
+        }
+        let last = resume.unwrap_or_else(|| self.last.clone());
         let points = &mut self.points;
 
-        let (last_time_stamp, last_size, last) =
-            (&mut self.last_time_stamp, &mut self.size, self.last.clone());
+        let (last_time_stamp, last_size) = (&mut self.last_time_stamp, &mut self.size);
+
+        // Created on the first event inside the time window.
+        let mut sampler: Option<Sampler> = None;
 
         macro_rules! update {
             ($f_uid:expr, _, last_size => |ref mut $val:pat| $action:expr) => {{
@@ -245,97 +267,23 @@ impl TimeSize {
 
                 // Inside the time-window.
                 base::RangeCmp::Inside => {
-                    let adjusted_timestamp = if let Some(last_time_stamp) = last_time_stamp.as_mut()
-                    {
-                        if timestamp - *last_time_stamp < min_time_spacing {
-                            last_time_stamp.clone()
-                        } else {
-                            *last_time_stamp = timestamp;
-                            timestamp
-                        }
-                    } else {
-                        *last_time_stamp = Some(timestamp);
-                        timestamp
-                    };
-
-                    let (vals, repeat_previous) = if let Some(last) = points.last_mut() {
-                        if last.key == adjusted_timestamp {
-                            (&mut last.vals.map, true)
-                        } else {
-                            let mut repeat = Point::new(adjusted_timestamp, PointVal::empty());
-                            let (last_val, last_everything_val) = (
-                                last_size
-                                    .map
-                                    .get(&f_uid)
-                                    .ok_or_else(|| format!("unexpected filter uid `{}`", f_uid))?
-                                    .clone(),
-                                last_size
-                                    .map
-                                    .get(&uid::Line::Everything)
-                                    .ok_or_else(|| format!("unexpected filter uid `{}`", f_uid))?
-                                    .clone(),
-                            );
-                            let prev = repeat.vals.map.insert(f_uid, last_val);
-                            debug_assert_eq!(prev, None);
-                            let prev = repeat
-                                .vals
-                                .map
-                                .insert(uid::Line::Everything, last_everything_val);
-                            debug_assert_eq!(prev, None);
-
-                            let new = repeat.clone();
-
-                            points.push(repeat);
-
-                            points.push(new);
+                    let sampler = match sampler.as_mut() {
+                        Some(sampler) => sampler,
+                        None => {
                             let last = points
-                                .last_mut()
-                                .expect("`last_mut` after `push` cannot fail");
-                            (&mut last.vals.map, true)
+                                .last()
+                                .ok_or("no initial point before the time window")?;
+                            sampler.get_or_insert(Sampler::new(
+                                downsampling,
+                                &time_window,
+                                resolution.width,
+                                last,
+                            ))
                         }
-                    } else {
-                        points.push(Point::new(adjusted_timestamp, last_size.clone()));
-                        let last = points
-                            .last_mut()
-                            .expect("`last_mut` after `push` cannot fail");
-                        (&mut last.vals.map, false)
                     };
 
                     let mut underflow = None;
 
-                    update! {
-                        f_uid, vals => |ref mut val| if add {
-                            val.size += size as u64
-                        } else {
-                            if val.size < size as u64 {
-                                underflow = Some(val.size)
-                            }
-                            val.size -= size as u64
-                        }
-                    }
-
-                    if let Some(prev) = underflow {
-                        show_last_two_points!(add, f_uid => size);
-                        bail!("underflow on {} (map)", prev)
-                    }
-
-                    if repeat_previous && points.len() >= 2 {
-                        let penultimate = points.len() - 2;
-                        if points[penultimate].vals.map.get(&f_uid).is_none() {
-                            let prev = points[penultimate].vals.map.insert(
-                                f_uid,
-                                last_size
-                                    .map
-                                    .get(&f_uid)
-                                    .ok_or_else(|| format!("unexpected filter uid `{}`", f_uid))?
-                                    .clone(),
-                            );
-                            debug_assert_eq!(prev, None)
-                        }
-                    }
-
-                    let mut underflow = None;
-
                     update! {
                         f_uid, _, last_size => |ref mut val| if add {
                             val.size += size as u64
@@ -353,11 +301,24 @@ impl TimeSize {
                         bail!("underflow on {} (last_size)", prev)
                     }
 
+                    *last_time_stamp = Some(timestamp);
+                    sampler.push(
+                        timestamp,
+                        &[
+                            (f_uid, *last_size.get(f_uid)?),
+                            (uid::Line::Everything, *last_size.get_everything_val()?),
+                        ],
+                        points,
+                    );
+
                     Ok(true)
                 }
 
                 // Above the range: generate the very last point and early exit.
                 base::RangeCmp::Above => {
+                    if let Some(sampler) = sampler.as_mut() {
+                        sampler.flush(points)
+                    }
                     let end_time = time_window.ubound;
                     if let Some(last) = points.last() {
                         if last.key < end_time {
@@ -371,6 +332,10 @@ impl TimeSize {
             }
         })?;
 
+        if let Some(sampler) = sampler.as_mut() {
+            sampler.flush(points)
+        }
+
         if let Some(ts) = last_time_stamp {
             if *ts != time_window.ubound {
                 let point = Point::new(time_window.ubound, self.size.clone());
@@ -21,6 +21,11 @@
 
 prelude! {}
 
+pub mod budget;
+pub mod cache;
+pub mod index;
+pub mod stats;
+pub mod traces;
 mod watcher;
 
 pub use watcher::Watcher;
@@ -89,6 +94,29 @@ pub fn start(target: impl AsRef<std::path::Path>) -> Res<()> {
     Ok(())
 }
 
+/// Loads a memtrace CTF file, or the most recent one in a directory, synchronously.
+///
+/// Unlike [`start`], does not spawn the file watcher daemon, meaning the data is loaded once and
+/// for all. Used by the subcommands that do not start the UI.
+///
+/// [`start`]: fn.start.html (The start function)
+pub fn load(target: impl AsRef<std::path::Path>) -> Res<()> {
+    let target = target.as_ref();
+    let file = if target.is_file() {
+        target.to_path_buf()
+    } else if target.is_dir() {
+        Watcher::latest_ctf_file(target)?.ok_or_else(|| {
+            format!(
+                "directory `{}` does not contain any memtrace CTF file",
+                target.display()
+            )
+        })?
+    } else {
+        bail!("expected a memtrace CTF file or directory, got `{}`", target.display())
+    };
+    Watcher::ctf_run(file)
+}
+
 lazy_static! {
     /// Progress indicator, used during loading.
     static ref PROG: sync::RwLock<Option<LoadInfo>> = sync::RwLock::new(Some(LoadInfo::unknown()));
@@ -205,6 +233,8 @@ pub struct Data {
     uid_map: uid::AllocMap<Alloc>,
     /// Map from time-of-death to allocation UIDs.
     tod_map: BTMap<time::SinceStart, BTSet<uid::Alloc>>,
+    /// Time-bucketed index of the allocation events.
+    time_index: index::TimeIndex,
     /// Time of the latest diff.
     current_time: time::SinceStart,
     /// Statistics.
@@ -225,6 +255,7 @@ impl Data {
             init: None,
             uid_map: uid::AllocMap::new(),
             tod_map: BTMap::new(),
+            time_index: index::TimeIndex::new(),
             current_time: time::SinceStart::zero(),
             stats: None,
         }
@@ -292,6 +323,30 @@ impl Data {
         self.uid_map.iter()
     }
 
+    /// Iterates over the allocations live at some time.
+    ///
+    /// Relies on allocations being sorted by time of creation, see
+    /// [`invariants::uid_order_is_toc_order`].
+    ///
+    /// [`invariants::uid_order_is_toc_order`]: invariants/fn.uid_order_is_toc_order.html
+    /// (The uid_order_is_toc_order function)
+    pub fn iter_live_at(&self, time: time::SinceStart) -> impl Iterator<Item = &Alloc> {
+        self.uid_map
+            .iter()
+            .take_while(move |alloc| alloc.toc <= time)
+            .filter(move |alloc| alloc.tod.map(|tod| tod > time).unwrap_or(true))
+    }
+
+    /// Iterates over the times of death, and the number of allocations dying at that time.
+    pub fn iter_tods(&self) -> impl Iterator<Item = (time::SinceStart, usize)> + '_ {
+        self.tod_map.iter().map(|(tod, uids)| (*tod, uids.len()))
+    }
+
+    /// Time-bucketed index of the allocation events.
+    pub fn time_index(&self) -> &index::TimeIndex {
+        &self.time_index
+    }
+
     /// True if there are any new events since some timestamp.
     pub fn has_new_stuff_since(&self, time: Option<(uid::Alloc, time::SinceStart)>) -> bool {
         if let Some((uid, tod)) = time {
@@ -426,6 +481,7 @@ impl Data {
         self.init = Some(init);
         self.uid_map.clear();
         self.tod_map.clear();
+        self.time_index.clear();
         self.current_time = time::SinceStart::zero();
     }
 
@@ -463,6 +519,8 @@ impl Data {
             )
         }
 
+        self.time_index.add_new(alloc.toc, uid);
+
         if let Some(tod) = alloc.tod.clone() {
             self.add_dead(tod, uid.clone())?
         }
@@ -484,6 +542,7 @@ impl Data {
                 uid
             )
         }
+        self.time_index.add_dead(timestamp, uid);
         Ok(())
     }
 
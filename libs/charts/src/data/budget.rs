@@ -0,0 +1,387 @@
+/*<LICENSE>
+    This file is part of Memthol.
+
+    Copyright (C) 2020 OCamlPro.
+
+    Memthol is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Memthol is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Memthol.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Memory budgets, checked against a trace.
+//!
+//! A budget is a list of assertions, one per line, of the form `<metric> [<loc>] <= <bound>`.
+//! Empty lines and lines starting with `#` are ignored. Bounds are integers with an optional
+//! `k`, `M`, `G` (powers of 1000) or `Ki`, `Mi`, `Gi` (powers of 1024) suffix, optionally followed
+//! by `B`. The metrics are
+//!
+//! - `peak_live`: highest size of the allocations alive at the same time, in bytes;
+//! - `total_bytes`: total size of the allocations, in bytes;
+//! - `live_bytes`: size of the allocations alive at the end of the run, in bytes;
+//! - `alloc_count`: number of allocations;
+//! - `site_bytes <loc>`: total size of the allocations whose allocation site matches `<loc>`;
+//! - `site_live_bytes <loc>`: same as `site_bytes`, for allocations alive at the end of the run;
+//! - `site_count <loc>`: number of allocations whose allocation site matches `<loc>`;
+//! - `site_live_count <loc>`: same as `site_count`, for allocations alive at the end of the run.
+//!
+//! where `<loc>` uses the syntax of location filters, *e.g.* `typing/btype.ml`,
+//! `#"^typing/.*"#` or `set.ml:[120, 130]`.
+//!
+//! ```rust
+//! use charts::data::budget::Budget;
+//! let budget = Budget::new("# Regression gate for the typer.\n\
+//!     peak_live <= 50MB\n\
+//!     site_bytes #\"^typing/.*\"# <= 2Gi\n\
+//!     site_live_count set.ml:[120, 130] <= 0\n\
+//! ").unwrap();
+//! assert_eq!(budget.len(), 3);
+//! assert_eq!(budget.to_string(), "\
+//!     peak_live <= 50000000\n\
+//!     site_bytes #\"^typing/.*\"# <= 2147483648\n\
+//!     site_live_count set.ml : [120, 130] <= 0\
+//! ");
+//! ```
+
+prelude! {}
+
+use filter::{loc::LocSpec, FilterExt};
+
+/// A metric constrained by a budget.
+#[derive(Debug, Clone)]
+pub enum Metric {
+    /// Highest size of the allocations alive at the same time, in bytes.
+    PeakLive,
+    /// Total size of the allocations, in bytes.
+    TotalBytes,
+    /// Size of the allocations alive at the end of the run, in bytes.
+    LiveBytes,
+    /// Number of allocations.
+    AllocCount,
+    /// Total size of the allocations from some sites, in bytes.
+    SiteBytes(LocSpec),
+    /// Size of the allocations from some sites alive at the end of the run, in bytes.
+    SiteLiveBytes(LocSpec),
+    /// Number of allocations from some sites.
+    SiteCount(LocSpec),
+    /// Number of allocations from some sites alive at the end of the run.
+    SiteLiveCount(LocSpec),
+}
+
+impl Metric {
+    /// Parses a metric, `loc` is everything between the metric's name and the bound.
+    fn new(name: &str, loc: &str) -> Res<Self> {
+        let site = |metric: fn(LocSpec) -> Self| {
+            if loc.is_empty() {
+                bail!("metric `{}` expects a location", name)
+            }
+            LocSpec::new(loc).map(metric)
+        };
+        let no_site = |metric: Self| {
+            if !loc.is_empty() {
+                bail!("metric `{}` does not take a location", name)
+            }
+            Ok(metric)
+        };
+        match name {
+            "peak_live" => no_site(Self::PeakLive),
+            "total_bytes" => no_site(Self::TotalBytes),
+            "live_bytes" => no_site(Self::LiveBytes),
+            "alloc_count" => no_site(Self::AllocCount),
+            "site_bytes" => site(Self::SiteBytes),
+            "site_live_bytes" => site(Self::SiteLiveBytes),
+            "site_count" => site(Self::SiteCount),
+            "site_live_count" => site(Self::SiteLiveCount),
+            _ => bail!("unknown metric `{}`", name),
+        }
+    }
+
+    /// Location spec of the metric, if any.
+    fn loc(&self) -> Option<&LocSpec> {
+        match self {
+            Self::PeakLive | Self::TotalBytes | Self::LiveBytes | Self::AllocCount => None,
+            Self::SiteBytes(loc)
+            | Self::SiteLiveBytes(loc)
+            | Self::SiteCount(loc)
+            | Self::SiteLiveCount(loc) => Some(loc),
+        }
+    }
+
+    /// Name of the metric.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::PeakLive => "peak_live",
+            Self::TotalBytes => "total_bytes",
+            Self::LiveBytes => "live_bytes",
+            Self::AllocCount => "alloc_count",
+            Self::SiteBytes(_) => "site_bytes",
+            Self::SiteLiveBytes(_) => "site_live_bytes",
+            Self::SiteCount(_) => "site_count",
+            Self::SiteLiveCount(_) => "site_live_count",
+        }
+    }
+
+    /// Value of the metric for some data.
+    ///
+    /// The statistics must have been computed over the same data.
+    fn eval(&self, data: &data::Data, stats: &super::stats::TraceStats) -> u64 {
+        let site_fold = |loc: &LocSpec, live_only: bool, value: fn(&Alloc) -> u64| {
+            data.iter_allocs()
+                .filter(|alloc| !live_only || alloc.tod.is_none())
+                .filter(|alloc| {
+                    alloc.alloc_site_do(|site| site.map(|site| loc.apply(site)).unwrap_or(false))
+                })
+                .map(value)
+                .sum()
+        };
+        match self {
+            Self::PeakLive => stats.peak_live_bytes,
+            Self::TotalBytes => stats.total_bytes,
+            Self::LiveBytes => stats.live_bytes,
+            Self::AllocCount => stats.stats.alloc_count as u64,
+            Self::SiteBytes(loc) => site_fold(loc, false, |alloc| alloc.real_size as u64),
+            Self::SiteLiveBytes(loc) => site_fold(loc, true, |alloc| alloc.real_size as u64),
+            Self::SiteCount(loc) => site_fold(loc, false, |_| 1),
+            Self::SiteLiveCount(loc) => site_fold(loc, true, |_| 1),
+        }
+    }
+}
+
+base::implement! {
+    impl Display for Metric {
+        |&self, fmt| {
+            write!(fmt, "{}", self.name())?;
+            if let Some(loc) = self.loc() {
+                write!(fmt, " {}", loc)?
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Parses a bound.
+///
+/// ```rust
+/// use charts::data::budget::parse_bound;
+/// assert_eq!(parse_bound("42").unwrap(), 42);
+/// assert_eq!(parse_bound("7 kB").unwrap(), 7_000);
+/// assert_eq!(parse_bound("3M").unwrap(), 3_000_000);
+/// assert_eq!(parse_bound("2GiB").unwrap(), 2 * 1024 * 1024 * 1024);
+/// assert!(parse_bound("2 bytes").is_err());
+/// ```
+pub fn parse_bound(s: &str) -> Res<u64> {
+    let s = s.trim();
+    let digits_end = s
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(s.len());
+    let (digits, suffix) = s.split_at(digits_end);
+    let value: u64 = digits
+        .parse()
+        .chain_err(|| format!("illegal bound `{}`, expected an integer", s))?;
+    let suffix = suffix.trim();
+    let suffix = suffix.strip_suffix('B').unwrap_or(suffix);
+    let factor: u64 = match suffix {
+        "" => 1,
+        "k" | "K" => 1_000,
+        "M" => 1_000_000,
+        "G" => 1_000_000_000,
+        "Ki" => 1 << 10,
+        "Mi" => 1 << 20,
+        "Gi" => 1 << 30,
+        _ => bail!("illegal bound `{}`, unknown suffix `{}`", s, suffix),
+    };
+    value
+        .checked_mul(factor)
+        .ok_or_else(|| format!("illegal bound `{}`, value is too large", s).into())
+}
+
+/// An assertion from a budget.
+#[derive(Debug, Clone)]
+pub struct Assertion {
+    /// Line of the assertion in the budget, starting at `1`.
+    pub line: usize,
+    /// Metric constrained.
+    pub metric: Metric,
+    /// Upper bound on the metric.
+    pub bound: u64,
+}
+
+impl Assertion {
+    /// Parses an assertion.
+    fn new(line: usize, s: &str) -> Res<Self> {
+        let (lhs, bound) = if let Some(idx) = s.rfind("<=") {
+            (s[0..idx].trim(), &s[idx + 2..])
+        } else {
+            bail!("expected an assertion of the form `<metric> [<loc>] <= <bound>`")
+        };
+        let (name, loc) = match lhs.find(char::is_whitespace) {
+            Some(idx) => (&lhs[0..idx], lhs[idx..].trim()),
+            None => (lhs, ""),
+        };
+        Ok(Self {
+            line,
+            metric: Metric::new(name, loc)?,
+            bound: parse_bound(bound)?,
+        })
+    }
+}
+
+base::implement! {
+    impl Display for Assertion {
+        |&self, fmt| write!(fmt, "{} <= {}", self.metric, self.bound)
+    }
+}
+
+/// A memory budget.
+#[derive(Debug, Clone)]
+pub struct Budget {
+    /// Assertions of the budget.
+    assertions: Vec<Assertion>,
+}
+
+impl Budget {
+    /// Parses a budget.
+    pub fn new(s: &str) -> Res<Self> {
+        let mut assertions = vec![];
+        for (idx, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let assertion = Assertion::new(idx + 1, line)
+                .chain_err(|| format!("on line {}: `{}`", idx + 1, line))?;
+            assertions.push(assertion)
+        }
+        Ok(Self { assertions })
+    }
+
+    /// Reads and parses a budget file.
+    pub fn of_file(path: impl AsRef<std::path::Path>) -> Res<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .chain_err(|| format!("while reading budget file `{}`", path.display()))?;
+        Self::new(&content).chain_err(|| format!("while parsing budget file `{}`", path.display()))
+    }
+
+    /// Number of assertions in the budget.
+    pub fn len(&self) -> usize {
+        self.assertions.len()
+    }
+    /// True if the budget has no assertion.
+    pub fn is_empty(&self) -> bool {
+        self.assertions.is_empty()
+    }
+
+    /// Checks the budget against some data.
+    pub fn check(&self, data: &data::Data) -> Res<Report> {
+        let stats = super::stats::TraceStats::new(data, 0)?;
+        let outcomes = self
+            .assertions
+            .iter()
+            .map(|assertion| Outcome {
+                value: assertion.metric.eval(data, &stats),
+                assertion: assertion.clone(),
+            })
+            .collect();
+        Ok(Report {
+            trace: stats.stats.dump_dir,
+            outcomes,
+        })
+    }
+
+    /// Checks the budget against the global data.
+    pub fn check_global(&self) -> Res<Report> {
+        self.check(&*super::get()?)
+    }
+}
+
+base::implement! {
+    impl Display for Budget {
+        |&self, fmt| {
+            for (idx, assertion) in self.assertions.iter().enumerate() {
+                if idx > 0 {
+                    writeln!(fmt)?
+                }
+                assertion.fmt(fmt)?
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Outcome of an assertion.
+#[derive(Debug, Clone)]
+pub struct Outcome {
+    /// Assertion checked.
+    pub assertion: Assertion,
+    /// Actual value of the metric.
+    pub value: u64,
+}
+
+impl Outcome {
+    /// True if the metric is within its bound.
+    pub fn is_ok(&self) -> bool {
+        self.value <= self.assertion.bound
+    }
+}
+
+/// Report of a budget check.
+#[derive(Debug, Clone)]
+pub struct Report {
+    /// Trace the budget was checked against.
+    pub trace: std::path::PathBuf,
+    /// Outcome of each assertion, in the order of the budget.
+    pub outcomes: Vec<Outcome>,
+}
+
+impl Report {
+    /// Number of assertions that do not hold.
+    pub fn exceeded_count(&self) -> usize {
+        self.outcomes.iter().filter(|outcome| !outcome.is_ok()).count()
+    }
+    /// True if all assertions hold.
+    pub fn is_ok(&self) -> bool {
+        self.exceeded_count() == 0
+    }
+}
+
+base::implement! {
+    impl Display for Report {
+        |&self, fmt| {
+            writeln!(fmt, "|===| Budget check for `{}`", self.trace.display())?;
+            for outcome in &self.outcomes {
+                let assertion = &outcome.assertion;
+                if outcome.is_ok() {
+                    writeln!(fmt, "| ok       line {}: {}", assertion.line, assertion)?
+                } else {
+                    writeln!(
+                        fmt,
+                        "| EXCEEDED line {}: {}, actual value is {} ({} over budget)",
+                        assertion.line,
+                        assertion,
+                        outcome.value,
+                        outcome.value - assertion.bound,
+                    )?
+                }
+            }
+            writeln!(fmt, "|===|")?;
+            match self.exceeded_count() {
+                0 => write!(fmt, "budget respected, {} assertion(s) hold", self.outcomes.len()),
+                n => write!(
+                    fmt,
+                    "budget exceeded, {} of {} assertion(s) do not hold",
+                    n,
+                    self.outcomes.len()
+                ),
+            }
+        }
+    }
+}
@@ -0,0 +1,574 @@
+/*<LICENSE>
+    This file is part of Memthol.
+
+    Copyright (C) 2020 OCamlPro.
+
+    Memthol is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Memthol is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Memthol.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Binary cache of parsed traces.
+//!
+//! Parsing a big trace can take minutes. Once a trace is loaded, its data is saved as a compact
+//! binary snapshot next to the trace, see [`path_of`]. Subsequent runs load the snapshot instead of
+//! parsing the trace again, as long as the trace did not change since the snapshot was created and
+//! the path remapping rules are the same, see `alloc_data::remap`.
+//!
+//! Strings, labels and traces are interned in the snapshot: allocations refer to them by their index
+//! in the snapshot, and loading a snapshot registers them in the `alloc_data::mem` factories.
+//! Statistics are recomputed while loading the allocations.
+//!
+//! [`path_of`]: fn.path_of.html (The path_of function)
+
+prelude! {}
+
+use base::bincode::Options;
+use std::{
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
+};
+
+/// Version of the snapshot format, must be bumped whenever the format changes.
+const VERSION: u32 = 3;
+
+/// Suffix added to the path of a trace to obtain the path of its snapshot.
+const SUFFIX: &str = ".cache";
+
+/// Bincode options used for snapshots, integers use a variable-length encoding.
+fn options() -> impl base::bincode::Options {
+    base::bincode::DefaultOptions::new()
+}
+
+/// Path of the snapshot of a trace.
+pub fn path_of(trace: impl AsRef<Path>) -> PathBuf {
+    let mut path = trace.as_ref().as_os_str().to_owned();
+    path.push(SUFFIX);
+    path.into()
+}
+
+/// Identifies a version of a trace file.
+///
+/// Must be retrieved *before* reading the trace a snapshot is created from, so that a trace that
+/// grows while it is being read does not look like it matches the snapshot.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Source {
+    /// Size of the trace in bytes.
+    len: u64,
+    /// Date of last modification, as seconds and nanoseconds since the unix epoch.
+    modified: (u64, u32),
+}
+impl Source {
+    /// Constructor from a trace file.
+    pub fn of(trace: impl AsRef<Path>) -> Res<Self> {
+        let trace = trace.as_ref();
+        let meta = trace
+            .metadata()
+            .chain_err(|| format!("could not retrieve metadata of `{}`", trace.display()))?;
+        let modified = meta
+            .modified()
+            .ok()
+            .and_then(|date| date.duration_since(std::time::UNIX_EPOCH).ok())
+            .ok_or_else(|| {
+                format!(
+                    "could not retrieve time of last modification of `{}`",
+                    trace.display()
+                )
+            })?;
+        Ok(Self {
+            len: meta.len(),
+            modified: (modified.as_secs(), modified.subsec_nanos()),
+        })
+    }
+}
+
+/// Snapshot header, used to decide whether a snapshot can be used.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Header {
+    /// Version of the snapshot format.
+    version: u32,
+    /// Trace the snapshot was created from.
+    source: Source,
+    /// Path remapping rules the files of the locations were remapped with.
+    remap: String,
+    /// PID and start time of the run the trace comes from, see `ctf::RunId`.
+    run: (ctf::prelude::Pid, ctf::prelude::Clock),
+    /// Length of the decompressed trace.
+    len: usize,
+}
+impl Header {
+    /// Constructor, uses the current path remapping rules.
+    fn new(source: Source, run: ctf::RunId, len: usize) -> Self {
+        Self {
+            version: VERSION,
+            source,
+            remap: remap_rules(),
+            run: (run.pid, run.start),
+            len,
+        }
+    }
+
+    /// Run the trace comes from.
+    fn run(&self) -> ctf::RunId {
+        ctf::RunId {
+            pid: self.run.0,
+            start: self.run.1,
+        }
+    }
+
+    /// True if a snapshot with this header can be used for a trace and some remapping rules.
+    fn accepts(&self, source: &Source, remap: &str) -> bool {
+        self.version == VERSION && self.source == *source && self.remap == remap
+    }
+}
+
+/// Current path remapping rules, as a string.
+fn remap_rules() -> String {
+    alloc::remap::remap_do(|remap| remap.to_string())
+}
+
+/// A location in a snapshot.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Loc {
+    /// Index of the file in the strings of the snapshot.
+    file: usize,
+    /// Line in the file.
+    line: usize,
+    /// Column span at that line in the file.
+    span: (usize, usize),
+}
+
+/// A trace in a snapshot.
+///
+/// Successive traces tend to share a long prefix, so a trace only stores the length of the prefix
+/// it shares with the previous trace of the snapshot, and the counted locations that follow.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Trace {
+    /// Length of the prefix shared with the previous trace.
+    prefix: usize,
+    /// Counted locations after the prefix, as indices in the locations of the snapshot.
+    suffix: Vec<(usize, usize)>,
+}
+
+/// An allocation in a snapshot.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Alloc {
+    /// Allocation UID.
+    uid: uid::Alloc,
+    /// Allocation kind.
+    kind: alloc::AllocKind,
+    /// Size of the allocation.
+    size: u32,
+    /// Sample count.
+    nsamples: u32,
+    /// Index of the allocation's trace in the traces of the snapshot.
+    trace: usize,
+    /// Index of the allocation's labels in the labels of the snapshot.
+    labels: usize,
+    /// Time of creation.
+    toc: time::SinceStart,
+    /// Time of death.
+    tod: Option<time::SinceStart>,
+}
+
+/// Snapshot of the data.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Snapshot {
+    /// Init state.
+    init: alloc::Init,
+    /// Time of the latest event.
+    current_time: time::SinceStart,
+    /// Interned strings.
+    strs: Vec<String>,
+    /// Interned labels, as indices in `strs`.
+    labels: Vec<Vec<usize>>,
+    /// Interned locations.
+    locs: Vec<Loc>,
+    /// Interned traces.
+    traces: Vec<Trace>,
+    /// Allocations.
+    allocs: Vec<Alloc>,
+}
+
+/// Interns elements as indices in a list.
+struct Interner<T> {
+    /// Maps elements to their index.
+    map: HMap<T, usize>,
+}
+impl<T: std::hash::Hash + Eq> Interner<T> {
+    /// Constructor.
+    fn new() -> Self {
+        Self { map: HMap::new() }
+    }
+    /// Index of an element, `new` is called when the element is not known yet.
+    fn get(&mut self, elm: T, new: impl FnOnce(usize)) -> usize {
+        let next = self.map.len();
+        *self.map.entry(elm).or_insert_with(|| {
+            new(next);
+            next
+        })
+    }
+}
+
+impl Snapshot {
+    /// Constructor from some data.
+    fn new(data: &data::Data) -> Res<Self> {
+        let init = data
+            .init()
+            .ok_or("cannot create a snapshot of uninitialized data")?
+            .clone();
+
+        let (mut strs, mut labels, mut locs, mut traces, mut allocs) =
+            (vec![], vec![], vec![], vec![], vec![]);
+        let mut str_interner = Interner::new();
+        let mut labels_interner = Interner::new();
+        let mut loc_interner = Interner::new();
+        let mut trace_interner = Interner::new();
+        // Last trace pushed on `traces`, as (location index, count) pairs.
+        let mut last_trace: Vec<(usize, usize)> = vec![];
+
+        for alloc in data.iter_allocs() {
+            let labels_idx = labels_interner.get(alloc.labels_uid(), |_| {
+                let list = alloc
+                    .labels()
+                    .iter()
+                    .map(|label| str_interner.get(*label, |_| strs.push(label.to_string())))
+                    .collect();
+                labels.push(list)
+            });
+            let trace_idx = trace_interner.get(alloc.trace_uid(), |_| {
+                let trace: Vec<(usize, usize)> = alloc
+                    .trace()
+                    .iter()
+                    .map(|cloc| {
+                        let loc = &cloc.loc;
+                        let idx = loc_interner.get(loc.clone(), |_| {
+                            let file =
+                                str_interner.get(loc.file, |_| strs.push(loc.file.to_string()));
+                            locs.push(Loc {
+                                file,
+                                line: loc.line,
+                                span: (loc.span.lbound, loc.span.ubound),
+                            })
+                        });
+                        (idx, cloc.cnt)
+                    })
+                    .collect();
+                let prefix = last_trace
+                    .iter()
+                    .zip(trace.iter())
+                    .take_while(|(last, current)| last == current)
+                    .count();
+                traces.push(Trace {
+                    prefix,
+                    suffix: trace[prefix..].to_vec(),
+                });
+                last_trace = trace
+            });
+            allocs.push(Alloc {
+                uid: alloc.uid,
+                kind: alloc.kind,
+                size: alloc.size,
+                nsamples: alloc.nsamples,
+                trace: trace_idx,
+                labels: labels_idx,
+                toc: alloc.toc,
+                tod: alloc.tod,
+            })
+        }
+
+        Ok(Self {
+            init,
+            current_time: *data.current_time(),
+            strs,
+            labels,
+            locs,
+            traces,
+            allocs,
+        })
+    }
+
+    /// Registers the content of the snapshot in the global data.
+    fn register(self, trace: &Path) -> Res<()> {
+        let Self {
+            init,
+            current_time,
+            strs,
+            labels,
+            locs,
+            traces,
+            allocs,
+        } = self;
+
+        super::progress::set_total(allocs.len())?;
+
+        let mut factory = data::FullFactory::new(false);
+
+        let strs: Vec<alloc::Str> = strs.iter().map(|s| factory.register_str(s)).collect();
+        let str_of = |idx: usize| {
+            strs.get(idx)
+                .cloned()
+                .ok_or_else(|| format!("[cache] unknown string index {}", idx))
+        };
+        let mut labels_uids = Vec::with_capacity(labels.len());
+        for list in labels {
+            let list = list.into_iter().map(&str_of).collect::<Result<_, _>>()?;
+            labels_uids.push(factory.register_labels(list))
+        }
+        let mut loc_list = Vec::with_capacity(locs.len());
+        for Loc { file, line, span } in locs {
+            loc_list.push(alloc::Loc::new(str_of(file)?, line, span))
+        }
+        let mut trace_uids = Vec::with_capacity(traces.len());
+        let mut last_trace: Vec<alloc::CLoc> = vec![];
+        for Trace { prefix, suffix } in traces {
+            if prefix > last_trace.len() {
+                bail!("[cache] illegal trace prefix length {}", prefix)
+            }
+            last_trace.truncate(prefix);
+            for (loc, cnt) in suffix {
+                let loc = loc_list
+                    .get(loc)
+                    .cloned()
+                    .ok_or_else(|| format!("[cache] unknown location index {}", loc))?;
+                last_trace.push(alloc::CLoc::new(loc, cnt))
+            }
+            trace_uids.push(factory.register_trace(last_trace.clone()))
+        }
+
+        factory.data.reset(trace, init.clone());
+        factory.data.reserve(allocs.len());
+
+        let mut dead = Vec::with_capacity(allocs.len());
+        for (idx, alloc) in allocs.into_iter().enumerate() {
+            let trace = *trace_uids
+                .get(alloc.trace)
+                .ok_or_else(|| format!("[cache] unknown trace index {}", alloc.trace))?;
+            let labels = *labels_uids
+                .get(alloc.labels)
+                .ok_or_else(|| format!("[cache] unknown labels index {}", alloc.labels))?;
+            let built = alloc::Builder::new(
+                Some(alloc.uid),
+                alloc.kind,
+                alloc.size,
+                trace,
+                labels,
+                alloc.toc,
+                None,
+            )
+            .nsamples(alloc.nsamples)
+            .build(&init.sample_rate, alloc.uid)?;
+            factory.add_new(built)?;
+            if let Some(tod) = alloc.tod {
+                dead.push((tod, alloc.uid))
+            }
+            if idx % 10_000 == 9_999 {
+                super::progress::set_loaded(idx + 1)?
+            }
+        }
+        for (tod, uid) in dead {
+            factory.add_dead(tod, uid)?
+        }
+
+        factory.mark_timestamp(current_time);
+        factory.fill_stats()
+    }
+}
+
+/// Opens the snapshot of a trace and reads its header.
+///
+/// Returns `None` if there is no snapshot or its header cannot be read, *e.g.* because it was
+/// created by a version of memthol using a different header format.
+fn open(trace: &Path) -> Res<Option<(Header, BufReader<std::fs::File>)>> {
+    let path = path_of(trace);
+    if !path.is_file() {
+        return Ok(None);
+    }
+
+    let mut reader = BufReader::new(
+        std::fs::File::open(&path)
+            .chain_err(|| format!("while opening cache file `{}`", path.display()))?,
+    );
+    match options().deserialize_from(&mut reader) {
+        Ok(header) => Ok(Some((header, reader))),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Loads the snapshot of a trace, if it exists and is up to date.
+///
+/// Returns the run the trace comes from and the length of the decompressed trace, so that the
+/// trace does not need to be read. Returns `None` if there is no usable snapshot, in which case the
+/// global data is left untouched.
+pub fn load(trace: impl AsRef<Path>) -> Res<Option<(ctf::RunId, usize)>> {
+    let trace = trace.as_ref();
+    let path = path_of(trace);
+    let (header, mut reader) = match open(trace)? {
+        Some(res) => res,
+        None => return Ok(None),
+    };
+    if !header.accepts(&Source::of(trace)?, &remap_rules()) {
+        log::info!("ignoring outdated cache file `{}`", path.display());
+        return Ok(None);
+    }
+
+    log::info!("loading cache file `{}`", path.display());
+    let snapshot: Snapshot = options()
+        .deserialize_from(&mut reader)
+        .chain_err(|| format!("while reading cache file `{}`", path.display()))?;
+    snapshot
+        .register(trace)
+        .chain_err(|| format!("while loading cache file `{}`", path.display()))?;
+    Ok(Some((header.run(), header.len)))
+}
+
+/// Saves the global data as the snapshot of a trace.
+///
+/// The `source` of the trace must have been retrieved before reading it, see [`Source`]. `run` and
+/// `len` are the run the trace comes from and the length of the decompressed trace.
+///
+/// [`Source`]: struct.Source.html (The Source struct)
+pub fn save(trace: impl AsRef<Path>, source: Source, run: ctf::RunId, len: usize) -> Res<()> {
+    let snapshot = Snapshot::new(&*data::get()?)?;
+    write(trace.as_ref(), &Header::new(source, run, len), &snapshot)
+}
+
+/// Writes a snapshot of a trace.
+///
+/// The snapshot is written to a temporary file first, and then moved over the actual snapshot so
+/// that an interrupted write never leaves a partial snapshot behind.
+fn write(trace: &Path, header: &Header, snapshot: &Snapshot) -> Res<()> {
+    let path = path_of(trace);
+    let tmp_path = {
+        let mut tmp_path = path.clone().into_os_string();
+        tmp_path.push(".tmp");
+        PathBuf::from(tmp_path)
+    };
+
+    let res = std::fs::File::create(&tmp_path)
+        .chain_err(|| format!("while creating cache file `{}`", tmp_path.display()))
+        .and_then(|file| {
+            use std::io::Write;
+            let mut writer = BufWriter::new(file);
+            options()
+                .serialize_into(&mut writer, header)
+                .and_then(|()| options().serialize_into(&mut writer, snapshot))
+                .map_err(err::Error::from)
+                .and_then(|()| writer.flush().map_err(err::Error::from))
+                .chain_err(|| format!("while writing cache file `{}`", tmp_path.display()))
+        })
+        .and_then(|()| {
+            std::fs::rename(&tmp_path, &path).chain_err(|| {
+                format!(
+                    "while moving cache file `{}` to `{}`",
+                    tmp_path.display(),
+                    path.display()
+                )
+            })
+        });
+    if res.is_err() {
+        let _ = std::fs::remove_file(&tmp_path);
+    }
+    res?;
+
+    log::info!("saved cache file `{}`", path.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Data with a few allocations, two of which die.
+    fn data() -> data::Data {
+        let mut data = data::Data::new();
+        data.reset("dump", alloc::Init::default());
+        let trace = alloc::mem::trace::add(vec![]);
+        let labels = alloc::mem::labels::add(vec![]);
+        for toc in 0..5 {
+            let builder = alloc::Builder::new(
+                None,
+                alloc::AllocKind::Minor,
+                8,
+                trace,
+                labels,
+                std::time::Duration::from_millis(toc * 10).into(),
+                None,
+            );
+            data.build_new(builder).unwrap()
+        }
+        for uid in 0..2usize {
+            let tod = std::time::Duration::from_millis(100 + uid as u64).into();
+            data.add_dead(tod, uid::Alloc::from(uid)).unwrap()
+        }
+        data
+    }
+
+    /// Run used for the snapshots.
+    const RUN: ctf::RunId = ctf::RunId {
+        pid: 42,
+        start: 1_000,
+    };
+
+    /// Header of a snapshot of `trace`.
+    fn new_header(trace: &Path) -> Header {
+        Header::new(Source::of(trace).unwrap(), RUN, 10)
+    }
+
+    /// True if the snapshot of `trace` exists and is up to date.
+    fn up_to_date(trace: &Path) -> bool {
+        let (header, _) = open(trace)
+            .unwrap()
+            .expect("could not read snapshot header");
+        header.accepts(&Source::of(trace).unwrap(), &remap_rules())
+    }
+
+    #[test]
+    fn round_trip_rejects_changes() {
+        let dir = std::env::temp_dir().join(format!("memthol_cache_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let trace = dir.join("trace.ctf");
+        std::fs::write(&trace, b"some trace").unwrap();
+
+        let snapshot = Snapshot::new(&data()).unwrap();
+        assert_eq!(snapshot.allocs.len(), 5);
+        assert_eq!(
+            snapshot.allocs.iter().filter(|a| a.tod.is_some()).count(),
+            2
+        );
+        write(&trace, &new_header(&trace), &snapshot).unwrap();
+        assert!(up_to_date(&trace));
+        assert!(!path_of(&trace).with_extension("cache.tmp").exists());
+
+        // The remapping rules changed.
+        let (header, _) = open(&trace).unwrap().unwrap();
+        assert!(!header.accepts(&Source::of(&trace).unwrap(), "some other rules"));
+        assert_eq!((header.run(), header.len), (RUN, 10));
+
+        // The length of the trace changed.
+        std::fs::write(&trace, b"some trace, longer").unwrap();
+        assert!(!up_to_date(&trace));
+
+        // The trace was modified, but its length did not change.
+        let source = Source::of(&trace).unwrap();
+        write(&trace, &new_header(&trace), &snapshot).unwrap();
+        assert!(up_to_date(&trace));
+        while Source::of(&trace).unwrap() == source {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            std::fs::write(&trace, b"same trace, longer").unwrap();
+        }
+        assert_eq!(Source::of(&trace).unwrap().len, source.len);
+        assert!(!up_to_date(&trace));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
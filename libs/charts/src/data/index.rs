@@ -0,0 +1,162 @@
+/*<LICENSE>
+    This file is part of Memthol.
+
+    Copyright (C) 2020 OCamlPro.
+
+    Memthol is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Memthol is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Memthol.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Time index over the allocation events.
+//!
+//! Time is split in buckets of [`BUCKET_SPAN_NANOS`] nanoseconds. Each bucket lists the
+//! allocations created and the allocations dying during the bucket. The index is maintained as
+//! allocations and deaths are registered, both when loading a trace and in live mode.
+//!
+//! Per-filter aggregates cannot live here since they depend on the filters, see
+//! [`filter::index`].
+//!
+//! [`BUCKET_SPAN_NANOS`]: constant.BUCKET_SPAN_NANOS.html (The BUCKET_SPAN_NANOS constant)
+//! [`filter::index`]: ../../filter/index/index.html (The filter::index module)
+
+prelude! {}
+
+/// Duration of a bucket, in nanoseconds.
+pub const BUCKET_SPAN_NANOS: u64 = 100_000_000;
+
+/// Allocation events happening during a bucket.
+#[derive(Debug, Clone)]
+pub struct Bucket {
+    /// Allocations created during the bucket, in chronological order.
+    new: Vec<uid::Alloc>,
+    /// Allocations dying during the bucket.
+    dead: Vec<uid::Alloc>,
+}
+impl Bucket {
+    /// Constructor.
+    fn new() -> Self {
+        Self {
+            new: vec![],
+            dead: vec![],
+        }
+    }
+
+    /// Allocations created during the bucket, in chronological order.
+    pub fn new_allocs(&self) -> &[uid::Alloc] {
+        &self.new
+    }
+    /// Allocations dying during the bucket.
+    pub fn dead_allocs(&self) -> &[uid::Alloc] {
+        &self.dead
+    }
+}
+
+/// Time-bucketed index of the allocation events.
+#[derive(Debug, Clone)]
+pub struct TimeIndex {
+    /// Buckets, the bucket at index `i` starts at `i * BUCKET_SPAN_NANOS`.
+    buckets: Vec<Bucket>,
+    /// Incremented each time the index is cleared.
+    ///
+    /// Lets aggregates built over the index detect that the data was reset.
+    epoch: usize,
+}
+
+impl TimeIndex {
+    /// Constructor.
+    pub fn new() -> Self {
+        Self {
+            buckets: vec![],
+            epoch: 0,
+        }
+    }
+
+    /// Clears the index, called when the data is reset.
+    pub fn clear(&mut self) {
+        self.buckets.clear();
+        self.epoch += 1;
+    }
+
+    /// Epoch of the index, changes each time the index is cleared.
+    pub fn epoch(&self) -> usize {
+        self.epoch
+    }
+
+    /// Number of buckets in the index.
+    ///
+    /// Buckets after the last one are empty.
+    pub fn len(&self) -> usize {
+        self.buckets.len()
+    }
+
+    /// Bucket accessor.
+    pub fn get(&self, index: usize) -> Option<&Bucket> {
+        self.buckets.get(index)
+    }
+
+    /// Index of the bucket some time falls in.
+    pub fn bucket_of(time: time::SinceStart) -> usize {
+        (time.as_nanos() / BUCKET_SPAN_NANOS as u128) as usize
+    }
+
+    /// Time at which a bucket starts.
+    pub fn bucket_start(index: usize) -> time::SinceStart {
+        std::time::Duration::from_nanos(index as u64 * BUCKET_SPAN_NANOS).into()
+    }
+
+    /// Bucket some time falls in, created if needed.
+    fn bucket_mut(&mut self, time: time::SinceStart) -> &mut Bucket {
+        let index = Self::bucket_of(time);
+        if self.buckets.len() <= index {
+            self.buckets.resize_with(index + 1, Bucket::new)
+        }
+        &mut self.buckets[index]
+    }
+
+    /// Registers a new allocation.
+    pub fn add_new(&mut self, toc: time::SinceStart, uid: uid::Alloc) {
+        self.bucket_mut(toc).new.push(uid)
+    }
+
+    /// Registers an allocation's death.
+    pub fn add_dead(&mut self, tod: time::SinceStart, uid: uid::Alloc) {
+        self.bucket_mut(tod).dead.push(uid)
+    }
+
+    /// Last allocation created before some bucket, if any.
+    pub fn last_new_before(&self, index: usize) -> Option<uid::Alloc> {
+        let end = std::cmp::min(index, self.buckets.len());
+        self.buckets[0..end]
+            .iter()
+            .rev()
+            .find_map(|bucket| bucket.new.last().cloned())
+    }
+
+    /// Position from which [`Data::iter_new_events`] yields the events of a bucket and the ones
+    /// after it.
+    ///
+    /// Returns `None` if no allocation was created before the bucket, in which case iterating from
+    /// the beginning is equivalent.
+    ///
+    /// [`Data::iter_new_events`]: ../struct.Data.html#method.iter_new_events
+    /// (The iter_new_events method on Data)
+    pub fn resume_at(&self, index: usize) -> Option<(uid::Alloc, time::SinceStart)> {
+        if index == 0 {
+            return None;
+        }
+        // `iter_new_events` yields deaths strictly after the time we give it.
+        let before_start =
+            Self::bucket_start(index) - time::SinceStart::from_nano_timestamp(0, 1);
+        self.last_new_before(index).map(|uid| (uid, before_start))
+    }
+}
@@ -0,0 +1,264 @@
+/*<LICENSE>
+    This file is part of Memthol.
+
+    Copyright (C) 2020 OCamlPro.
+
+    Memthol is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Memthol is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Memthol.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Statistics over a whole trace.
+//!
+//! Used by memthol's `stats` subcommand, which prints them without starting the UI. When
+//! serialized, times and lifetimes are amounts of seconds.
+
+prelude! {}
+
+use time::DurationExt;
+
+/// Serializes a duration as an amount of seconds, easier to handle for scripts than a struct.
+fn as_secs<T, S>(duration: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: DurationExt,
+    S: ::serde::Serializer,
+{
+    serializer.serialize_f64(duration.as_duration().as_secs_f64())
+}
+
+/// Statistics for an allocation kind.
+#[derive(Debug, Clone, Serialize)]
+pub struct KindStats {
+    /// Allocation kind.
+    pub kind: alloc::AllocKind,
+    /// Number of allocations of this kind.
+    pub count: usize,
+    /// Total size of the allocations of this kind, in bytes.
+    pub bytes: u64,
+}
+
+/// Statistics for an allocation site.
+#[derive(Debug, Clone, Serialize)]
+pub struct SiteStats {
+    /// Allocation site, `None` if unknown.
+    pub site: Option<String>,
+    /// Number of allocations at this site.
+    pub count: usize,
+    /// Total size of the allocations at this site, in bytes.
+    pub bytes: u64,
+    /// Size of the allocations at this site still alive at the end of the run, in bytes.
+    pub live_bytes: u64,
+}
+
+/// Lifetime percentiles of the allocations that die during the run.
+#[derive(Debug, Clone, Serialize)]
+pub struct LifetimeStats {
+    /// Number of allocations that die during the run.
+    pub count: usize,
+    /// Median lifetime.
+    #[serde(serialize_with = "as_secs")]
+    pub p50: time::Lifetime,
+    /// 90th percentile.
+    #[serde(serialize_with = "as_secs")]
+    pub p90: time::Lifetime,
+    /// 99th percentile.
+    #[serde(serialize_with = "as_secs")]
+    pub p99: time::Lifetime,
+    /// Longest lifetime.
+    #[serde(serialize_with = "as_secs")]
+    pub max: time::Lifetime,
+}
+
+/// Statistics over a whole trace.
+#[derive(Debug, Clone, Serialize)]
+pub struct TraceStats {
+    /// Basic allocation statistics.
+    pub stats: AllocStats,
+    /// Total size of the allocations, in bytes.
+    pub total_bytes: u64,
+    /// Number of allocations alive at the end of the run.
+    pub live_count: usize,
+    /// Size of the allocations alive at the end of the run, in bytes.
+    pub live_bytes: u64,
+    /// Highest size of the allocations alive at the same time, in bytes.
+    pub peak_live_bytes: u64,
+    /// Time at which the peak live size is reached.
+    #[serde(serialize_with = "as_secs")]
+    pub peak_time: time::SinceStart,
+    /// Statistics by allocation kind, most allocated bytes first.
+    pub by_kind: Vec<KindStats>,
+    /// Top allocation sites, most allocated bytes first.
+    pub top_sites: Vec<SiteStats>,
+    /// Lifetime percentiles, `None` if no allocation dies during the run.
+    pub lifetimes: Option<LifetimeStats>,
+}
+
+impl TraceStats {
+    /// Computes the statistics of some data.
+    ///
+    /// Only keeps the `top` allocation sites that allocate the most bytes.
+    pub fn new(data: &data::Data, top: usize) -> Res<Self> {
+        let mut stats = data
+            .stats()
+            .ok_or("cannot compute statistics of uninitialized data")?;
+        stats.alloc_count = data.alloc_count();
+
+        let mut total_bytes = 0;
+        let (mut live_count, mut live_bytes) = (0, 0);
+        let mut by_kind: HMap<alloc::AllocKind, KindStats> = HMap::new();
+        let mut by_site: HMap<Option<alloc::Loc>, SiteStats> = HMap::new();
+        let mut lifetimes = Vec::with_capacity(data.alloc_count());
+        // Allocation and death events as `(time, size delta)` pairs.
+        let mut events = Vec::with_capacity(2 * data.alloc_count());
+
+        for alloc in data.iter_allocs() {
+            let bytes = alloc.real_size as u64;
+            let is_live = alloc.tod.is_none();
+            total_bytes += bytes;
+
+            let kind = by_kind.entry(alloc.kind).or_insert_with(|| KindStats {
+                kind: alloc.kind,
+                count: 0,
+                bytes: 0,
+            });
+            kind.count += 1;
+            kind.bytes += bytes;
+
+            let site = alloc.alloc_site_do(|site| site.map(|cloc| cloc.loc.clone()));
+            let site = by_site.entry(site).or_insert_with_key(|site| SiteStats {
+                site: site.as_ref().map(alloc::Loc::to_string),
+                count: 0,
+                bytes: 0,
+                live_bytes: 0,
+            });
+            site.count += 1;
+            site.bytes += bytes;
+
+            events.push((alloc.toc, bytes as i64));
+            if let Some(tod) = alloc.tod {
+                events.push((tod, -(bytes as i64)));
+                lifetimes.push((tod - alloc.toc).to_lifetime());
+            }
+            if is_live {
+                live_count += 1;
+                live_bytes += bytes;
+                site.live_bytes += bytes;
+            }
+        }
+
+        // Deaths before allocations for events at the same time.
+        events.sort();
+        let (mut live, mut peak_live_bytes, mut peak_time) = (0i64, 0, time::SinceStart::zero());
+        for (time, delta) in events {
+            live += delta;
+            if live as u64 > peak_live_bytes {
+                peak_live_bytes = live as u64;
+                peak_time = time;
+            }
+        }
+
+        let mut by_kind: Vec<_> = by_kind.into_values().collect();
+        by_kind.sort_by_key(|kind| std::cmp::Reverse(kind.bytes));
+        let mut top_sites: Vec<_> = by_site.into_values().collect();
+        top_sites.sort_by(|lft, rgt| rgt.bytes.cmp(&lft.bytes).then(lft.site.cmp(&rgt.site)));
+        top_sites.truncate(top);
+
+        let lifetimes = if lifetimes.is_empty() {
+            None
+        } else {
+            lifetimes.sort();
+            let percentile = |p: usize| lifetimes[(lifetimes.len() - 1) * p / 100];
+            Some(LifetimeStats {
+                count: lifetimes.len(),
+                p50: percentile(50),
+                p90: percentile(90),
+                p99: percentile(99),
+                max: percentile(100),
+            })
+        };
+
+        Ok(Self {
+            stats,
+            total_bytes,
+            live_count,
+            live_bytes,
+            peak_live_bytes,
+            peak_time,
+            by_kind,
+            top_sites,
+            lifetimes,
+        })
+    }
+
+    /// Computes the statistics of the global data.
+    pub fn get(top: usize) -> Res<Self> {
+        Self::new(&*super::get()?, top)
+    }
+}
+
+base::implement! {
+    impl Display for TraceStats {
+        |&self, fmt| {
+            writeln!(fmt, "|===| Trace statistics")?;
+            writeln!(fmt, "| trace: `{}`", self.stats.dump_dir.display())?;
+            writeln!(fmt, "| start date: {}", self.stats.start_date)?;
+            writeln!(fmt, "| duration: {}", self.stats.duration)?;
+            writeln!(fmt, "| allocations: {}", self.stats.alloc_count)?;
+            writeln!(fmt, "| total allocated: {} bytes", self.total_bytes)?;
+            writeln!(
+                fmt,
+                "| peak live size: {} bytes at {}",
+                self.peak_live_bytes, self.peak_time
+            )?;
+            writeln!(
+                fmt,
+                "| live at the end: {} bytes ({} allocation(s))",
+                self.live_bytes, self.live_count
+            )?;
+
+            writeln!(fmt, "|===| By allocation kind")?;
+            for kind in &self.by_kind {
+                writeln!(
+                    fmt,
+                    "| {:>15} {:>10} allocation(s) {:>15} bytes",
+                    kind.kind.as_str(),
+                    kind.count,
+                    kind.bytes
+                )?;
+            }
+
+            writeln!(fmt, "|===| Top {} allocation site(s)", self.top_sites.len())?;
+            for site in &self.top_sites {
+                writeln!(
+                    fmt,
+                    "| {:>15} bytes {:>15} live {:>10} allocation(s) {}",
+                    site.bytes,
+                    site.live_bytes,
+                    site.count,
+                    site.site.as_deref().unwrap_or("<unknown>"),
+                )?;
+            }
+
+            writeln!(fmt, "|===| Lifetimes")?;
+            if let Some(lifetimes) = self.lifetimes.as_ref() {
+                writeln!(fmt, "| {} allocation(s) die during the run", lifetimes.count)?;
+                writeln!(fmt, "| p50: {}", lifetimes.p50)?;
+                writeln!(fmt, "| p90: {}", lifetimes.p90)?;
+                writeln!(fmt, "| p99: {}", lifetimes.p99)?;
+                writeln!(fmt, "| max: {}", lifetimes.max)?;
+            } else {
+                writeln!(fmt, "| no allocation dies during the run")?;
+            }
+            write!(fmt, "|===|")
+        }
+    }
+}
@@ -0,0 +1,226 @@
+/*<LICENSE>
+    This file is part of Memthol.
+
+    Copyright (C) 2020 OCamlPro.
+
+    Memthol is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Memthol is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Memthol.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Handles directories of memtrace CTF files.
+//!
+//! When running on a directory of CTF files, the watcher loads the most recent one unless the user
+//! selected another trace from the directory.
+
+prelude! {}
+
+use std::{path::PathBuf, time::SystemTime};
+
+/// State of the trace directory.
+struct State {
+    /// Trace directory, `None` if not running on a directory of traces.
+    dir: Option<PathBuf>,
+    /// Trace summaries, with the date of last modification of the trace they were computed from.
+    infos: BTMap<String, (SystemTime, TraceInfo)>,
+    /// Trace selected by the user, if any.
+    selected: Option<String>,
+    /// Trace currently loaded, if any.
+    active: Option<String>,
+}
+
+lazy_static! {
+    /// Global trace directory state.
+    static ref STATE: sync::RwLock<State> = sync::RwLock::new(State {
+        dir: None,
+        infos: BTMap::new(),
+        selected: None,
+        active: None,
+    });
+}
+
+/// Read-lock over the trace directory state.
+fn read<'a>() -> Res<sync::RwLockReadGuard<'a, State>> {
+    STATE
+        .read()
+        .map_err(|e| {
+            let e: err::Error = e.to_string().into();
+            e
+        })
+        .chain_err(|| "while reading the trace directory state")
+}
+/// Write-lock over the trace directory state.
+fn write<'a>() -> Res<sync::RwLockWriteGuard<'a, State>> {
+    STATE
+        .write()
+        .map_err(|e| {
+            let e: err::Error = e.to_string().into();
+            e
+        })
+        .chain_err(|| "while writing the trace directory state")
+}
+
+/// Sets the trace directory.
+pub fn set_dir(dir: impl Into<PathBuf>) -> Res<()> {
+    let mut state = write()?;
+    state.dir = Some(dir.into());
+    state.infos.clear();
+    state.selected = None;
+    state.active = None;
+    Ok(())
+}
+
+/// Path to the trace selected by the user, if any.
+pub fn selected() -> Res<Option<PathBuf>> {
+    let state = read()?;
+    Ok(state
+        .dir
+        .as_ref()
+        .and_then(|dir| state.selected.as_ref().map(|file| dir.join(file))))
+}
+
+/// Name of the trace currently loaded, if any.
+pub fn active() -> Res<Option<String>> {
+    read().map(|state| state.active.clone())
+}
+
+/// Selects a trace, the watcher will load it on its next poll.
+///
+/// Fails if not running on a trace directory, or if the trace is not one of the traces of the
+/// directory (see [`list`]). In particular, `file` must be a file name, not a path.
+///
+/// [`list`]: fn.list.html (The list function)
+pub fn select(file: impl Into<String>) -> Res<()> {
+    let file = file.into();
+    let is_file_name = !file.is_empty()
+        && file != "."
+        && !file.contains("..")
+        && !file.contains(|c: char| c == '/' || c == '\\' || std::path::is_separator(c))
+        && !std::path::Path::new(&file).is_absolute();
+    if !is_file_name {
+        bail!("cannot select trace `{}`, expected a trace file name", file)
+    }
+
+    let mut state = write()?;
+    if state.dir.is_none() {
+        bail!(
+            "cannot select trace `{}`, not running on a trace directory",
+            file
+        )
+    }
+    if !state.infos.contains_key(&file) {
+        bail!("cannot select unknown trace `{}`", file)
+    }
+    log::info!("selecting trace `{}`", file);
+    state.selected = Some(file);
+    Ok(())
+}
+
+/// Registers the trace currently loaded.
+pub fn set_active(path: &std::path::Path) -> Res<()> {
+    let mut state = write()?;
+    if state.dir.is_some() {
+        state.active = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned());
+    }
+    Ok(())
+}
+
+/// Lists the traces of the trace directory, if any.
+///
+/// Only returns the summaries computed by the last call to [`refresh`], which the watcher calls
+/// periodically.
+///
+/// [`refresh`]: fn.refresh.html (The refresh function)
+pub fn list() -> Res<Vec<TraceInfo>> {
+    Ok(read()?
+        .infos
+        .values()
+        .map(|(_, info)| info.clone())
+        .collect())
+}
+
+/// Updates the summaries of the traces of the trace directory, if any.
+///
+/// Summaries are only recomputed when a trace is modified. Traces that cannot be summarized, for
+/// instance because they are still being written, are ignored. Summaries are computed without
+/// holding the lock on the trace directory state.
+pub fn refresh() -> Res<()> {
+    let (dir, known) = {
+        let state = read()?;
+        let dir = if let Some(dir) = state.dir.clone() {
+            dir
+        } else {
+            return Ok(());
+        };
+        let known: BTMap<String, SystemTime> = state
+            .infos
+            .iter()
+            .map(|(file, (modified, _))| (file.clone(), *modified))
+            .collect();
+        (dir, known)
+    };
+
+    let entries = std::fs::read_dir(&dir)
+        .chain_err(|| format!("while reading trace directory `{}`", dir.display()))?;
+    let mut files = BTSet::new();
+    let mut summaries = BTMap::new();
+    for entry in entries {
+        let path = entry
+            .chain_err(|| format!("while reading trace directory `{}`", dir.display()))?
+            .path();
+        if !path.is_file() || !ctf::compress::has_ctf_extension(&path) {
+            continue;
+        }
+        let modified = match path.metadata().and_then(|meta| meta.modified()) {
+            Ok(modified) => modified,
+            Err(_) => continue,
+        };
+        let file = match path.file_name() {
+            Some(name) => name.to_string_lossy().into_owned(),
+            None => continue,
+        };
+        files.insert(file.clone());
+
+        if known.get(&file) == Some(&modified) {
+            continue;
+        }
+        let summary =
+            ctf::compress::read_file(&path, |_| ()).and_then(|bytes| ctf::summary(&bytes));
+        match summary {
+            Ok(summary) => {
+                let info = TraceInfo::new(file.clone(), summary);
+                summaries.insert(file, Some((modified, info)));
+            }
+            Err(e) => {
+                log::warn!("failed to summarize trace `{}`: {}", path.display(), e);
+                summaries.insert(file, None);
+            }
+        }
+    }
+
+    let mut state = write()?;
+    if state.dir.as_ref() != Some(&dir) {
+        // The trace directory changed in the meantime.
+        return Ok(());
+    }
+    state.infos.retain(|file, _| files.contains(file));
+    for (file, summary) in summaries {
+        if let Some(summary) = summary {
+            state.infos.insert(file, summary);
+        } else {
+            state.infos.remove(&file);
+        }
+    }
+    Ok(())
+}
@@ -28,6 +28,29 @@ use std::{
     time::{Duration, SystemTime},
 };
 
+/// What was parsed of a memtrace CTF file.
+struct Parsed {
+    /// Run the trace comes from.
+    run: ctf::RunId,
+    /// Length of the part of the (decompressed) trace that was parsed.
+    len: usize,
+    /// State of the parser after the part of the trace that was parsed.
+    ///
+    /// `None` if the trace was loaded from its cache, in which case the next append decodes the
+    /// whole trace again to rebuild the state of the parser.
+    resume: Option<ctf::Resume>,
+}
+impl Parsed {
+    /// Constructor from the state of the parser.
+    fn new(resume: ctf::Resume) -> Self {
+        Self {
+            run: resume.run_id(),
+            len: resume.offset(),
+            resume: Some(resume),
+        }
+    }
+}
+
 /// Daemon monitoring files.
 pub struct Watcher {
     /// Directory to watch.
@@ -59,9 +82,9 @@ impl Watcher {
     pub fn spawn(target: impl AsRef<Path>, forever: bool) {
         let path = target.as_ref();
 
-        if path.is_file() {
-            let path = path.display().to_string();
-            let _ = std::thread::spawn(move || match Self::ctf_run(path) {
+        if path.is_file() || Self::is_ctf_dir(path) {
+            let path = path.to_path_buf();
+            let _ = std::thread::spawn(move || match Self::ctf_watch(path) {
                 Ok(()) => (),
                 Err(e) => err::register_fatal(e),
             });
@@ -91,8 +114,216 @@ impl Watcher {
         }
     }
 
+    /// True if `path` is a directory containing memtrace CTF files and no legacy init file.
+    pub fn is_ctf_dir(path: impl AsRef<Path>) -> bool {
+        let path = path.as_ref();
+        path.is_dir()
+            && !path.join("init.memthol").exists()
+            && Self::latest_ctf_file(path)
+                .map(|file| file.is_some())
+                .unwrap_or(false)
+    }
+
+    /// Most recent memtrace CTF file in a directory, if any.
+    ///
+    /// Files are ordered by date of last modification, then by name.
+    pub fn latest_ctf_file(dir: impl AsRef<Path>) -> Res<Option<PathBuf>> {
+        let dir = dir.as_ref();
+        let entries = std::fs::read_dir(dir)
+            .chain_err(|| format!("while reading directory `{}`", dir.display()))?;
+        let mut latest: Option<(SystemTime, PathBuf)> = None;
+        for entry in entries {
+            // Files can be deleted or renamed while we list them, skip those.
+            let path = match entry {
+                Ok(entry) => entry.path(),
+                Err(e) => {
+                    log::warn!("while reading directory `{}`: {}", dir.display(), e);
+                    continue;
+                }
+            };
+            if !path.is_file() || !ctf::compress::has_ctf_extension(&path) {
+                continue;
+            }
+            let modified = match Self::stamp(&path) {
+                Ok((_, modified)) => modified,
+                Err(e) => {
+                    log::warn!("skipping `{}`: {}", path.display(), e);
+                    continue;
+                }
+            };
+            let candidate = (modified, path);
+            if latest.as_ref().map(|max| *max < candidate).unwrap_or(true) {
+                latest = Some(candidate)
+            }
+        }
+        Ok(latest.map(|(_, path)| path))
+    }
+
+    /// Size and date of last modification of a file.
+    fn stamp(file: &Path) -> Res<(u64, SystemTime)> {
+        let meta = file
+            .metadata()
+            .chain_err(|| format!("could not retrieve metadata of `{}`", file.display()))?;
+        let modified = meta.modified().chain_err(|| {
+            format!(
+                "could not retrieve time of last modification of `{}`",
+                file.display()
+            )
+        })?;
+        Ok((meta.len(), modified))
+    }
+
+    /// Runs on a memtrace CTF file, or a directory of CTF files, and reloads on changes.
+    ///
+    /// If `target` is a directory, the trace selected by the user is loaded (see
+    /// [`traces::select`]), or the most recent CTF file in the directory if none. Switching to
+    /// another trace, because a newer CTF file appears or another trace is selected, reloads the
+    /// data from scratch: the data is reset, and the charts restart on their next update.
+    ///
+    /// When the trace loaded changes, its header tells whether the profiling run restarted (new
+    /// start time or PID). If it did, the data is reloaded from scratch as above. Otherwise, only
+    /// the packets appended to the trace are parsed and added to the data (see [`ctf_append`]).
+    ///
+    /// A file is only (re)loaded once its size and date of last modification did not change
+    /// between two polls, so that we do not load traces that are still being written. The only
+    /// exception is the very first load, which happens right away. Files deleted or renamed between
+    /// two polls are skipped with a warning.
+    ///
+    /// [`traces::select`]: ../traces/fn.select.html (The select function in module traces)
+    /// [`ctf_append`]: #method.ctf_append (The ctf_append method)
+    pub fn ctf_watch(target: impl AsRef<Path>) -> Res<()> {
+        let target = target.as_ref();
+
+        if target.is_dir() {
+            super::traces::set_dir(target)?
+        }
+
+        // File, size and date of last modification of the last trace loaded.
+        let mut loaded: Option<(PathBuf, u64, SystemTime)> = None;
+        // Same as `loaded`, but for the file as it was during the previous poll.
+        let mut polled: Option<(PathBuf, u64, SystemTime)> = None;
+        // What was parsed of the last trace loaded, `None` if loading it failed.
+        let mut parsed: Option<Parsed> = None;
+        // Number of polls so far, used to refresh the trace summaries every few polls.
+        let mut polls: usize = 0;
+
+        loop {
+            if target.is_dir() && polls % 10 == 0 {
+                // Summarizing traces is expensive, do it on a slower poll.
+                if let Err(e) = super::traces::refresh() {
+                    log::warn!("failed to refresh the list of traces: {}", e)
+                }
+            }
+            polls = polls.wrapping_add(1);
+
+            let file = if target.is_dir() {
+                if let Some(selected) = super::traces::selected()? {
+                    Some(selected)
+                } else {
+                    Self::latest_ctf_file(target).unwrap_or_else(|e| {
+                        log::warn!("{}", e);
+                        None
+                    })
+                }
+            } else if target.is_file() {
+                Some(target.to_path_buf())
+            } else {
+                None
+            };
+
+            // The file can be deleted or renamed before we retrieve its metadata, in which case we
+            // skip this poll.
+            let file = file.and_then(|file| match Self::stamp(&file) {
+                Ok((len, modified)) => Some((file, len, modified)),
+                Err(e) => {
+                    log::warn!("skipping `{}`: {}", file.display(), e);
+                    None
+                }
+            });
+
+            if let Some(file) = file {
+                let current = Some(file);
+
+                if loaded.is_none() {
+                    let (file, _, _) = current.as_ref().unwrap();
+                    match Self::ctf_load(file) {
+                        Ok(now) => {
+                            parsed = Some(now);
+                            super::traces::set_active(file)?;
+                            loaded = current.clone()
+                        }
+                        // Deleted or renamed while we were loading it, try again on the next poll.
+                        Err(e) if !file.is_file() => log::warn!("{}", e),
+                        Err(e) => return Err(e),
+                    }
+                } else if current != loaded && current == polled {
+                    let (file, _, _) = current.as_ref().unwrap();
+                    let same_file = loaded
+                        .as_ref()
+                        .map(|(loaded_file, _, _)| loaded_file == file)
+                        .unwrap_or(false);
+
+                    let appended = match parsed.take() {
+                        Some(prev) if same_file => Self::ctf_append(file, prev),
+                        _ => Ok(None),
+                    };
+
+                    match appended {
+                        Ok(Some(now)) => parsed = Some(now),
+                        Ok(None) => {
+                            if same_file {
+                                log::info!(
+                                    "profiling run restarted, reloading `{}`",
+                                    file.display()
+                                )
+                            } else {
+                                log::info!("switching to trace `{}`", file.display())
+                            }
+                            super::progress::set_unknown()?;
+                            parsed = match Self::ctf_load(file) {
+                                Ok(now) => {
+                                    super::traces::set_active(file)?;
+                                    Some(now)
+                                }
+                                Err(e) => {
+                                    err::register_non_fatal(e.to_pretty());
+                                    super::progress::set_done()?;
+                                    None
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            // The data may be partially updated, the next change reloads it from
+                            // scratch.
+                            err::register_non_fatal(e.to_pretty());
+                            parsed = None
+                        }
+                    }
+                    loaded = current.clone()
+                }
+
+                polled = current
+            }
+
+            sleep(Duration::from_millis(500))
+        }
+    }
+
     /// Runs on a memtrace CTF file.
+    ///
+    /// Resets the data before loading, so this can be called again when the run restarts. Uses the
+    /// cache of the trace if it is up to date, and creates/updates it otherwise (see the [`cache`]
+    /// module). Truncated traces are loaded up to their last complete packet, and are not cached.
+    ///
+    /// [`cache`]: ../cache/index.html (The cache module)
     pub fn ctf_run(target: impl AsRef<Path>) -> Res<()> {
+        Self::ctf_load(target).map(|_| ())
+    }
+
+    /// Same as [`ctf_run`], but also returns what was parsed of the trace.
+    ///
+    /// [`ctf_run`]: #method.ctf_run (The ctf_run method)
+    fn ctf_load(target: impl AsRef<Path>) -> Res<Parsed> {
         base::new_time_stats! {
             struct Prof {
                 total => "total",
@@ -105,43 +336,53 @@ impl Watcher {
 
         let target = target.as_ref();
 
+        match super::cache::load(target) {
+            Ok(Some((run, len))) => {
+                super::progress::set_done()?;
+                log::info!("done loading ctf file `{}` from cache", target.display());
+                // Only cached traces that are not truncated, so the whole trace was parsed.
+                return Ok(Parsed {
+                    run,
+                    len,
+                    resume: None,
+                });
+            }
+            Ok(None) => (),
+            Err(e) => log::warn!("failed to load cache, parsing the trace instead: {}", e),
+        }
+
         log::info!("loading ctf file `{}`", target.display());
 
+        // Identify the trace before reading it, in case it changes while we read it.
+        let source = super::cache::Source::of(target);
+
         prof.load.start();
         let bytes = {
-            use std::io::Read;
-            let mut file = std::fs::OpenOptions::new()
-                .read(true)
-                .open(target)
-                .chain_err(|| format!("while opening ctf file `{}`", target.display()))?;
-            let len = file
+            // Progress over the bytes of the file while reading/decompressing it.
+            let file_len = target
                 .metadata()
                 .map(|meta| meta.len() as usize)
                 .unwrap_or(150_000);
-            let mut buff = Vec::with_capacity(len);
-            let data_len = file
-                .read_to_end(&mut buff)
-                .chain_err(|| format!("while reading ctf file `{}`", target.display()))?;
-            super::progress::set_total(data_len)?;
-            buff
+            super::progress::set_total(file_len)?;
+            let bytes = ctf::compress::read_file(target, |read| {
+                err::unwrap_register_fatal(super::progress::set_loaded(read))
+            })
+            .chain_err(|| format!("while loading ctf file `{}`", target.display()))?;
+            // Progress over the decompressed bytes while parsing.
+            super::progress::set_total(bytes.len())?;
+            bytes
         };
         prof.load.stop();
 
         let mut factory = data::FullFactory::new(false);
         prof.parse.start();
-        ctf::parse(
+        let (truncation, resume) = ctf::pipelined_parse(
             &bytes,
             &mut factory,
             |bytes_progress| {
                 err::unwrap_register_fatal(super::progress::set_loaded(bytes_progress))
             },
-            |factory, init| {
-                if factory.data.has_init() {
-                    panic!("live profiling restart is not supported yet")
-                } else {
-                    factory.data.reset(target, init)
-                }
-            },
+            |factory, init| factory.data.reset(target, init),
             |factory, builder| err::unwrap_register_fatal(factory.build_new(builder)),
             |factory, timestamp, uid| err::unwrap_register_fatal(factory.add_dead(timestamp, uid)),
             |factory, timestamp| factory.mark_timestamp(timestamp),
@@ -150,9 +391,21 @@ impl Watcher {
         prof.parse.stop();
 
         factory.fill_stats()?;
+        // Release the lock on the data, needed to create the snapshot.
+        drop(factory);
 
         super::progress::set_done()?;
 
+        if truncation.is_some() {
+            // Not caching truncated traces, the truncation warning would be lost when loading the
+            // cache.
+            log::info!("not caching truncated ctf file `{}`", target.display())
+        } else if let Err(e) = source
+            .and_then(|source| super::cache::save(target, source, resume.run_id(), resume.offset()))
+        {
+            log::warn!("failed to cache ctf file `{}`: {}", target.display(), e)
+        }
+
         prof.all_do(
             || log::info!("done loading ctf file `{}`", target.display()),
             |desc, sw| log::info!("| {:>9}: {}", desc, sw),
@@ -161,7 +414,109 @@ impl Watcher {
             log::info!("done loading ctf file `{}`", target.display());
         }
 
-        Ok(())
+        Ok(Parsed::new(resume))
+    }
+
+    /// Adds the packets appended to a memtrace CTF file since it was loaded to the data.
+    ///
+    /// Only the bytes appended to the trace are read and decoded, unless the trace was loaded from
+    /// its cache: the parser state is not cached, so the whole trace is decoded again once.
+    ///
+    /// Returns `None` if the header of the trace changed, *i.e.* the profiling run restarted, in
+    /// which case the data is left untouched and the trace must be reloaded with [`ctf_run`].
+    ///
+    /// [`ctf_run`]: #method.ctf_run (The ctf_run method)
+    fn ctf_append(target: impl AsRef<Path>, prev: Parsed) -> Res<Option<Parsed>> {
+        let target = target.as_ref();
+
+        // Identify the trace before reading it, in case it changes while we read it.
+        let source = super::cache::Source::of(target);
+        let run = ctf::run_id_of_file(target)
+            .chain_err(|| format!("while parsing ctf file `{}`", target.display()))?;
+        if run != prev.run {
+            return Ok(None);
+        }
+
+        let log_new_bytes = |count: usize| {
+            log::info!(
+                "loading {} new bytes of ctf file `{}`",
+                count,
+                target.display()
+            )
+        };
+        let new_action = |factory: &mut data::FullFactory, builder| {
+            err::unwrap_register_fatal(factory.build_new(builder))
+        };
+        let dead_action = |factory: &mut data::FullFactory, timestamp, uid| {
+            err::unwrap_register_fatal(factory.add_dead(timestamp, uid))
+        };
+        let mark_timestamp =
+            |factory: &mut data::FullFactory, timestamp| factory.mark_timestamp(timestamp);
+
+        let (truncation, resume, mut factory) = match prev.resume {
+            Some(resume) => {
+                let bytes = match ctf::compress::read_file_from(target, prev.len, None)
+                    .chain_err(|| format!("while loading ctf file `{}`", target.display()))?
+                {
+                    Some(bytes) => bytes,
+                    // The trace is shorter than what we parsed.
+                    None => return Ok(None),
+                };
+                if bytes.is_empty() {
+                    return Ok(Some(Parsed::new(resume)));
+                }
+                log_new_bytes(bytes.len());
+
+                let mut factory = data::FullFactory::new(false);
+                let (truncation, resume) = ctf::parse_resumed(
+                    resume,
+                    &bytes,
+                    &mut factory,
+                    |_| (),
+                    new_action,
+                    dead_action,
+                    mark_timestamp,
+                )
+                .chain_err(|| format!("while parsing ctf file `{}`", target.display()))?;
+                (truncation, resume, factory)
+            }
+            None => {
+                let bytes = ctf::compress::read_file(target, |_| ())
+                    .chain_err(|| format!("while loading ctf file `{}`", target.display()))?;
+                if bytes.len() < prev.len {
+                    return Ok(None);
+                } else if bytes.len() == prev.len {
+                    return Ok(Some(prev));
+                }
+                log_new_bytes(bytes.len() - prev.len);
+
+                let mut factory = data::FullFactory::new(false);
+                let (truncation, resume) = ctf::parse_appended(
+                    prev.len,
+                    &bytes,
+                    &mut factory,
+                    |_| (),
+                    new_action,
+                    dead_action,
+                    mark_timestamp,
+                )
+                .chain_err(|| format!("while parsing ctf file `{}`", target.display()))?;
+                (truncation, resume, factory)
+            }
+        };
+        factory.fill_stats()?;
+        // Release the lock on the data, needed to create the snapshot.
+        drop(factory);
+
+        if truncation.is_none() {
+            if let Err(e) = source.and_then(|source| {
+                super::cache::save(target, source, resume.run_id(), resume.offset())
+            }) {
+                log::warn!("failed to cache ctf file `{}`: {}", target.display(), e)
+            }
+        }
+
+        Ok(Some(Parsed::new(resume)))
     }
 
     /// Runs the watcher.
@@ -171,15 +526,8 @@ impl Watcher {
         // First init read.
         'first_init: loop {
             if let Some(init) = self.try_read_init()? {
-                let mut data =
-                    super::get_mut().chain_err(|| "while registering the initial state")?;
-
-                if data.has_init() {
-                    bail!("live profiling restart is not supported yet")
-                } else {
-                    data.reset(&self.dir, init)
-                }
-
+                self.reset_run(init)
+                    .chain_err(|| "while registering the initial state")?;
                 break 'first_init;
             } else {
                 sleep(Duration::from_millis(200));
@@ -33,6 +33,8 @@ pub mod sub;
 
 #[cfg(any(test, feature = "server"))]
 pub mod gen;
+#[cfg(any(test, feature = "server"))]
+pub mod index;
 
 #[cfg(any(test, feature = "server"))]
 pub use gen::FilterGen;
@@ -177,6 +179,9 @@ pub struct Filters {
     filters: Vec<Filter>,
     /// Remembers which filter is responsible for an allocation.
     memory: BTMap<uid::Alloc, uid::Filter>,
+    /// Per-line aggregates over the time index of the data.
+    #[cfg(any(test, feature = "server"))]
+    index: index::LineIndex,
 }
 
 impl Filters {
@@ -187,11 +192,15 @@ impl Filters {
             catch_all: FilterSpec::new_catch_all(),
             everything: FilterSpec::new_everything(),
             memory: BTMap::new(),
+            #[cfg(any(test, feature = "server"))]
+            index: index::LineIndex::new(vec![]),
         }
     }
     /// Constructor.
     pub fn new_with(filters: Vec<Filter>) -> Self {
         Filters {
+            #[cfg(any(test, feature = "server"))]
+            index: index::LineIndex::new(filters.clone()),
             filters,
             catch_all: FilterSpec::new_catch_all(),
             everything: FilterSpec::new_everything(),
@@ -294,6 +303,50 @@ impl Filters {
         self.memory.get(alloc).map(|uid| *uid)
     }
 
+    /// Brings the per-line aggregates up to date.
+    ///
+    /// The aggregates are recomputed from scratch if the predicates of the filters changed since
+    /// they were computed. Does nothing if some filter is time-dependent, the aggregates classify
+    /// allocations once and for all. Returns `false` in this case.
+    ///
+    /// Called each time new points are computed, *i.e.* when new data is loaded, so that queries
+    /// over a time window do not have to aggregate the whole data.
+    #[cfg(any(test, feature = "server"))]
+    pub fn update_index(&mut self, data: &data::Data) -> Res<bool> {
+        if self.filters.iter().any(Filter::is_time_dependent) {
+            return Ok(false);
+        }
+        if self.changes_predicates(self.index.filters()) {
+            self.index = index::LineIndex::new(self.filters.clone())
+        }
+        self.index
+            .update(data)
+            .chain_err(|| "while updating the per-line aggregates")?;
+        Ok(true)
+    }
+
+    /// Size of the lines before some time, and where to resume iterating over the events.
+    ///
+    /// Brings the per-line aggregates up to date first, see [`update_index`] and
+    /// [`index::LineIndex::size_before`].
+    ///
+    /// Returns `None` if some filter is time-dependent.
+    ///
+    /// [`update_index`]: #method.update_index (The update_index method)
+    /// [`index::LineIndex::size_before`]: index/struct.LineIndex.html#method.size_before
+    /// (The size_before method on LineIndex)
+    #[cfg(any(test, feature = "server"))]
+    pub fn size_before(
+        &mut self,
+        data: &data::Data,
+        time: time::SinceStart,
+    ) -> Res<Option<(BTMap<uid::Line, u64>, Option<(uid::Alloc, time::SinceStart)>)>> {
+        if !self.update_index(data)? {
+            return Ok(None);
+        }
+        Ok(self.index.size_before(data, time))
+    }
+
     /// Resets all the filters.
     pub fn reset(&mut self) {
         self.memory.clear()
@@ -329,16 +382,35 @@ impl Filters {
         let (res, should_reload) = match msg {
             RequestNew => (self.add_new(), false),
             RequestNewSub(uid) => (self.add_new_sub(uid), false),
+            RequestNewWith(name, sub) => (self.add_new_with(name, sub), false),
             Revert => (self.revert(), false),
             UpdateAll {
                 everything,
                 filters,
                 catch_all,
-            } => (self.update_all(everything, filters, catch_all), true),
+            } => {
+                let should_reload = self.changes_predicates(&filters);
+                (
+                    self.update_all(everything, filters, catch_all),
+                    should_reload,
+                )
+            }
         };
         res.map(|msgs| (msgs, should_reload))
     }
 
+    /// True if replacing the filters with `filters` changes the allocations each line captures.
+    ///
+    /// Changes to the name or the color of the filters do not impact points.
+    fn changes_predicates(&self, filters: &[Filter]) -> bool {
+        self.filters.len() != filters.len()
+            || self
+                .filters
+                .iter()
+                .zip(filters)
+                .any(|(old, new)| old.uid() != new.uid() || old.subs != new.subs)
+    }
+
     /// Sends all the filters to the client.
     pub fn revert(&self) -> Res<msg::to_client::Msgs> {
         let catch_all = self.catch_all.clone();
@@ -370,6 +442,20 @@ impl Filters {
         Ok(vec![msg])
     }
 
+    /// Adds a new filter with a name and a subfilter.
+    pub fn add_new_with(
+        &mut self,
+        name: String,
+        sub: sub::RawSubFilter,
+    ) -> Res<msg::to_client::Msgs> {
+        let mut spec = FilterSpec::new(Color::random());
+        spec.set_name(name);
+        let mut filter = Filter::new(spec).chain_err(|| "while creating new filter")?;
+        filter.insert(sub)?;
+        let msg = msg::to_client::FiltersMsg::add(filter);
+        Ok(vec![msg])
+    }
+
     /// Adds a new sub-filter.
     pub fn add_new_sub(&mut self, uid: uid::Filter) -> Res<msg::to_client::Msgs> {
         let msg = msg::to_client::FiltersMsg::add_sub(uid, SubFilter::default());
@@ -461,6 +547,13 @@ impl Filter {
         true
     }
 
+    /// True if the filter has a lifetime subfilter.
+    ///
+    /// Whether such a filter matches a live allocation depends on the current time.
+    pub fn is_time_dependent(&self) -> bool {
+        self.iter().any(|sub| sub.kind() == FilterKind::Lifetime)
+    }
+
     /// Removes a subfilter.
     pub fn remove(&mut self, sub_uid: uid::SubFilter) -> Res<()> {
         let prev = self.subs.remove(&sub_uid);
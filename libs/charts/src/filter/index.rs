@@ -0,0 +1,356 @@
+/*<LICENSE>
+    This file is part of Memthol.
+
+    Copyright (C) 2020 OCamlPro.
+
+    Memthol is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Memthol is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Memthol.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Per-line aggregates over the time index of the data.
+//!
+//! Stores, for each bucket of the [`TimeIndex`], the total size of the allocations of each line
+//! that are alive at the end of the bucket. Charts over a time window use it to get the state of
+//! the lines at the start of the window without going through all the events before it.
+//!
+//! The sizes are not stored for each bucket. Every [`CHECKPOINT_PERIOD`] buckets, the index stores
+//! the sizes of all the lines, and each non-empty bucket only stores the new sizes of the lines it
+//! changes.
+//!
+//! Allocations are classified when their bucket is aggregated, which only happens once the current
+//! time of the data is past the bucket. Their death is subtracted from the line they were added to,
+//! whatever the filters say at that point.
+//!
+//! [`TimeIndex`]: ../../data/index/struct.TimeIndex.html (The TimeIndex struct)
+//! [`CHECKPOINT_PERIOD`]: constant.CHECKPOINT_PERIOD.html (The CHECKPOINT_PERIOD constant)
+
+prelude! {}
+
+use data::index::TimeIndex;
+
+/// Number of buckets between two checkpoints.
+pub const CHECKPOINT_PERIOD: usize = 64;
+
+/// Per-line aggregates over the time index of the data.
+#[derive(Debug, Clone)]
+pub struct LineIndex {
+    /// Filters the aggregates were computed for.
+    filters: Vec<Filter>,
+    /// Epoch of the time index the aggregates were computed for.
+    epoch: Option<usize>,
+    /// Number of buckets aggregated.
+    aggregated: usize,
+    /// Size of the live allocations of each line at the start of bucket `i * CHECKPOINT_PERIOD`,
+    /// for each checkpoint `i`.
+    checkpoints: Vec<BTMap<uid::Line, u64>>,
+    /// New size of the lines changed by each non-empty bucket, in bucket order.
+    changes: Vec<(usize, Vec<(uid::Line, u64)>)>,
+    /// Size of the live allocations of each line at the end of the last bucket aggregated.
+    sizes: BTMap<uid::Line, u64>,
+    /// Line of the allocations alive at the end of the last bucket aggregated.
+    lines: BTMap<uid::Alloc, uid::Line>,
+}
+
+impl LineIndex {
+    /// Constructor.
+    pub fn new(filters: Vec<Filter>) -> Self {
+        let mut res = Self {
+            filters,
+            epoch: None,
+            aggregated: 0,
+            checkpoints: vec![],
+            changes: vec![],
+            sizes: BTMap::new(),
+            lines: BTMap::new(),
+        };
+        res.clear();
+        res
+    }
+
+    /// Forgets all the aggregates.
+    fn clear(&mut self) {
+        self.aggregated = 0;
+        self.checkpoints.clear();
+        self.changes.clear();
+        self.lines.clear();
+        self.sizes.clear();
+        self.sizes.insert(uid::Line::Everything, 0);
+        for filter in &self.filters {
+            self.sizes.insert(uid::Line::Filter(filter.uid()), 0);
+        }
+        self.sizes.insert(uid::Line::CatchAll, 0);
+    }
+
+    /// Filters the aggregates were computed for.
+    pub fn filters(&self) -> &[Filter] {
+        &self.filters
+    }
+
+    /// Line of an allocation.
+    fn line_of(&self, current_time: &time::SinceStart, alloc: &Alloc) -> uid::Line {
+        self.filters
+            .iter()
+            .find(|filter| filter.apply(current_time, alloc))
+            .map(|filter| uid::Line::Filter(filter.uid()))
+            .unwrap_or(uid::Line::CatchAll)
+    }
+
+    /// Aggregates the buckets the current time of the data is past.
+    pub fn update(&mut self, data: &data::Data) -> Res<()> {
+        let time_index = data.time_index();
+        if self.epoch != Some(time_index.epoch()) {
+            self.clear();
+            self.epoch = Some(time_index.epoch())
+        }
+
+        let current_time = data.current_time();
+        let closed = TimeIndex::bucket_of(*current_time);
+
+        while self.aggregated < closed {
+            let index = self.aggregated;
+            if index % CHECKPOINT_PERIOD == 0 {
+                self.checkpoints.push(self.sizes.clone())
+            }
+            self.aggregated += 1;
+
+            let bucket = match time_index.get(index) {
+                Some(bucket) => bucket,
+                None => continue,
+            };
+            if bucket.new_allocs().is_empty() && bucket.dead_allocs().is_empty() {
+                continue;
+            }
+
+            let mut changed = BTSet::new();
+            for uid in bucket.new_allocs() {
+                let alloc = &data[*uid];
+                let size = alloc.real_size as u64;
+                let line = self.line_of(current_time, alloc);
+                self.lines.insert(*uid, line);
+                for line in &[line, uid::Line::Everything] {
+                    *self.sizes.entry(*line).or_insert(0) += size;
+                    changed.insert(*line);
+                }
+            }
+            for uid in bucket.dead_allocs() {
+                let alloc = &data[*uid];
+                let size = alloc.real_size as u64;
+                // Allocations die after they are created, their bucket is already aggregated.
+                let line = self.lines.remove(uid).ok_or_else(|| {
+                    format!(
+                        "death of unknown allocation #{} while indexing bucket #{}",
+                        uid, index
+                    )
+                })?;
+                for line in &[line, uid::Line::Everything] {
+                    let val = self.sizes.entry(*line).or_insert(0);
+                    if *val < size {
+                        bail!("underflow on {} while indexing bucket #{}", line, index)
+                    }
+                    *val -= size;
+                    changed.insert(*line);
+                }
+            }
+
+            let changes = changed
+                .into_iter()
+                .map(|line| (line, self.sizes.get(&line).cloned().unwrap_or(0)))
+                .collect();
+            self.changes.push((index, changes))
+        }
+
+        Ok(())
+    }
+
+    /// Size of the lines before some time, and where to resume iterating over the events.
+    ///
+    /// The sizes are the ones at the start of the bucket `time` falls in, or at the start of the
+    /// first bucket not aggregated yet if it comes first. The events from that point on, obtained
+    /// by passing the position returned to [`Data::iter_new_events`], still need to be processed.
+    ///
+    /// Returns `None` if no bucket before `time` is aggregated.
+    ///
+    /// [`Data::iter_new_events`]: ../../data/struct.Data.html#method.iter_new_events
+    /// (The iter_new_events method on Data)
+    pub fn size_before(
+        &self,
+        data: &data::Data,
+        time: time::SinceStart,
+    ) -> Option<(BTMap<uid::Line, u64>, Option<(uid::Alloc, time::SinceStart)>)> {
+        let index = std::cmp::min(TimeIndex::bucket_of(time), self.aggregated);
+        if index == 0 {
+            return None;
+        }
+        let resume = data.time_index().resume_at(index);
+        if index == self.aggregated {
+            return Some((self.sizes.clone(), resume));
+        }
+
+        // Bucket `checkpoint` is aggregated since `checkpoint <= index < self.aggregated`.
+        let checkpoint = index / CHECKPOINT_PERIOD * CHECKPOINT_PERIOD;
+        let mut sizes = self.checkpoints[index / CHECKPOINT_PERIOD].clone();
+        let start = self
+            .changes
+            .partition_point(|(bucket, _)| *bucket < checkpoint);
+        let end = self.changes.partition_point(|(bucket, _)| *bucket < index);
+        for (_, changes) in &self.changes[start..end] {
+            for (line, size) in changes {
+                sizes.insert(*line, *size);
+            }
+        }
+        Some((sizes, resume))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use filter::{ord::Cmp, FilterSpec, SizeFilter};
+
+    fn at(millis: u64) -> time::SinceStart {
+        std::time::Duration::from_millis(millis).into()
+    }
+
+    /// Allocations as `(size, toc, tod)`, times in milliseconds.
+    const ALLOCS: &[(u32, u64, Option<u64>)] = &[
+        (4, 10, Some(350)),
+        (20, 50, Some(120)),
+        (12, 130, None),
+        (3, 140, Some(160)),
+        (30, 220, Some(910)),
+        (5, 480, Some(1020)),
+        (40, 700, None),
+        (7, 990, Some(995)),
+        (11, 1010, None),
+        (9, 7000, Some(13000)),
+        (16, 7010, Some(7020)),
+        (6, 13050, None),
+    ];
+
+    fn data() -> data::Data {
+        let mut data = data::Data::new();
+        data.reset("dump", alloc::Init::default());
+        let trace = alloc::mem::trace::add(vec![]);
+        let labels = alloc::mem::labels::add(vec![]);
+        for (size, toc, _) in ALLOCS {
+            let builder = alloc::Builder::new(
+                None,
+                alloc::AllocKind::Minor,
+                *size,
+                trace,
+                labels,
+                at(*toc),
+                None,
+            );
+            data.build_new(builder).unwrap()
+        }
+        let mut deaths: Vec<_> = ALLOCS
+            .iter()
+            .enumerate()
+            .filter_map(|(uid, (_, _, tod))| tod.map(|tod| (tod, uid)))
+            .collect();
+        deaths.sort();
+        for (tod, uid) in deaths {
+            data.add_dead(at(tod), uid::Alloc::from(uid)).unwrap()
+        }
+        data
+    }
+
+    fn filters() -> Vec<Filter> {
+        let mut filter = Filter::new(FilterSpec::new(Color::BLACK)).unwrap();
+        filter.insert(SizeFilter::cmp(Cmp::Ge, 10)).unwrap();
+        vec![filter]
+    }
+
+    /// Adds the events from `since` that happen before `time` to `sizes`.
+    fn scan(
+        index: &LineIndex,
+        data: &data::Data,
+        since: Option<(uid::Alloc, time::SinceStart)>,
+        time: time::SinceStart,
+        sizes: &mut BTMap<uid::Line, u64>,
+    ) {
+        data.iter_new_events(since, |event| {
+            let (timestamp, add, alloc) = event.either(
+                |alloc| (alloc.toc, true, alloc),
+                |(tod, alloc)| (tod, false, alloc),
+            );
+            if timestamp >= time {
+                return Ok(false);
+            }
+            let line = index.line_of(data.current_time(), alloc);
+            for line in &[line, uid::Line::Everything] {
+                let val = sizes.entry(*line).or_insert(0);
+                if add {
+                    *val += alloc.real_size as u64
+                } else {
+                    *val -= alloc.real_size as u64
+                }
+            }
+            Ok(true)
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn size_before_and_resume_is_full_scan() {
+        let mut data = data();
+        let mut index = LineIndex::new(filters());
+
+        // Aggregate in three steps, the last one goes over several checkpoints.
+        data.mark_timestamp(at(450));
+        index.update(&data).unwrap();
+        assert_eq!(index.aggregated, 4);
+        data.mark_timestamp(at(1050));
+        index.update(&data).unwrap();
+        assert_eq!(index.aggregated, 10);
+        data.mark_timestamp(at(14_000));
+        index.update(&data).unwrap();
+        assert_eq!(index.aggregated, 140);
+        assert_eq!(index.checkpoints.len(), 3);
+        // Buckets without events are not stored.
+        assert_eq!(index.changes.len(), 10);
+
+        let mut zero = BTMap::new();
+        for line in &[
+            uid::Line::Everything,
+            uid::Line::Filter(index.filters()[0].uid()),
+            uid::Line::CatchAll,
+        ] {
+            zero.insert(*line, 0);
+        }
+
+        let mut resumed_once = false;
+        for time in &[
+            0, 50, 100, 120, 150, 250, 360, 999, 1000, 1015, 1040, 1200, 6400, 7005, 7015, 7100,
+            12_800, 12_999, 13_000, 13_040, 13_100, 13_999, 14_000, 20_000,
+        ] {
+            let time = at(*time);
+
+            let mut full = zero.clone();
+            scan(&index, &data, None, time, &mut full);
+
+            let (mut resumed, since) = if let Some((sizes, since)) = index.size_before(&data, time)
+            {
+                resumed_once = true;
+                (sizes, since)
+            } else {
+                (zero.clone(), None)
+            };
+            scan(&index, &data, since, time, &mut resumed);
+
+            assert_eq!(full, resumed, "at {}", time)
+        }
+        assert!(resumed_once)
+    }
+}
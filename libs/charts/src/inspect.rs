@@ -0,0 +1,187 @@
+/*<LICENSE>
+    This file is part of Memthol.
+
+    Copyright (C) 2020 OCamlPro.
+
+    Memthol is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Memthol is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Memthol.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Allocation inspection.
+//!
+//! Lets the client drill down from a point of a chart to the allocations behind it. The client
+//! sends a [`Query`] for the allocations of a filter line that are live at some point in time, the
+//! server answers with one [`Page`] of these allocations.
+//!
+//! [`Query`]: struct.Query.html (The Query struct)
+//! [`Page`]: struct.Page.html (The Page struct)
+
+prelude! {}
+
+/// Number of allocations in a page.
+pub const PAGE_SIZE: usize = 20;
+
+/// A request for the allocations of a filter line live at some point in time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Query {
+    /// Filter line to inspect.
+    pub line: uid::Line,
+    /// Time at which the allocations must be live.
+    pub time: time::SinceStart,
+    /// Index of the page requested, starts at `0`.
+    pub page: usize,
+}
+impl Query {
+    /// Constructor, for the first page.
+    pub fn new(line: uid::Line, time: time::SinceStart) -> Self {
+        Self {
+            line,
+            time,
+            page: 0,
+        }
+    }
+
+    /// Same query, for a different page.
+    pub fn with_page(self, page: usize) -> Self {
+        Self { page, ..self }
+    }
+    /// Same query, for a different line, starting at the first page.
+    pub fn with_line(self, line: uid::Line) -> Self {
+        Self::new(line, self.time)
+    }
+}
+base::implement! {
+    impl Query {
+        Display {
+            |&self, fmt| write!(fmt, "{} @ {}, page {}", self.line, self.time, self.page)
+        }
+    }
+}
+
+/// An allocation, as displayed by the inspector.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AllocInfo {
+    /// UID of the allocation.
+    pub uid: uid::Alloc,
+    /// Allocation kind.
+    pub kind: alloc::AllocKind,
+    /// Size of the allocation.
+    pub size: u32,
+    /// Real size of the allocation.
+    pub real_size: u32,
+    /// Time of creation.
+    pub toc: time::SinceStart,
+    /// Time of death.
+    pub tod: Option<time::SinceStart>,
+    /// Symbolized allocation-site callstack, most recent call first.
+    pub trace: Vec<String>,
+    /// Source line of each location of the callstack, if the sources are available locally.
+    ///
+    /// See `alloc_data::remap::source_line`.
+    pub sources: Vec<Option<String>>,
+}
+impl AllocInfo {
+    /// Constructor.
+    pub fn new(alloc: &Alloc) -> Self {
+        let trace = alloc.trace();
+        Self {
+            uid: alloc.uid,
+            kind: alloc.kind,
+            size: alloc.size,
+            real_size: alloc.real_size,
+            toc: alloc.toc,
+            tod: alloc.tod,
+            trace: trace.iter().map(|loc| loc.to_string()).collect(),
+            sources: trace
+                .iter()
+                .map(|cloc| alloc::remap::source_line(&cloc.loc))
+                .collect(),
+        }
+    }
+
+    /// Lifetime of the allocation, `None` if it never dies.
+    pub fn lifetime(&self) -> Option<time::SinceStart> {
+        self.tod.map(|tod| tod - self.toc)
+    }
+}
+
+/// A page of the allocations answering a query.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Page {
+    /// Query this page answers.
+    pub query: Query,
+    /// Total number of allocations answering the query.
+    pub total: usize,
+    /// Allocations of the page.
+    pub allocs: Vec<AllocInfo>,
+}
+impl Page {
+    /// Number of pages for the query.
+    pub fn page_count(&self) -> usize {
+        self.total.div_ceil(PAGE_SIZE)
+    }
+    /// True if there is a page before this one.
+    pub fn has_prev(&self) -> bool {
+        self.query.page > 0
+    }
+    /// True if there is a page after this one.
+    pub fn has_next(&self) -> bool {
+        self.query.page + 1 < self.page_count()
+    }
+
+    /// Retrieves an allocation of the page.
+    pub fn get(&self, uid: uid::Alloc) -> Option<&AllocInfo> {
+        self.allocs.iter().find(|alloc| alloc.uid == uid)
+    }
+}
+
+#[cfg(any(test, feature = "server"))]
+impl Page {
+    /// Answers a query.
+    ///
+    /// An allocation belongs to a filter line if the first filter it matches is the filter of the
+    /// line. The catch-all line gets the allocations no filter matches, and the everything line
+    /// gets all of them.
+    pub fn new(filters: &Filters, query: Query) -> Res<Self> {
+        let data = data::get().chain_err(|| format!("while answering query {}", query))?;
+        let time = query.time;
+
+        let line_of = |alloc: &Alloc| {
+            filters
+                .filters()
+                .iter()
+                .find(|filter| filter.apply(&time, alloc))
+                .map(|filter| uid::Line::Filter(filter.uid()))
+                .unwrap_or(uid::Line::CatchAll)
+        };
+        let matches = |alloc: &Alloc| match query.line {
+            uid::Line::Everything => true,
+            line => line_of(alloc) == line,
+        };
+
+        let (mut total, mut allocs) = (0, Vec::with_capacity(PAGE_SIZE));
+        let first = query.page * PAGE_SIZE;
+        for alloc in data.iter_live_at(time).filter(|alloc| matches(alloc)) {
+            if total >= first && allocs.len() < PAGE_SIZE {
+                allocs.push(AllocInfo::new(alloc))
+            }
+            total += 1
+        }
+
+        Ok(Self {
+            query,
+            total,
+            allocs,
+        })
+    }
+}
@@ -0,0 +1,541 @@
+/*<LICENSE>
+    This file is part of Memthol.
+
+    Copyright (C) 2020 OCamlPro.
+
+    Memthol is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Memthol is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Memthol.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Leak-suspect detection.
+//!
+//! Flags the allocation sites that look like they leak:
+//!
+//! - sites whose live size grows (almost) monotonically over the run,
+//! - sites whose allocations are mostly never freed, and
+//! - sites whose allocations live longer and longer as the run goes on.
+//!
+//! Sites responsible for less than [`MIN_COUNT`] allocations are ignored. Each suspect comes with
+//! a subfilter capturing the allocations of its site, so that the client can turn it into a
+//! filter directly.
+//!
+//! [`MIN_COUNT`]: constant.MIN_COUNT.html (The MIN_COUNT constant)
+
+prelude! {}
+
+/// Number of suspects reported to the client.
+pub const CLIENT_TOP: usize = 20;
+/// Number of time buckets the run is split into for the analysis.
+pub const BUCKETS: usize = 50;
+/// Minimum number of allocations for a site to be analyzed.
+pub const MIN_COUNT: usize = 10;
+/// Minimum number of steps between buckets for a trend to be meaningful.
+pub const MIN_STEPS: usize = 10;
+/// Minimum percentage of non-decreasing steps for a trend to count as growing.
+pub const GROWTH_PERCENT: u8 = 90;
+/// Minimum percentage of allocations never freed for a site to be flagged.
+pub const NEVER_FREED_PERCENT: u8 = 90;
+/// Minimum ratio between the late and early average lifetimes for a site to be flagged.
+pub const LIFETIME_RATIO: f64 = 2.0;
+
+/// Reason why a site is suspected to leak.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Reason {
+    /// The live size of the site grows (almost) monotonically.
+    Growing,
+    /// Most allocations of the site are never freed.
+    NeverFreed,
+    /// The allocations of the site live longer and longer.
+    GrowingLifetime,
+}
+impl Reason {
+    /// Short description of the reason.
+    pub fn desc(self) -> &'static str {
+        match self {
+            Self::Growing => "growing live size",
+            Self::NeverFreed => "never freed",
+            Self::GrowingLifetime => "growing lifetime",
+        }
+    }
+}
+base::implement! {
+    impl Reason {
+        Display {
+            |&self, fmt| self.desc().fmt(fmt)
+        }
+    }
+}
+
+/// An allocation site suspected to leak.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Suspect {
+    /// Allocation site, `None` if unknown.
+    pub site: Option<String>,
+    /// Subfilter capturing the allocations of the site, `None` if the site is unknown.
+    ///
+    /// The subfilter is a suffix pattern, `** <site>`: it only matches the last frame of a
+    /// callstack, *i.e.* the allocation site, not the site appearing anywhere else in the stack.
+    /// Filters only know about files and lines though, so this also captures the allocations of
+    /// the sites at the same line but at a different column.
+    pub filter: Option<filter::sub::RawSubFilter>,
+    /// Reasons why the site is suspected, never empty.
+    pub reasons: Vec<Reason>,
+    /// Number of allocations at this site.
+    pub count: usize,
+    /// Total size of the allocations at this site, in bytes.
+    pub bytes: u64,
+    /// Number of allocations at this site still alive at the end of the run.
+    pub live_count: usize,
+    /// Size of the allocations at this site still alive at the end of the run, in bytes.
+    pub live_bytes: u64,
+    /// Percentage of the steps between time buckets where the live size does not decrease.
+    pub growth: u8,
+    /// Early and late average lifetimes of the allocations that die, if there are enough of them.
+    pub lifetimes: Option<(time::Lifetime, time::Lifetime)>,
+}
+impl Suspect {
+    /// Percentage of the allocations of the site that are never freed.
+    pub fn never_freed(&self) -> u8 {
+        percent(self.live_count, self.count)
+    }
+
+    /// Name for the filter of this suspect.
+    pub fn filter_name(&self) -> String {
+        format!("leak: {}", self.site.as_deref().unwrap_or("<unknown>"))
+    }
+}
+
+/// Leak suspects of a trace.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Report {
+    /// Number of allocation sites analyzed.
+    pub site_count: usize,
+    /// Number of suspects found.
+    pub suspect_count: usize,
+    /// Top suspects, most reasons first and then most live bytes first.
+    pub suspects: Vec<Suspect>,
+}
+
+/// Percentage of `num` over `den`, `0` if `den` is zero.
+fn percent(num: usize, den: usize) -> u8 {
+    (num * 100).checked_div(den).unwrap_or(0) as u8
+}
+
+/// Amount of seconds of a duration.
+#[cfg(any(test, feature = "server"))]
+fn secs(duration: impl time::DurationExt) -> f64 {
+    duration.as_duration().as_secs_f64()
+}
+
+/// Accumulates the information needed to analyze a site.
+#[cfg(any(test, feature = "server"))]
+struct SiteWork {
+    /// Number of allocations.
+    count: usize,
+    /// Total size of the allocations.
+    bytes: u64,
+    /// Number of allocations never freed.
+    live_count: usize,
+    /// Size of the allocations never freed.
+    live_bytes: u64,
+    /// Live size variation over each time bucket.
+    deltas: Vec<i64>,
+    /// Sum of the lifetimes (in seconds) and number of the allocations created in each time
+    /// bucket that die during the run.
+    lifetimes: Vec<(f64, usize)>,
+}
+
+#[cfg(any(test, feature = "server"))]
+impl SiteWork {
+    /// Constructor.
+    fn new() -> Self {
+        Self {
+            count: 0,
+            bytes: 0,
+            live_count: 0,
+            live_bytes: 0,
+            deltas: vec![0; BUCKETS],
+            lifetimes: vec![(0.0, 0); BUCKETS],
+        }
+    }
+
+    /// Registers an allocation, `bucket_of` yields the time bucket of a point in time.
+    fn add(&mut self, alloc: &Alloc, bucket_of: impl Fn(time::SinceStart) -> usize) {
+        let bytes = alloc.real_size as u64;
+        let toc_bucket = bucket_of(alloc.toc);
+        self.count += 1;
+        self.bytes += bytes;
+        self.deltas[toc_bucket] += bytes as i64;
+        if let Some(tod) = alloc.tod {
+            self.deltas[bucket_of(tod)] -= bytes as i64;
+            let lifetimes = &mut self.lifetimes[toc_bucket];
+            lifetimes.0 += secs(tod - alloc.toc);
+            lifetimes.1 += 1;
+        } else {
+            self.live_count += 1;
+            self.live_bytes += bytes;
+        }
+    }
+
+    /// Percentage of non-decreasing steps in a series, `None` if there are not enough steps.
+    fn non_decreasing<T: PartialOrd>(series: &[T]) -> Option<u8> {
+        let steps = series.len().saturating_sub(1);
+        if steps < MIN_STEPS {
+            return None;
+        }
+        let non_decreasing = series
+            .windows(2)
+            .filter(|window| window[0] <= window[1])
+            .count();
+        Some(percent(non_decreasing, steps))
+    }
+
+    /// Turns the work into a suspect, if the site looks like it leaks.
+    fn into_suspect(self, site: Option<alloc::Loc>) -> Option<Suspect> {
+        if self.count < MIN_COUNT {
+            return None;
+        }
+        let mut reasons = vec![];
+
+        // Live size at the end of each bucket, starting at the first bucket with live memory.
+        let mut live = 0;
+        let series: Vec<i64> = self
+            .deltas
+            .iter()
+            .map(|delta| {
+                live += delta;
+                live
+            })
+            .skip_while(|live| *live == 0)
+            .collect();
+        let growth = Self::non_decreasing(&series).unwrap_or(0);
+        if let (Some(first), Some(last)) = (series.first(), series.last()) {
+            let peak = series.iter().copied().max().unwrap_or(0);
+            if growth >= GROWTH_PERCENT && last > first && *last * 10 >= peak * 9 {
+                reasons.push(Reason::Growing)
+            }
+        }
+
+        if percent(self.live_count, self.count) >= NEVER_FREED_PERCENT {
+            reasons.push(Reason::NeverFreed)
+        }
+
+        // Average lifetime of the allocations created in each bucket, ignoring empty buckets.
+        let averages: Vec<f64> = self
+            .lifetimes
+            .iter()
+            .filter(|(_, count)| *count > 0)
+            .map(|(sum, count)| sum / *count as f64)
+            .collect();
+        let lifetimes = Self::non_decreasing(&averages).map(|trend| {
+            let quarter = averages.len() / 4;
+            let mean = |avgs: &[f64]| avgs.iter().sum::<f64>() / avgs.len() as f64;
+            let early = mean(&averages[..quarter]);
+            let late = mean(&averages[averages.len() - quarter..]);
+            if trend >= GROWTH_PERCENT && late >= early * LIFETIME_RATIO {
+                reasons.push(Reason::GrowingLifetime)
+            }
+            let lifetime = |secs| time::Lifetime::from(time::Duration::from_secs_f64(secs));
+            (lifetime(early), lifetime(late))
+        });
+
+        if reasons.is_empty() {
+            return None;
+        }
+
+        let filter = site.as_ref().map(|loc| {
+            let spec = filter::loc::LocSpec::Value {
+                value: loc.file.to_string(),
+                line: filter::loc::LineSpec::line(loc.line),
+            };
+            // Leading wildcard only, matches the last frame of the callstack.
+            filter::loc::LocFilter::contain(vec![filter::loc::LocSpec::Anything, spec]).into()
+        });
+
+        Some(Suspect {
+            site: site.as_ref().map(alloc::Loc::to_string),
+            filter,
+            reasons,
+            count: self.count,
+            bytes: self.bytes,
+            live_count: self.live_count,
+            live_bytes: self.live_bytes,
+            growth,
+            lifetimes,
+        })
+    }
+}
+
+#[cfg(any(test, feature = "server"))]
+impl Report {
+    /// Analyzes some data.
+    ///
+    /// Only keeps the `top` suspects.
+    pub fn new(data: &data::Data, top: usize) -> Self {
+        let end = secs(*data.current_time());
+        let bucket_of = |time: time::SinceStart| {
+            if end > 0.0 {
+                ((secs(time) / end * BUCKETS as f64) as usize).min(BUCKETS - 1)
+            } else {
+                0
+            }
+        };
+
+        let mut sites: HMap<Option<alloc::Loc>, SiteWork> = HMap::new();
+        for alloc in data.iter_allocs() {
+            let site = alloc.alloc_site_do(|site| site.map(|cloc| cloc.loc.clone()));
+            sites
+                .entry(site)
+                .or_insert_with(SiteWork::new)
+                .add(alloc, bucket_of)
+        }
+        let site_count = sites.len();
+
+        let mut suspects: Vec<_> = sites
+            .into_iter()
+            .filter_map(|(site, work)| work.into_suspect(site))
+            .collect();
+        let suspect_count = suspects.len();
+        suspects.sort_by(|lft, rgt| {
+            rgt.reasons
+                .len()
+                .cmp(&lft.reasons.len())
+                .then(rgt.live_bytes.cmp(&lft.live_bytes))
+                .then(lft.site.cmp(&rgt.site))
+        });
+        suspects.truncate(top);
+
+        Self {
+            site_count,
+            suspect_count,
+            suspects,
+        }
+    }
+
+    /// Analyzes the global data.
+    pub fn get(top: usize) -> Res<Self> {
+        Ok(Self::new(&*data::get()?, top))
+    }
+}
+
+base::implement! {
+    impl Display for Report {
+        |&self, fmt| {
+            writeln!(fmt, "|===| Leak suspects")?;
+            writeln!(
+                fmt,
+                "| {} suspect(s) out of {} allocation site(s)",
+                self.suspect_count, self.site_count
+            )?;
+            for suspect in &self.suspects {
+                writeln!(fmt, "|===| {}", suspect.site.as_deref().unwrap_or("<unknown>"))?;
+                let reasons: Vec<_> = suspect.reasons.iter().map(|reason| reason.desc()).collect();
+                writeln!(fmt, "| reasons: {}", reasons.join(", "))?;
+                writeln!(
+                    fmt,
+                    "| {} allocation(s), {} bytes, {}% never freed ({} bytes)",
+                    suspect.count,
+                    suspect.bytes,
+                    suspect.never_freed(),
+                    suspect.live_bytes
+                )?;
+                writeln!(fmt, "| live size does not decrease {}% of the time", suspect.growth)?;
+                if let Some((early, late)) = suspect.lifetimes {
+                    writeln!(fmt, "| average lifetime: {} early, {} late", early, late)?;
+                }
+                if let Some(filter) = suspect.filter.as_ref() {
+                    writeln!(fmt, "| filter: {}", filter)?;
+                }
+            }
+            write!(fmt, "|===|")
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc_data::parser::Parseable;
+
+    /// End of the run, in seconds.
+    const END: f64 = 100.0;
+
+    /// Time since start from some seconds.
+    fn at(secs: f64) -> time::SinceStart {
+        std::time::Duration::from_secs_f64(secs).into()
+    }
+
+    /// Frame at some line of `leak.ml`.
+    fn frame(line: usize) -> alloc::CLoc {
+        alloc::CLoc::parse(format!("`leak.ml`:{}:0-5#1", line)).unwrap()
+    }
+
+    /// Site string of a line of `leak.ml`.
+    fn site(line: usize) -> Option<String> {
+        Some(frame(line).loc.to_string())
+    }
+
+    /// An allocation spec: callstack, time of creation and time of death, in seconds.
+    type Spec = (Vec<alloc::CLoc>, f64, Option<f64>);
+
+    /// Allocations at the site at line `line`, with a `main` frame before it.
+    fn at_site(line: usize, allocs: impl IntoIterator<Item = (f64, Option<f64>)>) -> Vec<Spec> {
+        allocs
+            .into_iter()
+            .map(|(toc, tod)| (vec![frame(0), frame(line)], toc, tod))
+            .collect()
+    }
+
+    /// Builds the data for some allocations, all of size `10`.
+    fn data(specs: Vec<Spec>) -> data::Data {
+        let mut data = data::Data::new();
+        data.reset("dump", alloc::Init::default());
+
+        // Chronological events, `true` for creations.
+        let mut events = vec![];
+        for (idx, (_, toc, tod)) in specs.iter().enumerate() {
+            events.push((*toc, true, idx));
+            if let Some(tod) = tod {
+                events.push((*tod, false, idx))
+            }
+        }
+        events.sort_by(|lft, rgt| lft.partial_cmp(rgt).unwrap());
+
+        let labels = alloc::mem::labels::add(vec![]);
+        let mut uids = vec![None; specs.len()];
+        let mut next_uid = 0usize;
+        for (time, is_new, idx) in events {
+            if is_new {
+                let trace = alloc::mem::trace::add(specs[idx].0.clone());
+                let builder = alloc::Builder::new(
+                    None,
+                    alloc::AllocKind::Minor,
+                    10,
+                    trace,
+                    labels,
+                    at(time),
+                    None,
+                );
+                data.build_new(builder).unwrap();
+                uids[idx] = Some(uid::Alloc::from(next_uid));
+                next_uid += 1;
+            } else {
+                data.add_dead(at(time), uids[idx].unwrap()).unwrap()
+            }
+        }
+        data.mark_timestamp(at(END));
+        data
+    }
+
+    /// Suspects of some allocations, by site.
+    fn suspects(specs: Vec<Spec>) -> BTMap<Option<String>, Suspect> {
+        let report = Report::new(&data(specs), usize::MAX);
+        report
+            .suspects
+            .into_iter()
+            .map(|suspect| (suspect.site.clone(), suspect))
+            .collect()
+    }
+
+    /// Allocations whose lifetime grows, created in `buckets` buckets of the analysis.
+    ///
+    /// All of them die in the bucket they are created in.
+    fn growing_lifetimes(line: usize, buckets: usize) -> Vec<Spec> {
+        let bucket_span = END / BUCKETS as f64;
+        at_site(
+            line,
+            (0..buckets).flat_map(|bucket| {
+                let toc = bucket as f64 * bucket_span + 0.1;
+                let tod = toc + 0.1 * (bucket + 1) as f64;
+                vec![(toc, Some(tod)), (toc, Some(tod))]
+            }),
+        )
+    }
+
+    #[test]
+    fn reasons() {
+        let mut specs = vec![];
+        // Half the allocations are never freed, the others live for one second.
+        specs.extend(at_site(
+            1,
+            (0..100).flat_map(|t| {
+                let toc = t as f64 + 0.5;
+                let tod = Some(toc + 1.0).filter(|tod| *tod < END);
+                vec![(toc, None), (toc, tod)]
+            }),
+        ));
+        // Never freed, all created at the same time so the live size is flat.
+        specs.extend(at_site(2, (0..20).map(|_| (50.5, None))));
+        // Lifetimes grow, all allocations die before the end.
+        specs.extend(at_site(
+            3,
+            (0..90).map(|t| {
+                let toc = t as f64 + 0.5;
+                (toc, Some(toc + 0.1 + 0.05 * t as f64))
+            }),
+        ));
+        // Freed quickly, not a suspect.
+        specs.extend(at_site(
+            4,
+            (0..100).map(|t| (t as f64 + 0.5, Some(t as f64 + 0.6))),
+        ));
+
+        let suspects = suspects(specs);
+        let reasons = |line| suspects.get(&site(line)).map(|s| s.reasons.clone());
+        assert_eq!(reasons(1), Some(vec![Reason::Growing]));
+        assert_eq!(reasons(2), Some(vec![Reason::NeverFreed]));
+        assert_eq!(reasons(3), Some(vec![Reason::GrowingLifetime]));
+        assert_eq!(reasons(4), None);
+        assert_eq!(suspects.len(), 3);
+
+        let suspect = &suspects[&site(2)];
+        assert_eq!(suspect.count, 20);
+        assert_eq!(suspect.never_freed(), 100);
+        assert_eq!(suspect.live_bytes, 200);
+        let (early, late) = suspects[&site(3)].lifetimes.unwrap();
+        assert!(late > early);
+    }
+
+    #[test]
+    fn cut_offs() {
+        let mut specs = vec![];
+        specs.extend(at_site(1, (0..MIN_COUNT - 1).map(|_| (50.5, None))));
+        specs.extend(at_site(2, (0..MIN_COUNT).map(|_| (50.5, None))));
+        specs.extend(growing_lifetimes(3, MIN_STEPS));
+        specs.extend(growing_lifetimes(4, MIN_STEPS + 1));
+
+        let suspects = suspects(specs);
+        assert!(!suspects.contains_key(&site(1)));
+        assert_eq!(suspects[&site(2)].reasons, vec![Reason::NeverFreed]);
+        assert!(!suspects.contains_key(&site(3)));
+        assert_eq!(suspects[&site(4)].reasons, vec![Reason::GrowingLifetime]);
+        assert_eq!(suspects.len(), 2);
+    }
+
+    #[test]
+    fn filter_matches_the_site_only() {
+        let mut specs = at_site(2, (0..20).map(|_| (50.5, None)));
+        // Site `2` is in the callstack, but is not the allocation site.
+        specs.push((vec![frame(2), frame(5)], 60.0, None));
+        let data = data(specs);
+
+        let report = Report::new(&data, usize::MAX);
+        let filter = report.suspects[0].filter.as_ref().unwrap();
+        let now = *data.current_time();
+        let captured = data
+            .iter_allocs()
+            .filter(|alloc| filter.apply(&now, alloc))
+            .count();
+        assert_eq!(captured, 20)
+    }
+}
@@ -46,8 +46,13 @@ pub mod color;
 #[cfg(any(test, feature = "server"))]
 pub mod data;
 pub mod filter;
+pub mod inspect;
+pub mod leak;
+pub mod marker;
 pub mod msg;
 pub mod point;
+pub mod snapshot;
+pub mod view;
 
 #[cfg(any(test, feature = "server"))]
 pub use chart::Chart;
@@ -81,6 +86,10 @@ pub struct Charts {
     to_client_msgs: msg::to_client::Msgs,
     /// Settings.
     settings: settings::Charts,
+    /// Number of times the run restarted.
+    restarts: usize,
+    /// Markers generated from the data, and the time of the data they were generated for.
+    markers: Option<(time::SinceStart, Vec<marker::Marker>)>,
 }
 
 #[cfg(any(test, feature = "server"))]
@@ -93,6 +102,8 @@ impl Charts {
             start_time: None,
             to_client_msgs: msg::to_client::Msgs::with_capacity(7),
             settings: settings::Charts::new(),
+            restarts: 0,
+            markers: None,
         }
     }
 
@@ -104,6 +115,10 @@ impl Charts {
     pub fn filters(&self) -> &Filters {
         &self.filters
     }
+    /// Global settings.
+    pub fn settings(&self) -> &settings::Charts {
+        &self.settings
+    }
     /// Start time.
     pub fn start_time(&self) -> Option<&time::Date> {
         self.start_time.as_ref()
@@ -121,6 +136,8 @@ impl Charts {
             start_time: None,
             to_client_msgs: msg::to_client::Msgs::with_capacity(7),
             settings: settings::Charts::new(),
+            restarts: 0,
+            markers: None,
         })
     }
 
@@ -143,21 +160,89 @@ impl Charts {
 #[cfg(any(test, feature = "server"))]
 impl Charts {
     /// Restarts the charts and the filters if needed.
+    ///
+    /// When the run was restarted (the start time changed), the filters and the charts are
+    /// re-generated from the new data. The messages telling the client to drop its charts and to
+    /// use the new filters and charts are pushed on the internal list of messages, see
+    /// [`drain_msgs`].
+    ///
+    /// [`drain_msgs`]: #method.drain_msgs (The drain_msgs method)
     fn restart_if_needed(&mut self) -> Res<bool> {
-        let data = data::get();
+        let data = data::get().chain_err(|| "while checking if the charts should be restarted")?;
         let start_time = data
-            .and_then(|data| data.start_time())
+            .start_time()
             .chain_err(|| "while checking if the charts should be restarted")?;
-        if self.start_time != Some(start_time) {
-            self.start_time = Some(start_time);
-            for chart in &mut self.charts {
-                chart.reset(&self.filters)
+        match self.start_time {
+            Some(prev) if prev == start_time => Ok(false),
+            None => {
+                self.start_time = Some(start_time);
+                for chart in &mut self.charts {
+                    chart.reset(&self.filters)
+                }
+                self.filters.reset();
+                Ok(true)
+            }
+            Some(_) => {
+                log::info!("run restarted, re-generating filters and charts");
+                self.start_time = Some(start_time);
+                self.restarts += 1;
+                self.markers = None;
+                let (filters, charts) = Filters::auto_gen(&*data, filter::gen::get())
+                    .chain_err(|| "while re-generating filters after a restart")?;
+                self.filters = filters;
+                self.charts = charts;
+
+                self.to_client_msgs.push(msg::to_client::ChartsMsg::clear());
+                self.to_client_msgs.push(msg::to_client::FiltersMsg::revert(
+                    self.filters.everything().clone(),
+                    self.filters.filters().clone(),
+                    self.filters.catch_all().clone(),
+                ));
+                for chart in &self.charts {
+                    self.to_client_msgs
+                        .push(msg::to_client::ChartsMsg::new_chart(
+                            chart.spec().clone(),
+                            chart.settings().clone(),
+                        ))
+                }
+                Ok(true)
+            }
+        }
+    }
+
+    /// Regenerates the markers if the data changed since they were last generated.
+    ///
+    /// Pushes the new markers on the internal list of messages, see [`drain_msgs`].
+    ///
+    /// [`drain_msgs`]: #method.drain_msgs (The drain_msgs method)
+    fn update_markers(&mut self) -> Res<()> {
+        let data = data::get().chain_err(|| "while generating markers")?;
+        let current_time = *data.current_time();
+        match &self.markers {
+            Some((time, _)) if *time == current_time => (),
+            prev => {
+                let markers = marker::Marker::of_data(&data, self.restarts);
+                let changed = match prev {
+                    Some((_, prev)) => prev != &markers,
+                    None => true,
+                };
+                if changed {
+                    self.to_client_msgs
+                        .push(msg::to_client::ChartsMsg::markers(markers.clone()))
+                }
+                self.markers = Some((current_time, markers))
             }
-            self.filters.reset();
-            Ok(true)
-        } else {
-            Ok(false)
         }
+        Ok(())
+    }
+
+    /// Drains the messages for the client produced by the last call to [`new_points`].
+    ///
+    /// These messages must be sent to the client **before** the points.
+    ///
+    /// [`new_points`]: #method.new_points (The new_points method)
+    pub fn drain_msgs<'me>(&'me mut self) -> impl Iterator<Item = msg::to_client::Msg> + 'me {
+        self.to_client_msgs.drain(0..)
     }
 
     /// Extracts the new points for the different charts.
@@ -166,6 +251,11 @@ impl Charts {
     /// true when the init file of the run has changed (the run was restarted).
     pub fn new_points(&mut self, init: bool) -> Res<(point::ChartPoints, bool)> {
         let restarted = self.restart_if_needed()?;
+        self.update_markers()?;
+        {
+            let data = data::get().chain_err(|| "while updating the per-line aggregates")?;
+            self.filters.update_index(&data)?;
+        }
         let mut points = point::ChartPoints::new();
         for chart in &mut self.charts {
             if let Some(chart_points) = chart.new_points(
@@ -173,6 +263,11 @@ impl Charts {
                 &mut self.filters,
                 self.settings.time_windopt(),
             )? {
+                chart.register_sent(
+                    &chart_points,
+                    restarted || init,
+                    self.settings.time_windopt(),
+                )?;
                 let prev = points.insert(chart.uid(), chart_points);
                 debug_assert!(prev.is_none())
             }
@@ -243,33 +338,43 @@ impl Charts {
         Ok(reloaded)
     }
 
-    /// Recomputes all the points, and returns them as a message for the client.
+    /// Recomputes the points of the charts, and returns a message patching the client's points.
+    ///
+    /// Only reloads the chart with UID `uid` if any, all charts otherwise. `refresh_filters`
+    /// indicates the filters changed, in which case all the charts are recomputed. Otherwise, the
+    /// charts whose settings did not change since their points were last sent are skipped.
     pub fn reload_points(
         &mut self,
         uid: Option<uid::Chart>,
         refresh_filters: bool,
     ) -> Res<msg::to_client::Msg> {
-        let mut new_points = point::ChartPoints::new();
+        let mut patches = BTMap::new();
         for chart in &mut self.charts {
             if let Some(uid) = uid {
                 if chart.uid() != uid {
                     continue;
                 }
             }
+            if !refresh_filters && chart.is_up_to_date(self.settings.time_windopt()) {
+                log::debug!("skipping reload of chart #{}, nothing changed", chart.uid());
+                continue;
+            }
             chart.reset(&self.filters);
             self.filters.reset();
             let points_opt = chart
                 .new_points(true, &mut self.filters, self.settings.time_windopt())
                 .chain_err(|| format!("while generating points for chart #{}", chart.uid()))?;
             if let Some(points) = points_opt {
-                let prev = new_points.insert(chart.uid(), points);
-                if prev.is_some() {
-                    bail!("chart UID collision on #{}", chart.uid())
+                if let Some(patch) = chart.patch_sent(points, self.settings.time_windopt()) {
+                    let prev = patches.insert(chart.uid(), patch);
+                    if prev.is_some() {
+                        bail!("chart UID collision on #{}", chart.uid())
+                    }
                 }
             }
         }
-        Ok(msg::to_client::ChartsMsg::new_points(
-            new_points,
+        Ok(msg::to_client::ChartsMsg::patch_points(
+            patches,
             refresh_filters,
         ))
     }
@@ -291,6 +396,34 @@ impl Charts {
                 self.to_client_msgs.extend(msgs);
                 should_reload
             }
+            LoadTrace(file) => {
+                data::traces::select(file)?;
+                false
+            }
+            Inspect(query) => {
+                let page = inspect::Page::new(&self.filters, query)
+                    .chain_err(|| "while inspecting allocations")?;
+                self.to_client_msgs.push(msg::to_client::Msg::inspect(page));
+                false
+            }
+            Snapshot(query) => {
+                let snapshot =
+                    snapshot::Snapshot::get(query).chain_err(|| "while taking a heap snapshot")?;
+                self.to_client_msgs
+                    .push(msg::to_client::Msg::snapshot(snapshot));
+                false
+            }
+            Leaks => {
+                let report = leak::Report::get(leak::CLIENT_TOP)
+                    .chain_err(|| "while looking for leak suspects")?;
+                self.to_client_msgs.push(msg::to_client::Msg::leaks(report));
+                false
+            }
+            Share => {
+                let id = view::store(self.view()?).chain_err(|| "while sharing the view")?;
+                self.to_client_msgs.push(msg::to_client::Msg::shared(id));
+                false
+            }
         };
 
         Ok((self.to_client_msgs.drain(0..), reload))
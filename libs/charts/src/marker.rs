@@ -0,0 +1,219 @@
+/*<LICENSE>
+    This file is part of Memthol.
+
+    Copyright (C) 2020 OCamlPro.
+
+    Memthol is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Memthol is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Memthol.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Markers on the time axis of the charts.
+//!
+//! A marker is a labeled point in time drawn as a vertical line on all the time charts. Markers
+//! are either annotations added by the user, which are part of the [charts settings], or generated
+//! from the data by [`Marker::of_data`]: start of the run (or of the restarted run), end of the
+//! run, and collection bursts, *i.e.* periods where a lot of allocations die.
+//!
+//! [charts settings]: ../chart/settings/struct.Charts.html (The Charts settings struct)
+//! [`Marker::of_data`]: struct.Marker.html#method.of_data (The of_data function)
+
+prelude! {}
+
+/// Number of time buckets used to detect collection bursts.
+pub const BURST_BUCKETS: usize = 200;
+/// Ratio to the average number of collections per bucket above which a bucket is a burst.
+pub const BURST_RATIO: usize = 4;
+/// Minimal number of collections in a bucket for it to be a burst.
+pub const MIN_BURST_COLLECTIONS: usize = 10;
+/// Maximal number of collection bursts reported, only the ones with the most collections are.
+pub const MAX_BURSTS: usize = 5;
+
+/// Kinds of markers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Kind {
+    /// Annotation added by the user.
+    Annotation,
+    /// Start of the run.
+    Start,
+    /// Start of the run after the run restarted.
+    Restart,
+    /// End of the run.
+    End,
+    /// Period during which a lot of allocations die.
+    CollectionBurst,
+}
+impl Kind {
+    /// True for annotations.
+    pub fn is_annotation(self) -> bool {
+        self == Self::Annotation
+    }
+
+    /// Short description of the kind.
+    pub fn desc(self) -> &'static str {
+        match self {
+            Self::Annotation => "annotation",
+            Self::Start => "start",
+            Self::Restart => "restart",
+            Self::End => "end",
+            Self::CollectionBurst => "collection burst",
+        }
+    }
+}
+base::implement! {
+    impl Kind {
+        Display {
+            |&self, fmt| self.desc().fmt(fmt)
+        }
+    }
+}
+
+/// A labeled point in time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Marker {
+    /// Kind of marker.
+    pub kind: Kind,
+    /// Time of the marker.
+    pub time: time::SinceStart,
+    /// End of the period the marker stands for, if any.
+    pub until: Option<time::SinceStart>,
+    /// Label of the marker.
+    pub label: String,
+}
+impl Marker {
+    /// Constructor.
+    pub fn new(kind: Kind, time: time::SinceStart, label: impl Into<String>) -> Self {
+        Self {
+            kind,
+            time,
+            until: None,
+            label: label.into(),
+        }
+    }
+
+    /// Constructs an annotation.
+    ///
+    /// ```rust
+    /// use charts::marker::*;
+    /// let annotation = Marker::annotation(std::time::Duration::from_secs(3).into(), "warm-up done");
+    /// assert!(annotation.kind.is_annotation());
+    /// assert_eq!(annotation.to_string(), "annotation @ 3.000000: warm-up done");
+    /// ```
+    pub fn annotation(time: time::SinceStart, label: impl Into<String>) -> Self {
+        Self::new(Kind::Annotation, time, label)
+    }
+
+    /// Sets the end of the period the marker stands for.
+    pub fn with_until(mut self, until: time::SinceStart) -> Self {
+        self.until = Some(until);
+        self
+    }
+}
+base::implement! {
+    impl Marker {
+        Display {
+            |&self, fmt| write!(fmt, "{} @ {}: {}", self.kind, self.time, self.label)
+        }
+    }
+}
+
+#[cfg(any(test, feature = "server"))]
+impl Marker {
+    /// Generates the markers for some data.
+    ///
+    /// `restarts` is the number of times the run restarted, if it is not zero the start of the run
+    /// is a restart boundary.
+    pub fn of_data(data: &data::Data, restarts: usize) -> Vec<Self> {
+        let mut markers = vec![];
+        let start = time::SinceStart::zero();
+        if restarts == 0 {
+            markers.push(Self::new(Kind::Start, start, "start"))
+        } else {
+            markers.push(Self::new(
+                Kind::Restart,
+                start,
+                format!("restart #{}", restarts),
+            ))
+        }
+        markers.extend(Self::collection_bursts(data));
+        let end = *data.current_time();
+        if !end.is_zero() {
+            markers.push(Self::new(Kind::End, end, "end"))
+        }
+        markers
+    }
+
+    /// Detects the collection bursts of some data.
+    ///
+    /// Splits the run in [`BURST_BUCKETS`] buckets, and counts the allocations dying in each of
+    /// them. A bucket is a burst if it has at least [`BURST_RATIO`] times the average number of
+    /// collections per bucket, and at least [`MIN_BURST_COLLECTIONS`] collections. Consecutive
+    /// bursts are merged, and only the [`MAX_BURSTS`] bursts with the most collections are kept.
+    ///
+    /// [`BURST_BUCKETS`]: constant.BURST_BUCKETS.html (The BURST_BUCKETS constant)
+    /// [`BURST_RATIO`]: constant.BURST_RATIO.html (The BURST_RATIO constant)
+    /// [`MIN_BURST_COLLECTIONS`]: constant.MIN_BURST_COLLECTIONS.html
+    /// (The MIN_BURST_COLLECTIONS constant)
+    /// [`MAX_BURSTS`]: constant.MAX_BURSTS.html (The MAX_BURSTS constant)
+    fn collection_bursts(data: &data::Data) -> Vec<Self> {
+        let end = data.current_time().as_secs_f64();
+        if end <= 0. {
+            return vec![];
+        }
+        let bucket_secs = end / BURST_BUCKETS as f64;
+
+        let mut buckets = vec![0; BURST_BUCKETS];
+        let mut total = 0;
+        for (tod, count) in data.iter_tods() {
+            let index = (tod.as_secs_f64() / bucket_secs) as usize;
+            buckets[index.min(BURST_BUCKETS - 1)] += count;
+            total += count;
+        }
+        let threshold = (total * BURST_RATIO / BURST_BUCKETS).max(MIN_BURST_COLLECTIONS);
+
+        // Consecutive burst buckets: first bucket, last bucket, and number of collections.
+        let mut bursts: Vec<(usize, usize, usize)> = vec![];
+        for (index, count) in buckets.into_iter().enumerate() {
+            if count < threshold {
+                continue;
+            }
+            match bursts.last_mut() {
+                Some((_, last, total)) if *last + 1 == index => {
+                    *last = index;
+                    *total += count
+                }
+                _ => bursts.push((index, index, count)),
+            }
+        }
+        bursts.sort_by(|(_, _, c_1), (_, _, c_2)| c_2.cmp(c_1));
+        bursts.truncate(MAX_BURSTS);
+        bursts.sort();
+
+        let time_at = |bucket: usize| {
+            time::SinceStart::from(std::time::Duration::from_secs_f64(
+                bucket_secs * bucket as f64,
+            ))
+        };
+        bursts
+            .into_iter()
+            .map(|(first, last, count)| {
+                let (time, until) = (time_at(first), time_at(last + 1));
+                let label = format!(
+                    "{} collections in {}s",
+                    count,
+                    (until - time).display_millis()
+                );
+                Self::new(Kind::CollectionBurst, time, label).with_until(until)
+            })
+            .collect()
+    }
+}
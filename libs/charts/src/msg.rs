@@ -22,6 +22,105 @@
 prelude! {}
 use filter::*;
 
+/// Size in bytes above which encoded messages are compressed.
+pub const COMPRESSION_THRESHOLD: usize = 1024;
+
+/// Maximum size in bytes of a decompressed message.
+///
+/// Messages come from the network, decoding fails instead of decompressing them past this size.
+pub const MAX_DECOMPRESSED_LEN: usize = 128 << 20;
+
+/// Header byte of uncompressed encoded messages.
+const RAW_HEADER: u8 = 0;
+/// Header byte of compressed encoded messages.
+const DEFLATE_HEADER: u8 = 1;
+
+/// Encodes a message as bytes.
+///
+/// The first byte of the result is a header indicating whether the rest is compressed, which
+/// happens when the message is bigger than [`COMPRESSION_THRESHOLD`] bytes. The message itself is
+/// encoded with `bincode`, and compressed with deflate.
+///
+/// [`COMPRESSION_THRESHOLD`]: constant.COMPRESSION_THRESHOLD.html
+pub fn encode(msg: &impl ::serde::Serialize) -> Res<Vec<u8>> {
+    let bytes = base::bincode::serialize(msg)?;
+    let (header, mut bytes) = if bytes.len() > COMPRESSION_THRESHOLD {
+        let compressed = miniz_oxide::deflate::compress_to_vec(&bytes, 6);
+        (DEFLATE_HEADER, compressed)
+    } else {
+        (RAW_HEADER, bytes)
+    };
+    bytes.insert(0, header);
+    Ok(bytes)
+}
+
+/// Decodes a message encoded by [`encode`].
+///
+/// [`encode`]: fn.encode.html
+///
+/// ```rust
+/// # use charts::msg::*;
+/// let small = "small".to_string();
+/// let big: Vec<u64> = (0..10_000).map(|n| n % 7).collect();
+///
+/// let small_bytes = encode(&small).unwrap();
+/// let big_bytes = encode(&big).unwrap();
+/// assert!(big_bytes.len() < COMPRESSION_THRESHOLD);
+///
+/// assert_eq!(decode::<String>(&small_bytes).unwrap(), small);
+/// assert_eq!(decode::<Vec<u64>>(&big_bytes).unwrap(), big);
+/// ```
+pub fn decode<T: ::serde::de::DeserializeOwned>(bytes: &[u8]) -> Res<T> {
+    let (header, bytes) = match bytes.split_first() {
+        Some(pair) => pair,
+        None => bail!("cannot decode an empty message"),
+    };
+    match *header {
+        RAW_HEADER => Ok(base::bincode::deserialize(bytes)?),
+        DEFLATE_HEADER => {
+            let bytes = inflate(bytes, MAX_DECOMPRESSED_LEN)?;
+            Ok(base::bincode::deserialize(&bytes)?)
+        }
+        header => bail!("unknown message header `{}`", header),
+    }
+}
+
+/// Decompresses some bytes, fails if the result is bigger than `max_len` bytes.
+///
+/// Does not use `decompress_to_vec_with_limit` as it doubles its buffer and fails as soon as the
+/// doubled buffer would exceed the limit, even if the actual result fits.
+fn inflate(bytes: &[u8], max_len: usize) -> Res<Vec<u8>> {
+    use miniz_oxide::inflate::{
+        core::{decompress, inflate_flags, DecompressorOxide},
+        TINFLStatus,
+    };
+    let flags = inflate_flags::TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF;
+    let mut decomp = Box::<DecompressorOxide>::default();
+    let mut res = vec![0; bytes.len().saturating_mul(2).min(max_len)];
+    let (mut in_pos, mut out_pos) = (0, 0);
+    loop {
+        let (status, in_consumed, out_consumed) =
+            decompress(&mut decomp, &bytes[in_pos..], &mut res, out_pos, flags);
+        in_pos += in_consumed;
+        out_pos += out_consumed;
+        match status {
+            TINFLStatus::Done => {
+                res.truncate(out_pos);
+                return Ok(res);
+            }
+            TINFLStatus::HasMoreOutput if res.len() < max_len => {
+                let new_len = res.len().saturating_mul(2).max(1).min(max_len);
+                res.resize(new_len, 0)
+            }
+            TINFLStatus::HasMoreOutput => bail!(
+                "failed to decompress message: more than {} bytes once decompressed",
+                max_len
+            ),
+            e => bail!("failed to decompress message: {:?}", e),
+        }
+    }
+}
+
 /// Chart settings message.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ChartSettingsMsg {
@@ -33,6 +132,10 @@ pub enum ChartSettingsMsg {
     SetDisplayMode(chart::settings::DisplayMode),
     /// Changes the resolution of a chart.
     SetResolution(chart::settings::Resolution),
+    /// Changes the downsampling strategy of a chart.
+    SetDownsampling(chart::settings::Downsampling),
+    /// Changes the local time window of a chart, `None` to use the global time window.
+    SetTimeWindow(Option<TimeWindopt>),
 }
 
 impl ChartSettingsMsg {
@@ -70,6 +173,25 @@ impl ChartSettingsMsg {
     {
         (uid, Self::SetResolution(resolution.into())).into()
     }
+
+    /// Changes the downsampling strategy of a chart.
+    pub fn set_downsampling<Res>(
+        uid: uid::Chart,
+        downsampling: chart::settings::Downsampling,
+    ) -> Res
+    where
+        (uid::Chart, Self): Into<Res>,
+    {
+        (uid, Self::SetDownsampling(downsampling)).into()
+    }
+
+    /// Changes the local time window of a chart.
+    pub fn set_time_window<Res>(uid: uid::Chart, time_window: Option<TimeWindopt>) -> Res
+    where
+        (uid::Chart, Self): Into<Res>,
+    {
+        (uid, Self::SetTimeWindow(time_window)).into()
+    }
 }
 
 impl fmt::Display for ChartSettingsMsg {
@@ -79,6 +201,22 @@ impl fmt::Display for ChartSettingsMsg {
             Self::SetDisplayMode(mode) => write!(fmt, "set display mode: {}", mode.desc()),
             Self::ChangeTitle(title) => write!(fmt, "change title: {}", title),
             Self::SetResolution(resolution) => write!(fmt, "set resolution: {}", resolution),
+            Self::SetDownsampling(downsampling) => {
+                write!(fmt, "set downsampling: {}", downsampling)
+            }
+            Self::SetTimeWindow(Some(window)) => write!(
+                fmt,
+                "set time window: [{}, {}]",
+                window
+                    .lbound
+                    .map(|lb| lb.to_string())
+                    .unwrap_or_else(|| "_".into()),
+                window
+                    .ubound
+                    .map(|ub| ub.to_string())
+                    .unwrap_or_else(|| "_".into()),
+            ),
+            Self::SetTimeWindow(None) => write!(fmt, "set time window: global"),
         }
     }
 }
@@ -98,25 +236,66 @@ pub mod to_server {
 
         /// Operation over filters.
         Filters(FiltersMsg),
+
+        /// Loads a trace from the trace directory.
+        LoadTrace(String),
+
+        /// Requests the allocations of a filter line live at some point in time.
+        Inspect(inspect::Query),
+
+        /// Requests a heap snapshot.
+        Snapshot(snapshot::Query),
+
+        /// Requests the leak suspects of the trace.
+        Leaks,
+
+        /// Requests an id for the current view, to share it.
+        Share,
     }
     impl fmt::Display for Msg {
         fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
             match self {
                 Self::Charts(msg) => write!(fmt, "charts({})", msg),
                 Self::Filters(msg) => write!(fmt, "filters({})", msg),
+                Self::LoadTrace(file) => write!(fmt, "load trace({})", file),
+                Self::Inspect(query) => write!(fmt, "inspect({})", query),
+                Self::Snapshot(query) => write!(fmt, "snapshot({})", query),
+                Self::Leaks => write!(fmt, "leaks"),
+                Self::Share => write!(fmt, "share"),
             }
         }
     }
 
     impl Msg {
-        /// Encodes the message as bytes.
+        /// Constructor for `LoadTrace`.
+        pub fn load_trace(file: impl Into<String>) -> Self {
+            Self::LoadTrace(file.into())
+        }
+        /// Constructor for `Inspect`.
+        pub fn inspect(query: inspect::Query) -> Self {
+            Self::Inspect(query)
+        }
+        /// Constructor for `Snapshot`.
+        pub fn snapshot(query: snapshot::Query) -> Self {
+            Self::Snapshot(query)
+        }
+        /// Constructor for `Leaks`.
+        pub fn leaks() -> Self {
+            Self::Leaks
+        }
+        /// Constructor for `Share`.
+        pub fn share() -> Self {
+            Self::Share
+        }
+
+        /// Encodes the message as bytes, see [`encode`](../fn.encode.html).
         pub fn to_bytes(&self) -> Res<Vec<u8>> {
-            Ok(base::bincode::serialize(self)?)
+            encode(self)
         }
 
-        /// Decodes the message from bytes.
+        /// Decodes the message from bytes, see [`decode`](../fn.decode.html).
         pub fn from_bytes(bytes: &[u8]) -> Res<Self> {
-            Ok(base::bincode::deserialize(bytes)?)
+            decode(bytes)
         }
     }
 
@@ -240,6 +419,15 @@ pub mod to_server {
         /// (The Add message)
         RequestNewSub(uid::Filter),
 
+        /// Requests a new filter with a name and a subfilter.
+        ///
+        /// Same as [`FiltersMsg::RequestNew`], but the filter is not empty. Used to turn leak
+        /// suspects into filters.
+        ///
+        /// [`FiltersMsg::RequestNew`]: enum.FiltersMsg.html#variant.RequestNew
+        /// (The RequestNew message)
+        RequestNewWith(String, filter::sub::RawSubFilter),
+
         /// Requests the current server-side list of filters.
         Revert,
 
@@ -258,6 +446,7 @@ pub mod to_server {
             match self {
                 Self::RequestNew => write!(fmt, "request new"),
                 Self::RequestNewSub(_) => write!(fmt, "request new sub"),
+                Self::RequestNewWith(name, _) => write!(fmt, "request new with({})", name),
                 Self::Revert => write!(fmt, "revert"),
                 Self::UpdateAll { .. } => write!(fmt, "update all"),
             }
@@ -273,6 +462,10 @@ pub mod to_server {
         pub fn request_new_sub(uid: uid::Filter) -> Msg {
             Self::RequestNewSub(uid).into()
         }
+        /// Requests a new filter with a name and a subfilter.
+        pub fn request_new_with(name: impl Into<String>, sub: filter::sub::RawSubFilter) -> Msg {
+            Self::RequestNewWith(name.into(), sub).into()
+        }
         /// Requests the current server-side list of filters.
         pub fn revert() -> Msg {
             Self::Revert.into()
@@ -328,6 +521,28 @@ pub mod to_client {
         Filters(FiltersMsg),
         /// Some filter statistics.
         FilterStats(filter::stats::AllFilterStats),
+        /// Traces of the trace directory, if any, and the trace currently loaded.
+        Traces {
+            /// Traces of the trace directory.
+            traces: Vec<TraceInfo>,
+            /// Name of the trace currently loaded.
+            active: Option<String>,
+        },
+        /// A page of allocations, answers an inspection query.
+        Inspect(inspect::Page),
+        /// A heap snapshot, answers a snapshot query.
+        Snapshot(snapshot::Snapshot),
+        /// Leak suspects of the trace.
+        Leaks(leak::Report),
+        /// Token of the client's session, sent when the client connects.
+        ///
+        /// The client gives it back when reconnecting to reattach the session. The rest of the
+        /// state of the session follows, the client should drop its own state.
+        Session(String),
+        /// Global charts settings of the session.
+        Settings(settings::Charts),
+        /// Id of the view of the session, answers a share request.
+        Shared(String),
     }
     impl Msg {
         /// Constructor for `Info`.
@@ -357,15 +572,43 @@ pub mod to_client {
         pub fn filter_stats(stats: filter::stats::AllFilterStats) -> Self {
             Self::FilterStats(stats)
         }
+        /// Constructor for `Traces`.
+        pub fn traces(traces: Vec<TraceInfo>, active: Option<String>) -> Self {
+            Self::Traces { traces, active }
+        }
+        /// Constructor for `Inspect`.
+        pub fn inspect(page: inspect::Page) -> Self {
+            Self::Inspect(page)
+        }
+        /// Constructor for `Snapshot`.
+        pub fn snapshot(snapshot: snapshot::Snapshot) -> Self {
+            Self::Snapshot(snapshot)
+        }
+        /// Constructor for `Leaks`.
+        pub fn leaks(report: leak::Report) -> Self {
+            Self::Leaks(report)
+        }
+        /// Constructor for `Session`.
+        pub fn session(token: impl Into<String>) -> Self {
+            Self::Session(token.into())
+        }
+        /// Constructor for `Settings`.
+        pub fn settings(settings: settings::Charts) -> Self {
+            Self::Settings(settings)
+        }
+        /// Constructor for `Shared`.
+        pub fn shared(id: impl Into<String>) -> Self {
+            Self::Shared(id.into())
+        }
 
-        /// Encodes the message as bytes.
+        /// Encodes the message as bytes, see [`encode`](../fn.encode.html).
         pub fn to_bytes(&self) -> Res<Vec<u8>> {
-            Ok(base::bincode::serialize(self)?)
+            encode(self)
         }
 
-        /// Decodes the message from bytes.
+        /// Decodes the message from bytes, see [`decode`](../fn.decode.html).
         pub fn from_bytes(bytes: &[u8]) -> Res<Self> {
-            Ok(base::bincode::deserialize(bytes)?)
+            decode(bytes)
         }
 
         /// True if the message is a minor message.
@@ -379,7 +622,14 @@ pub mod to_client {
                 | Self::LoadProgress(_)
                 | Self::AllocStats(_)
                 | Self::DoneLoading
-                | Self::FilterStats(_) => true,
+                | Self::FilterStats(_)
+                | Self::Traces { .. }
+                | Self::Inspect(_)
+                | Self::Snapshot(_)
+                | Self::Leaks(_)
+                | Self::Session(_)
+                | Self::Settings(_)
+                | Self::Shared(_) => true,
             }
         }
     }
@@ -395,6 +645,13 @@ pub mod to_client {
                 Self::FilterStats(_) => "filter stats".fmt(fmt),
                 Self::DoneLoading => "done loading".fmt(fmt),
                 Self::Filters(_) => "filter".fmt(fmt),
+                Self::Traces { .. } => "traces".fmt(fmt),
+                Self::Inspect(page) => write!(fmt, "inspect({})", page.query),
+                Self::Snapshot(snapshot) => write!(fmt, "snapshot({})", snapshot.query),
+                Self::Leaks(report) => write!(fmt, "leaks({})", report.suspect_count),
+                Self::Session(_) => "session".fmt(fmt),
+                Self::Settings(_) => "settings".fmt(fmt),
+                Self::Shared(id) => write!(fmt, "shared({})", id),
             }
         }
     }
@@ -429,8 +686,29 @@ pub mod to_client {
         },
         /// Some points to append to existing points.
         AddPoints(point::ChartPoints),
+        /// Patches for the points of some charts, sent when reloading points.
+        ///
+        /// Charts that do not appear in `patches` did not change.
+        PatchPoints {
+            /// Maps chart UIDs to the patch for their points.
+            patches: BTMap<uid::Chart, point::PointsPatch>,
+            /// If true, refresh all filters.
+            refresh_filters: bool,
+        },
+        /// Removes all the charts, sent when the profiling run restarts.
+        Clear,
+        /// Markers generated from the data, replace the previous ones.
+        Markers(Vec<marker::Marker>),
     }
     impl ChartsMsg {
+        /// Constructor for `Clear`.
+        pub fn clear() -> Msg {
+            Msg::charts(Self::Clear)
+        }
+        /// Constructor for `Markers`.
+        pub fn markers(markers: Vec<marker::Marker>) -> Msg {
+            Msg::charts(Self::Markers(markers))
+        }
         /// Constructor for `NewChart`.
         pub fn new_chart(spec: chart::ChartSpec, settings: settings::Chart) -> Msg {
             Msg::charts(Self::NewChart(spec, settings))
@@ -447,6 +725,17 @@ pub mod to_client {
             Msg::charts(Self::AddPoints(points))
         }
 
+        /// Constructor for `PatchPoints`.
+        pub fn patch_points(
+            patches: BTMap<uid::Chart, point::PointsPatch>,
+            refresh_filters: bool,
+        ) -> Msg {
+            Msg::charts(Self::PatchPoints {
+                patches,
+                refresh_filters,
+            })
+        }
+
         /// Constructs a `NewPoints` if `overwrite`, and a `AddPoints` otherwise.
         pub fn points(points: point::ChartPoints, overwrite: bool) -> Msg {
             if overwrite {
@@ -484,6 +773,19 @@ pub mod to_client {
                     }
                     Ok(())
                 }
+                Self::PatchPoints { patches, .. } => {
+                    "patch points:".fmt(fmt)?;
+                    for (idx, (uid, patch)) in patches.iter().enumerate() {
+                        write!(fmt, " ")?;
+                        if idx > 0 {
+                            write!(fmt, "| ")?
+                        }
+                        write!(fmt, "{}: {}", uid, patch)?
+                    }
+                    Ok(())
+                }
+                Self::Clear => "clear".fmt(fmt),
+                Self::Markers(markers) => write!(fmt, "{} markers", markers.len()),
             }
         }
     }
@@ -616,3 +918,58 @@ pub mod to_client {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn encode_decode_small() {
+        let msg: Vec<u64> = (0..10).collect();
+        let bytes = encode(&msg).unwrap();
+        assert_eq!(bytes[0], RAW_HEADER);
+        assert!(bytes.len() <= COMPRESSION_THRESHOLD + 1);
+        assert_eq!(decode::<Vec<u64>>(&bytes).unwrap(), msg);
+    }
+
+    #[test]
+    fn encode_decode_big() {
+        let msg: Vec<u64> = (0..1_000).map(|n| n % 13).collect();
+        assert!(base::bincode::serialize(&msg).unwrap().len() > COMPRESSION_THRESHOLD);
+        let bytes = encode(&msg).unwrap();
+        assert_eq!(bytes[0], DEFLATE_HEADER);
+        assert_eq!(decode::<Vec<u64>>(&bytes).unwrap(), msg);
+    }
+
+    #[test]
+    fn encode_decode_threshold() {
+        // A `Vec<u8>` is encoded as its length on 8 bytes followed by its elements.
+        let cases = [
+            (COMPRESSION_THRESHOLD - 8, RAW_HEADER),
+            (COMPRESSION_THRESHOLD - 7, DEFLATE_HEADER),
+        ];
+        for (len, header) in cases.iter() {
+            let msg: Vec<u8> = (0..*len).map(|n| (n % 251) as u8).collect();
+            let raw_len = base::bincode::serialize(&msg).unwrap().len();
+            assert_eq!(raw_len, len + 8);
+            let bytes = encode(&msg).unwrap();
+            assert_eq!(bytes[0], *header, "raw length {}", raw_len);
+            assert_eq!(decode::<Vec<u8>>(&bytes).unwrap(), msg);
+        }
+    }
+
+    #[test]
+    fn inflate_limit() {
+        let zeros = vec![0u8; 10_000];
+        let compressed = miniz_oxide::deflate::compress_to_vec(&zeros, 6);
+        assert!(compressed.len() < 100);
+        assert_eq!(inflate(&compressed, 10_000).unwrap(), zeros);
+        assert!(inflate(&compressed, 9_999).is_err());
+    }
+
+    #[test]
+    fn decode_illegal() {
+        assert!(decode::<Vec<u8>>(&[]).is_err());
+        assert!(decode::<Vec<u8>>(&[42, 0, 0]).is_err());
+    }
+}
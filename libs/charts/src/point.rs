@@ -994,6 +994,45 @@ impl TimePoints {
         }
     }
 
+    /// Time range covered by the points, `None` if there are no points.
+    pub fn time_range(&self) -> Option<TimeWindow> {
+        match self {
+            Self::Size(points) => match (points.first(), points.last()) {
+                (Some(first), Some(last)) => Some(Range::new(first.key, last.key)),
+                _ => None,
+            },
+        }
+    }
+
+    /// Values of all the lines at the point closest to some time.
+    ///
+    /// Points do not necessarily have a value for all lines, in which case the value of a line is
+    /// its last value at or before the point closest to `time`. Returns the time of that point,
+    /// `None` if there are no points.
+    pub fn values_at(
+        &self,
+        time: time::SinceStart,
+    ) -> Option<(time::SinceStart, BTMap<uid::Line, u64>)> {
+        match self {
+            Self::Size(points) => {
+                let after = points.iter().position(|point| point.key >= time);
+                let index = match after {
+                    None => points.len().checked_sub(1)?,
+                    Some(0) => 0,
+                    Some(index) if points[index].key - time < time - points[index - 1].key => index,
+                    Some(index) => index - 1,
+                };
+                let mut values = BTMap::new();
+                for point in &points[0..=index] {
+                    for (line, size) in point.vals.map.iter() {
+                        values.insert(*line, size.size);
+                    }
+                }
+                Some((points[index].key, values))
+            }
+        }
+    }
+
     /// Extends some points with other points, returns `true` iff new points were added.
     ///
     /// Fails if the two kinds of points are not compatible.
@@ -1062,6 +1101,27 @@ impl Points {
         }
     }
 
+    /// Time range covered by the points, `None` if there are no points.
+    pub fn time_range(&self) -> Option<TimeWindow> {
+        match self {
+            Self::Time(points) => points.time_range(),
+        }
+    }
+
+    /// Values of all the lines at the point closest to some time.
+    ///
+    /// See [`TimePoints::values_at`].
+    ///
+    /// [`TimePoints::values_at`]: enum.TimePoints.html#method.values_at
+    pub fn values_at(
+        &self,
+        time: time::SinceStart,
+    ) -> Option<(time::SinceStart, BTMap<uid::Line, u64>)> {
+        match self {
+            Self::Time(points) => points.values_at(time),
+        }
+    }
+
     /// Extends some points with other points, returns `true` iff new points were added.
     ///
     /// Fails if the two kinds of points are not compatible.
@@ -1134,3 +1194,336 @@ base::implement! {
         }
     }
 }
+
+/// Changes turning some points into some new points.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum PolyPatch<Key, Val> {
+    /// Keeps the first `keep` x-axis ticks, and replaces the rest with `points`.
+    Tail {
+        /// Number of x-axis ticks to keep.
+        keep: usize,
+        /// Points replacing the ticks that are not kept.
+        points: PolyPoints<Key, Val>,
+    },
+    /// The x-axis ticks did not change, only some lines did.
+    ///
+    /// Maps each line that changed to its value at each x-axis tick, `None` if the line has no
+    /// value at that tick.
+    Lines(BTMap<uid::Line, Vec<Option<Val>>>),
+}
+
+impl<Key, Val> PolyPatch<Key, Val>
+where
+    Key: PartialEq + Clone,
+    Val: PartialEq + Clone,
+{
+    /// Patch turning `old` into `new`, `None` if they are the same.
+    ///
+    /// When the x-axis ticks are the same and only some of the lines changed, the patch only
+    /// contains these lines. Otherwise, it only contains the points after the longest common
+    /// prefix of `old` and `new`.
+    pub fn new(old: &PolyPoints<Key, Val>, new: &PolyPoints<Key, Val>) -> Option<Self> {
+        let same_keys =
+            old.len() == new.len() && old.iter().zip(new).all(|(old, new)| old.key == new.key);
+
+        if same_keys {
+            let all_lines: BTSet<uid::Line> = old
+                .iter()
+                .chain(new)
+                .flat_map(|point| point.vals.map.keys().cloned())
+                .collect();
+            let mut lines = BTMap::new();
+            for line in all_lines.iter().cloned() {
+                let changed = old
+                    .iter()
+                    .zip(new)
+                    .any(|(old, new)| old.vals.map.get(&line) != new.vals.map.get(&line));
+                if changed {
+                    let vals = new
+                        .iter()
+                        .map(|point| point.vals.map.get(&line).cloned())
+                        .collect();
+                    let prev = lines.insert(line, vals);
+                    debug_assert!(prev.is_none())
+                }
+            }
+            if lines.is_empty() {
+                return None;
+            } else if lines.len() < all_lines.len() {
+                return Some(Self::Lines(lines));
+            }
+        }
+
+        let keep = old
+            .iter()
+            .zip(new)
+            .take_while(|(old, new)| old.key == new.key && old.vals.map == new.vals.map)
+            .count();
+        Some(Self::Tail {
+            keep,
+            points: new[keep..].to_vec(),
+        })
+    }
+
+    /// Applies the patch to some points.
+    pub fn apply(self, points: &mut PolyPoints<Key, Val>) -> Res<()> {
+        match self {
+            Self::Tail { keep, points: tail } => {
+                if keep > points.len() {
+                    bail!(
+                        "cannot keep {} x-axis ticks, only {} are available",
+                        keep,
+                        points.len()
+                    )
+                }
+                points.truncate(keep);
+                points.extend(tail)
+            }
+            Self::Lines(lines) => {
+                for (line, vals) in lines {
+                    if vals.len() != points.len() {
+                        bail!(
+                            "cannot patch line {}: expected {} values, got {}",
+                            line,
+                            points.len(),
+                            vals.len()
+                        )
+                    }
+                    for (point, val) in points.iter_mut().zip(vals) {
+                        match val {
+                            Some(val) => {
+                                point.vals.map.insert(line, val);
+                            }
+                            None => {
+                                point.vals.map.remove(&line);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<Key, Val> fmt::Display for PolyPatch<Key, Val> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Tail { keep, points } => write!(fmt, "keep {}, add {}", keep, points.len()),
+            Self::Lines(lines) => write!(fmt, "{} changed line(s)", lines.len()),
+        }
+    }
+}
+
+/// A patch for some time points.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum TimePatch {
+    /// Patch for size over time points.
+    Size(PolyPatch<time::SinceStart, Size>),
+}
+
+base::implement! {
+    impl TimePatch {
+        Display {
+            |&self, fmt| match self {
+                Self::Size(patch) => patch.fmt(fmt),
+            }
+        }
+    }
+}
+
+/// A patch for the points of a chart.
+///
+/// Sent instead of all the points when the client already has most of them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum PointsPatch {
+    /// Replaces all the points.
+    Overwrite(Points),
+    /// Patch for time points.
+    Time(TimePatch),
+}
+
+base::implement! {
+    impl PointsPatch {
+        Display {
+            |&self, fmt| match self {
+                Self::Overwrite(points) => write!(fmt, "overwrite with {}", points.len()),
+                Self::Time(patch) => patch.fmt(fmt),
+            }
+        }
+    }
+}
+
+impl Points {
+    /// Patch turning these points into `new`, `None` if they are the same.
+    pub fn patch_to(&self, new: &Self) -> Option<PointsPatch> {
+        match (self, new) {
+            (Self::Time(TimePoints::Size(old)), Self::Time(TimePoints::Size(new))) => {
+                PolyPatch::new(old, new).map(|patch| PointsPatch::Time(TimePatch::Size(patch)))
+            }
+        }
+    }
+
+    /// Applies a patch.
+    ///
+    /// Fails if the patch is not compatible with the points.
+    pub fn patch(&mut self, patch: PointsPatch) -> Res<()> {
+        match (self, patch) {
+            (slf, PointsPatch::Overwrite(points)) => {
+                *slf = points;
+                Ok(())
+            }
+            (Self::Time(TimePoints::Size(points)), PointsPatch::Time(TimePatch::Size(patch))) => {
+                patch.apply(points)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Builds points from their keys and the values of their lines.
+    fn points(raw: &[(u64, &[(uid::Line, u64)])]) -> PolyPoints<u64, u64> {
+        raw.iter()
+            .map(|(key, vals)| {
+                let mut map = BTMap::new();
+                for (line, val) in vals.iter() {
+                    map.insert(*line, *val);
+                }
+                Point::new(*key, PointVal { map })
+            })
+            .collect()
+    }
+
+    /// Checks that patching `old` yields `new`, returns the patch.
+    fn check(old: &PolyPoints<u64, u64>, new: &PolyPoints<u64, u64>) -> PolyPatch<u64, u64> {
+        let patch = PolyPatch::new(old, new).expect("points differ, expected a patch");
+        let mut patched = old.clone();
+        patch.clone().apply(&mut patched).unwrap();
+        let as_pairs = |points: &PolyPoints<u64, u64>| -> Vec<(u64, BTMap<uid::Line, u64>)> {
+            points
+                .iter()
+                .map(|point| (point.key, point.vals.map.clone()))
+                .collect()
+        };
+        assert_eq!(as_pairs(&patched), as_pairs(new), "patch: {}", patch);
+        patch
+    }
+
+    const EVERYTHING: uid::Line = uid::Line::Everything;
+    const CATCH_ALL: uid::Line = uid::Line::CatchAll;
+
+    #[test]
+    fn patch_same_points() {
+        let old = points(&[(0, &[(EVERYTHING, 1)]), (1, &[(EVERYTHING, 2)])]);
+        assert!(PolyPatch::new(&old, &old.clone()).is_none())
+    }
+
+    #[test]
+    fn patch_tail_changed() {
+        let old = points(&[
+            (0, &[(EVERYTHING, 1), (CATCH_ALL, 1)]),
+            (1, &[(EVERYTHING, 2), (CATCH_ALL, 2)]),
+            (2, &[(EVERYTHING, 3), (CATCH_ALL, 3)]),
+        ]);
+        let new = points(&[
+            (0, &[(EVERYTHING, 1), (CATCH_ALL, 1)]),
+            (1, &[(EVERYTHING, 2), (CATCH_ALL, 2)]),
+            (2, &[(EVERYTHING, 7), (CATCH_ALL, 7)]),
+            (3, &[(EVERYTHING, 4), (CATCH_ALL, 4)]),
+        ]);
+        match check(&old, &new) {
+            PolyPatch::Tail { keep, points } => {
+                assert_eq!(keep, 2);
+                assert_eq!(points.len(), 2);
+            }
+            patch => panic!("expected a tail patch, got {}", patch),
+        }
+    }
+
+    #[test]
+    fn patch_prefix_changed() {
+        let old = points(&[
+            (0, &[(EVERYTHING, 1), (CATCH_ALL, 1)]),
+            (1, &[(EVERYTHING, 2), (CATCH_ALL, 2)]),
+            (2, &[(EVERYTHING, 3), (CATCH_ALL, 3)]),
+        ]);
+
+        // Same keys, all lines changed.
+        let new = points(&[
+            (0, &[(EVERYTHING, 5), (CATCH_ALL, 5)]),
+            (1, &[(EVERYTHING, 2), (CATCH_ALL, 2)]),
+            (2, &[(EVERYTHING, 3), (CATCH_ALL, 3)]),
+        ]);
+        match check(&old, &new) {
+            PolyPatch::Tail { keep, .. } => assert_eq!(keep, 0),
+            patch => panic!("expected a tail patch, got {}", patch),
+        }
+
+        // Same keys, only one line changed.
+        let new = points(&[
+            (0, &[(EVERYTHING, 5), (CATCH_ALL, 1)]),
+            (1, &[(EVERYTHING, 2), (CATCH_ALL, 2)]),
+            (2, &[(EVERYTHING, 3), (CATCH_ALL, 3)]),
+        ]);
+        match check(&old, &new) {
+            PolyPatch::Lines(lines) => {
+                assert_eq!(lines.keys().cloned().collect::<Vec<_>>(), vec![EVERYTHING])
+            }
+            patch => panic!("expected a lines patch, got {}", patch),
+        }
+
+        // Different keys from the start.
+        let new = points(&[
+            (1, &[(EVERYTHING, 2), (CATCH_ALL, 2)]),
+            (2, &[(EVERYTHING, 3), (CATCH_ALL, 3)]),
+        ]);
+        match check(&old, &new) {
+            PolyPatch::Tail { keep, .. } => assert_eq!(keep, 0),
+            patch => panic!("expected a tail patch, got {}", patch),
+        }
+    }
+
+    #[test]
+    fn patch_lines_removed() {
+        let old = points(&[
+            (0, &[(EVERYTHING, 1), (CATCH_ALL, 1)]),
+            (1, &[(EVERYTHING, 2), (CATCH_ALL, 2)]),
+        ]);
+        let new = points(&[(0, &[(EVERYTHING, 1)]), (1, &[(EVERYTHING, 2)])]);
+        match check(&old, &new) {
+            PolyPatch::Lines(lines) => {
+                assert_eq!(lines.get(&CATCH_ALL), Some(&vec![None, None]));
+                assert_eq!(lines.len(), 1);
+            }
+            patch => panic!("expected a lines patch, got {}", patch),
+        }
+
+        // Points removed too.
+        let new = points(&[(0, &[(EVERYTHING, 1)])]);
+        check(&old, &new);
+    }
+
+    #[test]
+    fn patch_mismatch() {
+        let old = points(&[
+            (0, &[(EVERYTHING, 1), (CATCH_ALL, 1)]),
+            (1, &[(EVERYTHING, 2), (CATCH_ALL, 2)]),
+        ]);
+        let new = points(&[
+            (0, &[(EVERYTHING, 3), (CATCH_ALL, 1)]),
+            (1, &[(EVERYTHING, 2), (CATCH_ALL, 2)]),
+        ]);
+        let patch = PolyPatch::new(&old, &new).unwrap();
+        let mut too_short = points(&[(0, &[(EVERYTHING, 1), (CATCH_ALL, 1)])]);
+        assert!(patch.apply(&mut too_short).is_err());
+
+        let patch = PolyPatch::Tail {
+            keep: 3,
+            points: vec![],
+        };
+        assert!(patch.apply(&mut old.clone()).is_err())
+    }
+}
@@ -61,8 +61,9 @@ pub use crate::{
     chart::{self, settings},
     color::Color,
     filter::{self, Filter, Filters},
-    msg,
+    inspect, leak, marker, msg,
     point::{self, Point, PointVal, Points},
+    snapshot,
 };
 
 /// Number pretty formatting.
@@ -187,3 +188,33 @@ impl AllocStats {
         data::Data::get_stats()
     }
 }
+
+/// Information about a trace file, when running on a directory of traces.
+///
+/// Sent to the client so that it can list the traces and let users switch between them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceInfo {
+    /// Name of the trace file in the trace directory.
+    pub file: String,
+    /// Name of the executable.
+    pub exe: String,
+    /// Date at which the run started.
+    pub start_date: time::Date,
+    /// Duration of the run.
+    pub duration: time::SinceStart,
+    /// Number of (sampled) allocations.
+    pub alloc_count: u64,
+}
+#[cfg(any(test, feature = "server"))]
+impl TraceInfo {
+    /// Constructor from a CTF summary.
+    pub fn new(file: impl Into<String>, summary: ctf::Summary) -> Self {
+        Self {
+            file: file.into(),
+            exe: summary.exe_name,
+            start_date: summary.start_time,
+            duration: summary.duration,
+            alloc_count: summary.alloc_count,
+        }
+    }
+}
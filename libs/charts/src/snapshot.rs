@@ -0,0 +1,236 @@
+/*<LICENSE>
+    This file is part of Memthol.
+
+    Copyright (C) 2020 OCamlPro.
+
+    Memthol is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Memthol is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Memthol.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Heap snapshots.
+//!
+//! A snapshot is the set of allocations live at some point in time, aggregated by allocation site
+//! and call stack. The client sends a [`Query`] for the snapshot at some time, optionally with a
+//! *base* time, in which case the [`Snapshot`] the server answers with is the difference between
+//! the snapshot at the base time and the one at the query time: which sites grew or shrank.
+//!
+//! [`Query`]: struct.Query.html (The Query struct)
+//! [`Snapshot`]: struct.Snapshot.html (The Snapshot struct)
+
+prelude! {}
+
+/// Maximal number of allocation sites in a snapshot.
+pub const TOP_SITES: usize = 50;
+/// Maximal number of call stacks for each allocation site of a snapshot.
+pub const TOP_STACKS: usize = 10;
+
+/// A request for the snapshot at some time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Query {
+    /// Time of the snapshot.
+    pub time: time::SinceStart,
+    /// Time of the base snapshot, if any.
+    pub base: Option<time::SinceStart>,
+}
+impl Query {
+    /// Constructor.
+    pub fn new(time: time::SinceStart, base: Option<time::SinceStart>) -> Self {
+        Self { time, base }
+    }
+}
+base::implement! {
+    impl Query {
+        Display {
+            |&self, fmt| {
+                write!(fmt, "snapshot @ {}", self.time)?;
+                if let Some(base) = self.base {
+                    write!(fmt, " (base @ {})", base)?
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Number and size of some live allocations.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Live {
+    /// Number of allocations.
+    pub count: usize,
+    /// Size of the allocations, in bytes.
+    pub bytes: u64,
+}
+impl Live {
+    /// Registers an allocation.
+    pub fn add(&mut self, alloc: &Alloc) {
+        self.count += 1;
+        self.bytes += alloc.real_size as u64
+    }
+
+    /// Size difference with a base, in bytes.
+    ///
+    /// ```rust
+    /// use charts::snapshot::Live;
+    /// let (base, live) = (Live { count: 3, bytes: 70 }, Live { count: 2, bytes: 50 });
+    /// assert_eq!(live.delta_bytes(&base), -20);
+    /// ```
+    pub fn delta_bytes(&self, base: &Self) -> i64 {
+        self.bytes as i64 - base.bytes as i64
+    }
+    /// Count difference with a base.
+    pub fn delta_count(&self, base: &Self) -> i64 {
+        self.count as i64 - base.count as i64
+    }
+}
+
+/// Live allocations of a call stack.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Stack {
+    /// Call stack, allocation site last.
+    pub trace: Vec<String>,
+    /// Live allocations at the time of the snapshot.
+    pub live: Live,
+    /// Live allocations at the base time, empty if there is no base.
+    pub base: Live,
+}
+
+/// Live allocations of an allocation site.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Site {
+    /// Allocation site, `None` if unknown.
+    pub site: Option<String>,
+    /// Live allocations at the time of the snapshot.
+    pub live: Live,
+    /// Live allocations at the base time, empty if there is no base.
+    pub base: Live,
+    /// Number of call stacks of the site.
+    pub stack_count: usize,
+    /// Top call stacks of the site.
+    pub stacks: Vec<Stack>,
+}
+
+/// A snapshot answering a query.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Query this snapshot answers.
+    pub query: Query,
+    /// Live allocations at the time of the snapshot.
+    pub live: Live,
+    /// Live allocations at the base time, empty if there is no base.
+    pub base: Live,
+    /// Number of allocation sites, only counting the ones that changed if there is a base.
+    pub site_count: usize,
+    /// Top allocation sites.
+    ///
+    /// Without a base, these are the sites with the most live bytes. Otherwise, these are the
+    /// sites with the biggest size difference, growing or shrinking.
+    pub sites: Vec<Site>,
+}
+impl Snapshot {
+    /// True if the snapshot is a diff with a base snapshot.
+    pub fn is_diff(&self) -> bool {
+        self.query.base.is_some()
+    }
+}
+
+#[cfg(any(test, feature = "server"))]
+impl Snapshot {
+    /// Answers a query.
+    pub fn new(data: &data::Data, query: Query) -> Self {
+        let is_diff = query.base.is_some();
+
+        // Live allocations at the query and base time for each call stack.
+        let mut by_trace: HMap<alloc::Trace, (Live, Live)> = HMap::new();
+        let (mut live, mut base) = (Live::default(), Live::default());
+        for alloc in data.iter_live_at(query.time) {
+            by_trace.entry(alloc.trace_uid()).or_default().0.add(alloc);
+            live.add(alloc)
+        }
+        if let Some(base_time) = query.base {
+            for alloc in data.iter_live_at(base_time) {
+                by_trace.entry(alloc.trace_uid()).or_default().1.add(alloc);
+                base.add(alloc)
+            }
+        }
+
+        // Ranks the stacks and the sites.
+        let rank = |live: &Live, base: &Live| {
+            if is_diff {
+                live.delta_bytes(base).unsigned_abs()
+            } else {
+                live.bytes
+            }
+        };
+
+        let mut by_site: HMap<Option<alloc::Loc>, Site> = HMap::new();
+        for (trace, (live, base)) in by_trace {
+            if is_diff && live == base {
+                continue;
+            }
+            let trace = trace.get();
+            let site = by_site
+                .entry(trace.last().map(|cloc| cloc.loc.clone()))
+                .or_insert_with_key(|site| Site {
+                    site: site.as_ref().map(alloc::Loc::to_string),
+                    live: Live::default(),
+                    base: Live::default(),
+                    stack_count: 0,
+                    stacks: vec![],
+                });
+            site.live.count += live.count;
+            site.live.bytes += live.bytes;
+            site.base.count += base.count;
+            site.base.bytes += base.bytes;
+            site.stack_count += 1;
+            site.stacks.push(Stack {
+                trace: trace.iter().map(|cloc| cloc.to_string()).collect(),
+                live,
+                base,
+            })
+        }
+
+        let mut sites: Vec<Site> = by_site
+            .into_values()
+            .filter(|site| !is_diff || site.live != site.base)
+            .collect();
+        let site_count = sites.len();
+        sites.sort_by(|lft, rgt| {
+            rank(&rgt.live, &rgt.base)
+                .cmp(&rank(&lft.live, &lft.base))
+                .then_with(|| lft.site.cmp(&rgt.site))
+        });
+        sites.truncate(TOP_SITES);
+        for site in &mut sites {
+            site.stacks.sort_by(|lft, rgt| {
+                rank(&rgt.live, &rgt.base)
+                    .cmp(&rank(&lft.live, &lft.base))
+                    .then_with(|| lft.trace.cmp(&rgt.trace))
+            });
+            site.stacks.truncate(TOP_STACKS)
+        }
+
+        Self {
+            query,
+            live,
+            base,
+            site_count,
+            sites,
+        }
+    }
+
+    /// Answers a query over the global data.
+    pub fn get(query: Query) -> Res<Self> {
+        let data = data::get().chain_err(|| format!("while answering query {}", query))?;
+        Ok(Self::new(&data, query))
+    }
+}
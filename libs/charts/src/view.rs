@@ -0,0 +1,166 @@
+/*<LICENSE>
+    This file is part of Memthol.
+
+    Copyright (C) 2020 OCamlPro.
+
+    Memthol is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Memthol is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Memthol.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Shareable views.
+//!
+//! A [`View`] is everything the user sees of a session except for the points: the global settings
+//! (including the time window), the filters, and the charts with their settings. Clients ask the
+//! server to [`store`] the view of their session, and get a short id back they can put in a link.
+//! A client connecting with this id gets a new session built from the view.
+//!
+//! Views are stored in memory, they do not survive a restart of the server. At most [`MAX_VIEWS`]
+//! views are stored, storing a view when the limit is reached drops the oldest one.
+//!
+//! [`View`]: struct.View.html (The View struct)
+//! [`store`]: fn.store.html (The store function)
+//! [`MAX_VIEWS`]: constant.MAX_VIEWS.html (The MAX_VIEWS constant)
+
+#[cfg(any(test, feature = "server"))]
+prelude! {}
+
+#[cfg(any(test, feature = "server"))]
+use crate::{chart::ChartSpec, filter::FilterSpec, Chart, Charts};
+
+/// Name of the query parameter carrying the id of a view in websocket requests and page URLs.
+pub const QUERY_KEY: &str = "view";
+
+/// Maximum number of views stored.
+pub const MAX_VIEWS: usize = 1024;
+
+#[cfg(any(test, feature = "server"))]
+lazy_static! {
+    /// Stored views, by id, with the time at which they were stored.
+    static ref VIEWS: sync::RwLock<HMap<String, (time::Instant, View)>> =
+        sync::RwLock::new(HMap::new());
+}
+
+#[cfg(any(test, feature = "server"))]
+/// Read lock over the stored views.
+fn read<'a>() -> Res<sync::RwLockReadGuard<'a, HMap<String, (time::Instant, View)>>> {
+    VIEWS
+        .read()
+        .map_err(|e| {
+            let e: err::Error = e.to_string().into();
+            e
+        })
+        .chain_err(|| "while reading the stored views")
+}
+
+#[cfg(any(test, feature = "server"))]
+/// Write lock over the stored views.
+fn write<'a>() -> Res<sync::RwLockWriteGuard<'a, HMap<String, (time::Instant, View)>>> {
+    VIEWS
+        .write()
+        .map_err(|e| {
+            let e: err::Error = e.to_string().into();
+            e
+        })
+        .chain_err(|| "while writing the stored views")
+}
+
+#[cfg(any(test, feature = "server"))]
+/// Stores a view, returns its id.
+///
+/// Drops the oldest views if there are already [`MAX_VIEWS`] views.
+///
+/// [`MAX_VIEWS`]: constant.MAX_VIEWS.html (The MAX_VIEWS constant)
+pub fn store(view: View) -> Res<String> {
+    let mut views = write()?;
+    while views.len() >= MAX_VIEWS {
+        let oldest = views
+            .iter()
+            .min_by_key(|(_, (since, _))| *since)
+            .map(|(id, _)| id.clone());
+        if let Some(oldest) = oldest {
+            views.remove(&oldest);
+        } else {
+            break;
+        }
+    }
+    loop {
+        let id = format!("{:08x}", base::rand::random::<u32>());
+        if !views.contains_key(&id) {
+            views.insert(id.clone(), (time::Instant::now(), view));
+            return Ok(id);
+        }
+    }
+}
+
+#[cfg(any(test, feature = "server"))]
+/// Retrieves a stored view.
+pub fn get(id: &str) -> Res<Option<View>> {
+    read().map(|views| views.get(id).map(|(_, view)| view.clone()))
+}
+
+#[cfg(any(test, feature = "server"))]
+/// The view of a session, without any point.
+#[derive(Debug, Clone)]
+pub struct View {
+    /// Trace the view was taken on, if the server runs on a trace directory.
+    pub trace: Option<String>,
+    /// Global settings.
+    settings: settings::Charts,
+    /// Specification of the "everything" filter.
+    everything: FilterSpec,
+    /// User filters.
+    filters: Vec<Filter>,
+    /// Specification of the "catch-all" filter.
+    catch_all: FilterSpec,
+    /// Charts and their settings.
+    charts: Vec<(ChartSpec, settings::Chart)>,
+}
+
+#[cfg(any(test, feature = "server"))]
+impl Charts {
+    /// The view of the charts.
+    pub fn view(&self) -> Res<View> {
+        Ok(View {
+            trace: data::traces::active()?,
+            settings: self.settings.clone(),
+            everything: self.filters.everything().clone(),
+            filters: self.filters.filters().clone(),
+            catch_all: self.filters.catch_all().clone(),
+            charts: self
+                .charts
+                .iter()
+                .map(|chart| (chart.spec().clone(), chart.settings().clone()))
+                .collect(),
+        })
+    }
+
+    /// Creates some charts from a view.
+    pub fn of_view(view: &View) -> Res<Self> {
+        let mut filters = Filters::new();
+        let _ = filters.update_all(
+            view.everything.clone(),
+            view.filters.clone(),
+            view.catch_all.clone(),
+        )?;
+        let mut charts = Self::new();
+        for (spec, settings) in &view.charts {
+            let mut chart = Chart::from_spec(None, &filters, spec.clone())
+                .chain_err(|| format!("while restoring chart `{}`", settings.title()))?;
+            *chart.settings_mut() = settings.clone();
+            charts.push(chart)
+        }
+        charts.filters = filters;
+        charts.settings = view.settings.clone();
+        Ok(charts)
+    }
+}
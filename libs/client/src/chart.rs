@@ -38,6 +38,10 @@ pub struct Charts {
     dom_node_id: &'static str,
     /// Link to the model.
     link: Link,
+    /// Markers generated by the server, drawn on all the charts.
+    markers: Vec<marker::Marker>,
+    /// User annotations, drawn on all the charts.
+    annotations: Vec<marker::Marker>,
 }
 
 impl Charts {
@@ -48,9 +52,32 @@ impl Charts {
             link,
             new_chart: new::NewChart::new(),
             dom_node_id: "charts_list",
+            markers: vec![],
+            annotations: vec![],
         }
     }
 
+    /// Removes all the charts and markers.
+    pub fn clear(&mut self) -> ShouldRender {
+        self.charts.clear();
+        self.markers.clear();
+        true
+    }
+
+    /// Sets the user annotations, returns `true` if they changed.
+    ///
+    /// All the charts are redrawn if the annotations changed.
+    pub fn set_annotations(&mut self, annotations: &[marker::Marker]) -> ShouldRender {
+        if self.annotations == annotations {
+            return false;
+        }
+        self.annotations = annotations.to_vec();
+        for chart in &mut self.charts {
+            chart.redraw = true
+        }
+        true
+    }
+
     /// Name of the DOM node containing all the charts.
     pub fn dom_node_id(&self) -> &str {
         &self.dom_node_id
@@ -123,7 +150,8 @@ impl Charts {
     /// Runs post-rendering actions.
     pub fn rendered(&mut self, filters: filter::Reference, stats: &AllFilterStats) {
         for chart in &mut self.charts {
-            if let Err(e) = chart.rendered(filters, stats) {
+            let markers = self.markers.iter().chain(&self.annotations);
+            if let Err(e) = chart.rendered(filters, stats, markers) {
                 alert!("error while running `rendered`: {}", e)
             }
         }
@@ -225,6 +253,11 @@ impl Charts {
                 true
             }
 
+            ChartsMsg::Clear => {
+                log::info!("clearing all charts");
+                self.clear()
+            }
+
             ChartsMsg::NewPoints {
                 mut points,
                 refresh_filters,
@@ -239,20 +272,51 @@ impl Charts {
                 }
                 true
             }
+            ChartsMsg::PatchPoints {
+                mut patches,
+                refresh_filters,
+            } => {
+                for chart in &mut self.charts {
+                    if let Some(patch) = patches.remove(&chart.uid()) {
+                        chart.patch_points(patch)?
+                    }
+                }
+                if refresh_filters {
+                    self.refresh_filters(filters)?;
+                }
+                true
+            }
             ChartsMsg::AddPoints(mut points) => {
                 for chart in &mut self.charts {
                     if let Some(points) = points.remove(&chart.uid()) {
-                        chart.add_points(points, filters, stats)?
+                        let markers = self.markers.iter().chain(&self.annotations);
+                        chart.add_points(points, filters, stats, markers)?
                     }
                 }
                 false
             }
 
+            ChartsMsg::Markers(markers) => {
+                self.markers = markers;
+                for chart in &mut self.charts {
+                    chart.redraw = true
+                }
+                true
+            }
+
             ChartsMsg::Chart { uid, msg } => {
-                let (_index, chart) = self.get_mut(uid)?;
+                // Not using `self.get_mut` as the markers are borrowed at the same time.
+                let chart = self
+                    .charts
+                    .iter_mut()
+                    .find(|chart| chart.uid() == uid)
+                    .ok_or_else(|| format!("unknown chart UID #{}", uid))?;
+                let markers = self.markers.iter().chain(&self.annotations);
                 match msg {
                     ChartMsg::NewPoints(points) => chart.overwrite_points(points)?,
-                    ChartMsg::Points(points) => chart.add_points(points, filters, stats)?,
+                    ChartMsg::Points(points) => {
+                        chart.add_points(points, filters, stats, markers)?
+                    }
                 }
                 true
             }
@@ -301,6 +365,10 @@ pub struct Chart {
     redraw: bool,
     /// True if the chart settings are visible.
     settings_visible: bool,
+    /// Mouse drag in progress on the chart's canvas, if any.
+    drag: Option<Drag>,
+    /// X-position (in pixels) of the mouse hovering over the chart's canvas, if any.
+    hover: Option<i32>,
 }
 impl Chart {
     /// Constructor.
@@ -323,18 +391,52 @@ impl Chart {
             prev_active: BTMap::new(),
             settings_visible: false,
             redraw: true,
+            drag: None,
+            hover: None,
         })
     }
 
     /// Handles a message for this chart.
     pub fn update(&mut self, msg: msg::ChartMsg) -> Res<ShouldRender> {
         use msg::ChartMsg::*;
-        match msg {
-            SettingsToggleVisible => self.toggle_settings_visible(),
-            FilterToggleVisible(l_uid) => self.filter_toggle_visible(l_uid)?,
-            SettingsUpdate(msg) => self.settings.update(msg),
-        }
-        Ok(true)
+        let render = match msg {
+            SettingsToggleVisible => {
+                self.toggle_settings_visible();
+                true
+            }
+            FilterToggleVisible(l_uid) => {
+                self.filter_toggle_visible(l_uid)?;
+                true
+            }
+            SettingsUpdate(msg) => {
+                self.settings.update(msg);
+                true
+            }
+            DragStart { x, pan } => {
+                self.drag = Some(Drag::new(x, pan));
+                false
+            }
+            MouseMove(x) => self.mouse_move(x),
+            DragEnd(x) => self.drag_end(x),
+            MouseLeave => self.mouse_leave(),
+            Zoom { x, zoom_in } => {
+                self.zoom(x, zoom_in);
+                false
+            }
+            Annotate(x) => {
+                self.annotate(x);
+                false
+            }
+            ResetZoom => {
+                self.reset_zoom();
+                true
+            }
+            ToggleLocalWindow => {
+                self.toggle_local_window();
+                true
+            }
+        };
+        Ok(render)
     }
 
     /// UID accessor.
@@ -407,6 +509,469 @@ impl Chart {
 
     /// Destroys the chart.
     pub fn destroy(self) {}
+
+    /// Inspects the allocations at some x-position (in pixels) on the chart's canvas.
+    ///
+    /// Does nothing if the position is outside of the drawing area.
+    pub fn inspect(&self, x: i32) {
+        if let Some(frame) = self.frame() {
+            if frame.contains(x) {
+                let time = secs_to_time(frame.secs_at(x));
+                self.link.send_message(inspector::Msg::inspect(time))
+            }
+        }
+    }
+}
+
+/// A mouse drag on a chart's canvas.
+#[derive(Debug, Clone, Copy)]
+struct Drag {
+    /// X-position (in pixels) where the drag started.
+    start: i32,
+    /// Current x-position (in pixels) of the mouse.
+    current: i32,
+    /// Pans the chart if true, selects a time window otherwise.
+    pan: bool,
+}
+impl Drag {
+    /// Constructor.
+    fn new(x: i32, pan: bool) -> Self {
+        Self {
+            start: x,
+            current: x,
+            pan,
+        }
+    }
+    /// True if the mouse moved enough for the drag not to be a click.
+    fn is_drag(&self) -> bool {
+        (self.current - self.start).abs() >= Chart::DRAG_THRESHOLD_PX
+    }
+}
+
+/// Maps x-positions (in pixels) on a chart's canvas to times.
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+    /// Left-most x-position of the drawing area.
+    left: i32,
+    /// Width of the drawing area.
+    width: i32,
+    /// Time at the left of the drawing area, in seconds.
+    lb: f64,
+    /// Time at the right of the drawing area, in seconds.
+    ub: f64,
+}
+impl Frame {
+    /// Constructor from the width of the chart's canvas and the points drawn.
+    ///
+    /// Returns `None` if there are no points or if the canvas is too small.
+    fn new(canvas_width: u32, points: &point::Points) -> Option<Self> {
+        let range = points.time_range()?;
+        let left = Chart::Y_LABEL_AREA as i32;
+        let width = canvas_width as i32 - left - Chart::RIGHT_MARGIN as i32;
+        if width <= 0 {
+            return None;
+        }
+        Some(Self {
+            left,
+            width,
+            lb: range.lbound.as_secs_f64(),
+            ub: range.ubound.as_secs_f64(),
+        })
+    }
+    /// True if an x-position is in the drawing area.
+    fn contains(&self, x: i32) -> bool {
+        self.left <= x && x <= self.left + self.width
+    }
+    /// Time span of the drawing area, in seconds.
+    fn span(&self) -> f64 {
+        self.ub - self.lb
+    }
+    /// Time at some x-position, in seconds, clamped to the drawing area.
+    fn secs_at(&self, x: i32) -> f64 {
+        let ratio = (x - self.left) as f64 / self.width as f64;
+        self.lb + self.span() * ratio.clamp(0., 1.)
+    }
+    /// X-position of some time, in seconds.
+    fn x_at(&self, secs: f64) -> i32 {
+        let span = self.span();
+        if span <= 0. {
+            self.left
+        } else {
+            self.left + ((secs - self.lb) / span * self.width as f64).round() as i32
+        }
+    }
+}
+
+/// Numeric readout of the values of the visible filters at some point of a chart.
+struct Readout {
+    /// X-position (in pixels) of the point.
+    x: i32,
+    /// Lines of text of the readout, with their color.
+    lines: Vec<(String, RGBColor)>,
+}
+impl Readout {
+    /// Font size of the readout.
+    const FONT_SIZE: u32 = 15;
+    /// Height of a line of text, in pixels.
+    const LINE_HEIGHT: i32 = 18;
+    /// Padding around the text, in pixels.
+    const PADDING: i32 = 5;
+    /// Horizontal offset between the crosshair and the readout box, in pixels.
+    const OFFSET: i32 = 10;
+
+    /// Constructor, `None` if `x` is not in the drawing area or if there are no points.
+    ///
+    /// The readout is for the point closest to `x`. In stacked-area modes, the `everything` filter
+    /// is not drawn and thus does not appear in the readout. The stacked-area-percent mode also
+    /// shows the percentage of each filter.
+    fn new<'spec>(
+        frame: &Frame,
+        x: i32,
+        points: &point::Points,
+        mode: settings::DisplayMode,
+        active_filters: impl Iterator<Item = &'spec filter::FilterSpec> + Clone,
+    ) -> Option<Self> {
+        if !frame.contains(x) {
+            return None;
+        }
+        let (time, values) = points.values_at(secs_to_time(frame.secs_at(x)))?;
+
+        let active_filters =
+            active_filters.filter(|spec| !(mode.is_stacked_area() && spec.uid().is_everything()));
+        let value = |uid: uid::Line| values.get(&uid).cloned().unwrap_or(0);
+        let total: u64 = active_filters.clone().map(|spec| value(spec.uid())).sum();
+
+        let mut lines = vec![(format!("{}s", time.display_millis()), BLACK)];
+        for spec in active_filters {
+            let val = value(spec.uid());
+            let mut line = format!(
+                "{}: {}",
+                spec.name(),
+                num_fmt::bin_str_do(val as f64, |mut s| {
+                    s.push('B');
+                    s
+                })
+            );
+            if mode == settings::DisplayMode::StackedAreaPercent {
+                let percent = if total == 0 {
+                    0.
+                } else {
+                    val as f64 * 100. / total as f64
+                };
+                line.push_str(&format!(" ({:.2}%)", percent))
+            }
+            let (r, g, b) = spec.color().rgb();
+            lines.push((line, RGBColor(r, g, b)))
+        }
+
+        Some(Self {
+            x: frame.x_at(time.as_secs_f64()),
+            lines,
+        })
+    }
+
+    /// Draws the crosshair and the readout box between two y-positions.
+    fn draw(
+        &self,
+        chart: &plotters::drawing::DrawingArea<plotters::CanvasBackend, plotters::coord::Shift>,
+        top: i32,
+        bottom: i32,
+    ) -> Res<()> {
+        let crosshair = PathElement::new(
+            vec![(self.x, top), (self.x, bottom)],
+            BLACK.mix(0.5).stroke_width(1),
+        );
+        chart
+            .draw(&crosshair)
+            .map_err(|e| format!("error while drawing crosshair: {}", e))?;
+
+        let font = ("sans-serif", Self::FONT_SIZE).into_font();
+        let style = TextStyle::from(font.clone());
+        let mut text_width = 0;
+        for (line, _) in &self.lines {
+            let (width, _) = chart
+                .estimate_text_size(line, &style)
+                .map_err(|e| format!("error while measuring readout: {}", e))?;
+            text_width = text_width.max(width as i32)
+        }
+        let width = text_width + 2 * Self::PADDING;
+        let height = self.lines.len() as i32 * Self::LINE_HEIGHT + 2 * Self::PADDING;
+
+        // Put the box on the right of the crosshair, unless it does not fit.
+        let (canvas_width, _) = chart.dim_in_pixel();
+        let left = if self.x + Self::OFFSET + width <= canvas_width as i32 {
+            self.x + Self::OFFSET
+        } else {
+            self.x - Self::OFFSET - width
+        };
+        let corners = [(left, top), (left + width, top + height)];
+        chart
+            .draw(&Rectangle::new(corners, WHITE.mix(0.85).filled()))
+            .and_then(|()| chart.draw(&Rectangle::new(corners, BLACK.mix(0.3).stroke_width(1))))
+            .map_err(|e| format!("error while drawing readout box: {}", e))?;
+
+        for (index, (line, color)) in self.lines.iter().enumerate() {
+            let pos = (
+                left + Self::PADDING,
+                top + Self::PADDING + index as i32 * Self::LINE_HEIGHT,
+            );
+            chart
+                .draw(&Text::new(line.clone(), pos, font.color(color)))
+                .map_err(|e| format!("error while drawing readout: {}", e))?
+        }
+        Ok(())
+    }
+}
+
+/// Turns an amount of seconds into a time since the start of the run, negative values yield zero.
+fn secs_to_time(secs: f64) -> time::SinceStart {
+    time::SinceStart::from(std::time::Duration::from_secs_f64(secs.max(0.)))
+}
+
+/// # Zoom, pan and time-window selection.
+impl Chart {
+    /// Minimal horizontal mouse move (in pixels) for a drag not to be a click.
+    const DRAG_THRESHOLD_PX: i32 = 5;
+    /// Span ratio applied to the time window when zooming in.
+    const ZOOM_IN_RATIO: f64 = 0.8;
+    /// Minimal time window span, in seconds.
+    const MIN_WINDOW_SECS: f64 = 0.001;
+
+    /// Maps x-positions on the chart's canvas to times, `None` if the chart has no points yet.
+    fn frame(&self) -> Option<Frame> {
+        let (_, canvas) = self.chart.as_ref()?;
+        Frame::new(canvas.width(), self.points.as_ref()?)
+    }
+
+    /// True if the chart uses a local time window.
+    pub fn has_local_window(&self) -> bool {
+        self.settings.local_time_windopt().is_some()
+    }
+
+    /// Sets the time window of the chart.
+    ///
+    /// Sets the global time window, unless the chart uses a local time window.
+    fn set_time_window(&mut self, window: TimeWindopt) {
+        if self.has_local_window() {
+            self.set_local_window(Some(window))
+        } else {
+            self.link
+                .send_message(msg::settings::Msg::ApplyTimeWindow(window))
+        }
+    }
+
+    /// Sets the time window of the chart to `[lb, ub]` (in seconds).
+    fn set_time_window_secs(&mut self, lb: f64, ub: f64) {
+        if ub - lb >= Self::MIN_WINDOW_SECS {
+            let window = TimeWindopt::new(Some(secs_to_time(lb)), Some(secs_to_time(ub)));
+            self.set_time_window(window)
+        }
+    }
+
+    /// Sets the local time window of the chart, `None` to use the global time window.
+    fn set_local_window(&mut self, window: Option<TimeWindopt>) {
+        self.settings
+            .update(msg::ChartSettingsMsg::SetTimeWindow(window));
+        let msg = msg::ChartSettingsMsg::set_time_window(self.uid(), window);
+        self.link.send_message(Msg::ToServer(msg))
+    }
+
+    /// Toggles between the global time window and a local one.
+    ///
+    /// The local time window initially covers the whole run.
+    pub fn toggle_local_window(&mut self) {
+        if self.has_local_window() {
+            self.set_local_window(None)
+        } else {
+            self.set_local_window(Some(TimeWindopt::new(None, None)))
+        }
+    }
+
+    /// Resets the time window of the chart so that it covers the whole run.
+    pub fn reset_zoom(&mut self) {
+        self.set_time_window(TimeWindopt::new(None, None))
+    }
+
+    /// Zooms in or out around some x-position.
+    pub fn zoom(&mut self, x: i32, zoom_in: bool) {
+        if let Some(frame) = self.frame() {
+            if frame.contains(x) {
+                let ratio = if zoom_in {
+                    Self::ZOOM_IN_RATIO
+                } else {
+                    1. / Self::ZOOM_IN_RATIO
+                };
+                let center = frame.secs_at(x);
+                self.set_time_window_secs(
+                    center - (center - frame.lb) * ratio,
+                    center + (frame.ub - center) * ratio,
+                )
+            }
+        }
+    }
+
+    /// Handles a mouse move, returns `true` if the chart needs to be redrawn.
+    ///
+    /// Updates the current drag if any, moves the hover crosshair otherwise.
+    fn mouse_move(&mut self, x: i32) -> ShouldRender {
+        if let Some(drag) = self.drag.as_mut() {
+            drag.current = x;
+            self.hover = None;
+            if !drag.pan {
+                self.redraw = true;
+                return true;
+            }
+            false
+        } else {
+            self.hover = Some(x);
+            self.redraw = true;
+            true
+        }
+    }
+
+    /// Cancels the current drag and hides the crosshair, returns `true` if the chart needs to be
+    /// redrawn.
+    fn mouse_leave(&mut self) -> ShouldRender {
+        let had_hover = self.hover.take().is_some();
+        let had_selection = match self.drag.take() {
+            Some(drag) => !drag.pan && drag.is_drag(),
+            None => false,
+        };
+        if had_hover || had_selection {
+            self.redraw = true;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Ends a drag: pans, selects a time window, or inspects allocations if the mouse did not move.
+    fn drag_end(&mut self, x: i32) -> ShouldRender {
+        let mut drag = if let Some(drag) = self.drag.take() {
+            drag
+        } else {
+            return false;
+        };
+        drag.current = x;
+
+        if !drag.is_drag() {
+            self.inspect(drag.start);
+            return false;
+        }
+
+        if let Some(frame) = self.frame() {
+            if drag.pan {
+                let shift = frame.secs_at(drag.start) - frame.secs_at(drag.current);
+                // Do not pan before the start of the run.
+                let shift = shift.max(-frame.lb);
+                self.set_time_window_secs(frame.lb + shift, frame.ub + shift)
+            } else {
+                let (start, current) = (frame.secs_at(drag.start), frame.secs_at(drag.current));
+                self.set_time_window_secs(start.min(current), start.max(current))
+            }
+        }
+
+        // Erase the selection.
+        self.redraw = true;
+        true
+    }
+}
+
+/// # Markers and annotations.
+impl Chart {
+    /// Font size of the marker labels.
+    const MARKER_FONT_SIZE: u32 = 13;
+    /// Vertical space between the labels of consecutive markers, in pixels.
+    const MARKER_LABEL_STEP: i32 = 15;
+    /// Number of label positions used to keep the labels of close markers from overlapping.
+    const MARKER_LABEL_ROWS: usize = 3;
+
+    /// Color of a kind of marker.
+    fn marker_color(kind: marker::Kind) -> RGBColor {
+        match kind {
+            marker::Kind::Annotation => RGBColor(128, 0, 128),
+            marker::Kind::Start | marker::Kind::Restart | marker::Kind::End => RGBColor(90, 90, 90),
+            marker::Kind::CollectionBurst => RGBColor(230, 120, 0),
+        }
+    }
+
+    /// Adds an annotation at some x-position, asks the user for its label.
+    ///
+    /// Does nothing if the position is outside of the drawing area, or if the user cancels.
+    pub fn annotate(&self, x: i32) {
+        if let Some(frame) = self.frame() {
+            if frame.contains(x) {
+                let time = secs_to_time(frame.secs_at(x));
+                let label = js::prompt(&format!("label of the annotation at {}s", time), "");
+                match label {
+                    Some(label) if !label.trim().is_empty() => {
+                        let annotation = marker::Marker::annotation(time, label.trim());
+                        self.link
+                            .send_message(msg::settings::Msg::Annotate(annotation))
+                    }
+                    _ => (),
+                }
+            }
+        }
+    }
+
+    /// Draws a marker as a vertical labeled line between two y-positions.
+    ///
+    /// Markers standing for a period also shade that period. The index of the marker is used to
+    /// stagger the labels. Does nothing if the marker is not in the frame.
+    fn draw_marker(
+        chart: &plotters::drawing::DrawingArea<plotters::CanvasBackend, plotters::coord::Shift>,
+        frame: &Frame,
+        marker: &marker::Marker,
+        index: usize,
+        top: i32,
+        bottom: i32,
+    ) -> Res<()> {
+        let color = Self::marker_color(marker.kind);
+        let time = marker.time.as_secs_f64();
+
+        if let Some(until) = marker.until {
+            let until = until.as_secs_f64();
+            if time <= frame.ub && frame.lb <= until {
+                let corners = [
+                    (frame.x_at(time.max(frame.lb)), top),
+                    (frame.x_at(until.min(frame.ub)), bottom),
+                ];
+                chart
+                    .draw(&Rectangle::new(corners, color.mix(0.15).filled()))
+                    .map_err(|e| format!("error while drawing marker period: {}", e))?
+            }
+        }
+
+        if time < frame.lb || frame.ub < time {
+            return Ok(());
+        }
+        let x = frame.x_at(time);
+        let line = PathElement::new(vec![(x, top), (x, bottom)], color.stroke_width(1));
+        chart
+            .draw(&line)
+            .map_err(|e| format!("error while drawing marker: {}", e))?;
+
+        let font = ("sans-serif", Self::MARKER_FONT_SIZE).into_font();
+        let (label_width, _) = chart
+            .estimate_text_size(&marker.label, &TextStyle::from(font.clone()))
+            .map_err(|e| format!("error while measuring marker label: {}", e))?;
+        // Put the label on the right of the line, unless it does not fit.
+        let label_x = if x + 3 + label_width as i32 <= frame.left + frame.width {
+            x + 3
+        } else {
+            x - 3 - label_width as i32
+        };
+        let label_y = top + (index % Self::MARKER_LABEL_ROWS) as i32 * Self::MARKER_LABEL_STEP;
+        chart
+            .draw(&Text::new(
+                marker.label.clone(),
+                (label_x, label_y),
+                font.color(&color),
+            ))
+            .map_err(|e| format!("error while drawing marker label: {}", e))?;
+        Ok(())
+    }
 }
 
 /// # Features that (can) trigger a re-draw.
@@ -444,22 +1009,23 @@ impl Chart {
     }
 
     /// Appends some points to the chart.
-    pub fn add_points(
+    pub fn add_points<'m>(
         &mut self,
         mut points: point::Points,
         filters: filter::Reference,
         stats: &AllFilterStats,
+        markers: impl Iterator<Item = &'m marker::Marker>,
     ) -> Res<()> {
         let mut redraw = false;
         if let Some(my_points) = &mut self.points {
             let changed = my_points.extend(&mut points)?;
             if changed {
-                self.draw(filters, stats)?
+                self.draw(filters, stats, markers)?
             }
             redraw = true;
         } else if !points.is_empty() {
             self.points = Some(points);
-            self.draw(filters, stats)?;
+            self.draw(filters, stats, markers)?;
             redraw = true;
         }
 
@@ -473,6 +1039,24 @@ impl Chart {
         self.redraw = true;
         Ok(())
     }
+
+    /// Applies a patch to the points in a chart.
+    pub fn patch_points(&mut self, patch: point::PointsPatch) -> Res<()> {
+        if let Some(points) = &mut self.points {
+            points
+                .patch(patch)
+                .chain_err(|| format!("while patching the points of chart #{}", self.uid()))?
+        } else if let point::PointsPatch::Overwrite(points) = patch {
+            self.points = Some(points)
+        } else {
+            bail!(
+                "cannot patch the points of chart #{}, it has no points",
+                self.uid()
+            )
+        }
+        self.redraw = true;
+        Ok(())
+    }
 }
 
 /// # Canvas Handling.
@@ -641,7 +1225,12 @@ impl Chart {
     ///
     /// If the chart is not visible, drawing is postponed until the chart becomes visible. Meaning
     /// that this function does nothing if the chart is not visible.
-    pub fn draw(&mut self, filters: filter::Reference, stats: &AllFilterStats) -> Res<()> {
+    pub fn draw<'m>(
+        &mut self,
+        filters: filter::Reference,
+        stats: &AllFilterStats,
+        markers: impl Iterator<Item = &'m marker::Marker>,
+    ) -> Res<()> {
         // If the chart's not visible, do nothing. We will draw once the chart becomes visible
         // again.
         if !self.settings.is_visible() {
@@ -649,6 +1238,9 @@ impl Chart {
         }
 
         let visible_filters = self.spec.active();
+        let selection = self.drag.filter(|drag| !drag.pan && drag.is_drag());
+        let hover = self.hover;
+        let mode = self.settings.display_mode();
 
         if let Some((chart, canvas)) = &mut self.chart {
             let width = canvas.client_width();
@@ -695,6 +1287,37 @@ impl Chart {
                     filters.specs_iter().filter(|spec| is_active(spec.uid())),
                 )?;
 
+                let frame = Frame::new(chart_w, points);
+                if let Some(frame) = frame {
+                    let (top, bottom) = (
+                        Self::TOP_MARGIN as i32,
+                        chart_h as i32 - Self::X_LABEL_AREA as i32,
+                    );
+                    for (index, marker) in markers.enumerate() {
+                        Self::draw_marker(chart, &frame, marker, index, top, bottom)?
+                    }
+                }
+
+                if let Some(drag) = selection {
+                    let selection = plotters::prelude::Rectangle::new(
+                        [(drag.start, 0), (drag.current, chart_h as i32)],
+                        plotters::prelude::BLUE.mix(0.2).filled(),
+                    );
+                    chart
+                        .draw(&selection)
+                        .map_err(|e| format!("error while drawing selection: {}", e))?
+                }
+
+                let readout = hover.and_then(|x| {
+                    let frame = frame?;
+                    let active_filters = filters.specs_iter().filter(|spec| is_active(spec.uid()));
+                    Readout::new(&frame, x, points, mode, active_filters)
+                });
+                if let Some(readout) = readout {
+                    let bottom = chart_h as i32 - Self::X_LABEL_AREA as i32;
+                    readout.draw(chart, Self::TOP_MARGIN as i32, bottom)?
+                }
+
                 chart
                     .present()
                     .map_err(|e| format!("error while presenting chart: {}", e))?
@@ -708,7 +1331,12 @@ impl Chart {
 /// # Rendering
 impl Chart {
     /// Runs post-rendering actions.
-    pub fn rendered(&mut self, filters: filter::Reference, stats: &AllFilterStats) -> Res<()> {
+    pub fn rendered<'m>(
+        &mut self,
+        filters: filter::Reference,
+        stats: &AllFilterStats,
+        markers: impl Iterator<Item = &'m marker::Marker>,
+    ) -> Res<()> {
         self.rebind_canvas()?;
 
         if self.chart.is_none() {
@@ -718,7 +1346,7 @@ impl Chart {
 
         if self.redraw {
             // Do **not** unset `self.redraw` here, function `draw` is in charge of that.
-            self.draw(filters, stats)?;
+            self.draw(filters, stats, markers)?;
         }
         Ok(())
     }
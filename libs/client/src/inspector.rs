@@ -0,0 +1,388 @@
+/*<LICENSE>
+    This file is part of Memthol.
+
+    Copyright (C) 2020 OCamlPro.
+
+    Memthol is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Memthol is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Memthol.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Allocation inspector.
+//!
+//! Lists the allocations of a filter line live at some point in time, as requested by clicking on
+//! a chart, and displays the callstack of the allocation selected.
+
+prelude! {}
+
+use charts::inspect::{Page, Query};
+
+define_style! {
+    CONTAINER_STYLE = {
+        block,
+        margin(0%, 1%, 2%, 1%),
+    };
+    TABLE_STYLE = {
+        width(100%),
+        text_align(center),
+    };
+    HEADER_STYLE = {
+        bold,
+    };
+    ROW_STYLE = {
+        pointer,
+    };
+    SELECTED_ROW_STYLE = {
+        bold,
+        fg({"#c8f5fd"}),
+        bg({layout::DARK_GREY_BG}),
+    };
+    LINE_STYLE = {
+        pointer,
+        padding(0%, 10 px),
+        underline,
+    };
+    ACTIVE_LINE_STYLE = {
+        bold,
+        padding(0%, 10 px),
+    };
+    NAV_STYLE = {
+        pointer,
+        padding(0%, 10 px),
+        underline,
+    };
+    HIDDEN_NAV_STYLE = {
+        padding(0%, 10 px),
+        hidden,
+    };
+    TRACE_STYLE = {
+        font(code),
+        text_align(left),
+        margin(1%, 5%, 0%, 5%),
+    };
+    SOURCE_STYLE = {
+        italic,
+        fg({"#a0a0a0"}),
+        margin(0%, 0%, 0%, 2%),
+    };
+}
+
+/// Messages acting on the allocation inspector.
+#[derive(Clone, Debug)]
+pub enum Msg {
+    /// Inspects the allocations live at some point in time, for the current line.
+    Inspect(time::SinceStart),
+    /// Changes the line inspected.
+    SetLine(uid::Line),
+    /// Requests a different page.
+    Page(usize),
+    /// Selects an allocation, to display its callstack.
+    Select(uid::Alloc),
+    /// Closes the inspector.
+    Close,
+}
+impl Msg {
+    /// Inspects the allocations live at some point in time.
+    pub fn inspect(time: time::SinceStart) -> msg::Msg {
+        Self::Inspect(time).into()
+    }
+}
+base::implement! {
+    impl Msg {
+        Display {
+            |&self, fmt| match self {
+                Self::Inspect(time) => write!(fmt, "inspect {}", time),
+                Self::SetLine(line) => write!(fmt, "set line {}", line),
+                Self::Page(page) => write!(fmt, "page {}", page),
+                Self::Select(uid) => write!(fmt, "select {}", uid),
+                Self::Close => write!(fmt, "close"),
+            }
+        }
+    }
+}
+
+/// Allocation inspector.
+pub struct Inspector {
+    /// Link to the model.
+    link: Link,
+    /// Last query sent to the server, `None` if the inspector is closed.
+    query: Option<Query>,
+    /// Last page received from the server.
+    page: Option<Page>,
+    /// Allocation selected.
+    selected: Option<uid::Alloc>,
+}
+
+impl Inspector {
+    /// Constructor.
+    pub fn new(link: Link) -> Self {
+        Self {
+            link,
+            query: None,
+            page: None,
+            selected: None,
+        }
+    }
+
+    /// Sends a query to the server.
+    fn send(&mut self, query: Query) -> ShouldRender {
+        self.query = Some(query);
+        self.link
+            .send_message(msg::Msg::ToServer(msg::to_server::Msg::inspect(query)));
+        true
+    }
+
+    /// Handles an inspector message.
+    pub fn update(&mut self, msg: Msg) -> Res<ShouldRender> {
+        let redraw = match msg {
+            Msg::Inspect(time) => {
+                let line = self
+                    .query
+                    .map(|query| query.line)
+                    .unwrap_or(uid::Line::Everything);
+                self.send(Query::new(line, time))
+            }
+            Msg::SetLine(line) => match self.query {
+                Some(query) if query.line != line => self.send(query.with_line(line)),
+                Some(_) => false,
+                None => bail!("cannot change the line of a closed inspector"),
+            },
+            Msg::Page(page) => match self.query {
+                Some(query) if query.page != page => self.send(query.with_page(page)),
+                Some(_) => false,
+                None => bail!("cannot change the page of a closed inspector"),
+            },
+            Msg::Select(uid) => {
+                let redraw = self.selected != Some(uid);
+                self.selected = Some(uid);
+                redraw
+            }
+            Msg::Close => {
+                let redraw = self.query.is_some();
+                self.query = None;
+                self.page = None;
+                self.selected = None;
+                redraw
+            }
+        };
+        Ok(redraw)
+    }
+
+    /// Handles a page of allocations from the server.
+    ///
+    /// Pages answering a query other than the last one sent are ignored.
+    pub fn server_update(&mut self, page: Page) -> ShouldRender {
+        if self.query != Some(page.query) {
+            return false;
+        }
+        if let Some(uid) = self.selected {
+            if page.get(uid).is_none() {
+                self.selected = None
+            }
+        }
+        self.page = Some(page);
+        true
+    }
+
+    /// Renders the inspector.
+    pub fn render(&self, model: &Model) -> Html {
+        let query = if let Some(query) = self.query {
+            query
+        } else {
+            return html! {};
+        };
+
+        let lines = model.filters().specs_iter().map(|spec| {
+            let line = spec.uid();
+            if line == query.line {
+                html! {
+                    <span
+                        style = ACTIVE_LINE_STYLE
+                    >
+                        {spec.name()}
+                    </span>
+                }
+            } else {
+                html! {
+                    <span
+                        style = LINE_STYLE
+                        onclick = self.link.callback(move |_| Msg::SetLine(line))
+                    >
+                        {spec.name()}
+                    </span>
+                }
+            }
+        });
+
+        let time = query.time;
+        let snapshot = html! {
+            <span
+                style = NAV_STYLE
+                onclick = self.link.callback(move |_| snapshot::Msg::Take(time))
+            >
+                {"heap snapshot"}
+            </span>
+        };
+
+        let close = html! {
+            <span
+                style = NAV_STYLE
+                onclick = self.link.callback(|_| Msg::Close)
+            >
+                {"close"}
+            </span>
+        };
+
+        html! {
+            <div
+                style = CONTAINER_STYLE
+            >
+                {layout::section_title(&format!("Allocations live at {}", query.time))}
+                <center>
+                    {for lines}
+                    {snapshot}
+                    {close}
+                </center>
+                {self.render_page(query)}
+                {self.render_trace()}
+            </div>
+        }
+    }
+
+    /// Renders the current page, if it answers the current query.
+    fn render_page(&self, query: Query) -> Html {
+        let page = match self.page.as_ref() {
+            Some(page) if page.query == query => page,
+            _ => return html! { <center>{"loading..."}</center> },
+        };
+
+        let nav = |label: &str, active: bool, target: usize| {
+            if active {
+                html! {
+                    <span
+                        style = NAV_STYLE
+                        onclick = self.link.callback(move |_| Msg::Page(target))
+                    >
+                        {label}
+                    </span>
+                }
+            } else {
+                html! {
+                    <span
+                        style = HIDDEN_NAV_STYLE
+                    >
+                        {label}
+                    </span>
+                }
+            }
+        };
+        let prev = nav("previous", page.has_prev(), query.page.saturating_sub(1));
+        let next = nav("next", page.has_next(), query.page + 1);
+
+        let rows = page.allocs.iter().map(|alloc| {
+            let uid = alloc.uid;
+            let style: &str = if self.selected == Some(uid) {
+                &SELECTED_ROW_STYLE
+            } else {
+                &ROW_STYLE
+            };
+            html! {
+                <tr
+                    style = style
+                    onclick = self.link.callback(move |_| Msg::Select(uid))
+                >
+                    <td>{uid}</td>
+                    <td>{format!("{}B", alloc.real_size)}</td>
+                    <td>{alloc.kind.as_str()}</td>
+                    <td>{alloc.toc}</td>
+                    <td>{
+                        alloc.tod.map(|tod| tod.to_string()).unwrap_or_else(|| "_".into())
+                    }</td>
+                    <td>{
+                        alloc
+                            .lifetime()
+                            .map(|lifetime| lifetime.to_string())
+                            .unwrap_or_else(|| "_".into())
+                    }</td>
+                </tr>
+            }
+        });
+
+        html! {
+            <>
+                <center>
+                    {prev}
+                    {format!(
+                        "page {}/{} ({} allocations)",
+                        query.page + 1,
+                        page.page_count().max(1),
+                        page.total,
+                    )}
+                    {next}
+                </center>
+                <table
+                    style = TABLE_STYLE
+                >
+                    <tr
+                        style = HEADER_STYLE
+                    >
+                        <td>{"uid"}</td>
+                        <td>{"size"}</td>
+                        <td>{"kind"}</td>
+                        <td>{"time of creation"}</td>
+                        <td>{"time of death"}</td>
+                        <td>{"lifetime"}</td>
+                    </tr>
+                    {for rows}
+                </table>
+            </>
+        }
+    }
+
+    /// Renders the callstack of the selected allocation, if any.
+    fn render_trace(&self) -> Html {
+        let alloc = match (self.selected, self.page.as_ref()) {
+            (Some(uid), Some(page)) => page.get(uid),
+            _ => None,
+        };
+        let alloc = if let Some(alloc) = alloc {
+            alloc
+        } else {
+            return html! {};
+        };
+
+        html! {
+            <div
+                style = TRACE_STYLE
+            >
+                <div
+                    style = HEADER_STYLE
+                >
+                    {format!("callstack of allocation {}", alloc.uid)}
+                </div>
+                { for alloc.trace.iter().enumerate().map(|(idx, loc)| {
+                    let source = alloc.sources.get(idx).and_then(Option::as_ref);
+                    html! {
+                        <>
+                            <div>{loc}</div>
+                            { if let Some(source) = source {
+                                html! { <div style = SOURCE_STYLE>{source}</div> }
+                            } else {
+                                html! {}
+                            } }
+                        </>
+                    }
+                }) }
+            </div>
+        }
+    }
+}
@@ -45,6 +45,8 @@ use wasm::*;
 extern "C" {
     /// Issues an alert.
     pub fn alert(s: &str);
+    /// Asks the user for some text, `None` if the user cancels.
+    pub fn prompt(message: &str, default: &str) -> Option<String>;
 }
 
 /// Alias type for `wasm_bindgen`'s `JsValue`.
@@ -71,27 +73,101 @@ pub fn get_element_by_id(id: &str) -> Res<web_sys::Element> {
 pub mod server {
     prelude! {}
 
+    use yew::services::storage::{Area, StorageService};
+
+    /// Key of the session token in the browser's local storage.
+    const SESSION_KEY: &str = "memthol.session";
+
     fn location() -> Res<web_sys::Location> {
         web_sys::window()
             .map(|w| w.location())
             .ok_or_else(|| err::Error::from("could not retrieve (window) JS location"))
     }
 
-    /// Retrievs the address and the port of the server.
-    pub fn address() -> Res<(String, usize)> {
+    /// Retrieves the address of the server's websocket.
+    ///
+    /// The websocket is the `ws` route next to the page, on the same host and port. Hence it works
+    /// behind port forwarding and reverse proxies, including ones serving memthol under a path.
+    pub fn ws_address() -> Res<String> {
+        let js_err = |js_val: super::Value| err::Error::from(format!("{:?}", js_val));
         location()
             .and_then(|loc| {
-                Ok((
-                    loc.hostname()
-                        .map_err(|js_val| err::Error::from(format!("{:?}", js_val)))?,
-                    usize::from_str_radix(
-                        &loc.port()
-                            .map_err(|js_val| err::Error::from(format!("{:?}", js_val)))?,
-                        10,
-                    )
-                    .map_err(|e| e.to_string())?,
-                ))
+                let protocol = if loc.protocol().map_err(js_err)? == "https:" {
+                    "wss"
+                } else {
+                    "ws"
+                };
+                let host = loc.host().map_err(js_err)?;
+                let path = loc.pathname().map_err(js_err)?;
+                let dir = &path[0..path.rfind('/').map(|idx| idx + 1).unwrap_or(0)];
+                Ok(format!("{}://{}{}ws", protocol, host, dir))
             })
-            .chain_err(|| "while retrieving server's address and port")
+            .chain_err(|| "while retrieving server's websocket address")
+    }
+
+    /// Retrieves the id of the view in the page's URL, if any.
+    pub fn view_id() -> Res<Option<String>> {
+        let search = location()?
+            .search()
+            .map_err(|js_val| err::Error::from(format!("{:?}", js_val)))
+            .chain_err(|| "while retrieving the page's URL parameters")?;
+        let id = search
+            .trim_start_matches('?')
+            .split('&')
+            .filter_map(|pair| {
+                let mut kv = pair.splitn(2, '=');
+                match (kv.next(), kv.next()) {
+                    (Some(charts::view::QUERY_KEY), Some(id)) if !id.is_empty() => Some(id),
+                    _ => None,
+                }
+            })
+            .next()
+            .map(String::from);
+        Ok(id)
+    }
+
+    /// Removes the URL parameters of the page, without reloading it.
+    ///
+    /// Does nothing if the page has no URL parameters.
+    pub fn forget_view_id() -> Res<()> {
+        if view_id()?.is_none() {
+            return Ok(());
+        }
+        let js_err = |js_val: super::Value| err::Error::from(format!("{:?}", js_val));
+        let path = location()?.pathname().map_err(js_err)?;
+        web_sys::window()
+            .ok_or_else(|| err::Error::from("could not retrieve (window) JS history"))?
+            .history()
+            .map_err(js_err)?
+            .replace_state_with_url(&super::Value::NULL, "", Some(&path))
+            .map_err(js_err)
+            .chain_err(|| "while removing the view from the page's URL")
+    }
+
+    /// URL of the page opening a view.
+    pub fn view_url(id: &str) -> Res<String> {
+        let js_err = |js_val: super::Value| err::Error::from(format!("{:?}", js_val));
+        let loc = location()?;
+        Ok(format!(
+            "{}{}?{}={}",
+            loc.origin().map_err(js_err)?,
+            loc.pathname().map_err(js_err)?,
+            charts::view::QUERY_KEY,
+            id,
+        ))
+    }
+
+    /// Retrieves the session token stored in the browser, if any.
+    pub fn session_token() -> Option<String> {
+        StorageService::new(Area::Local)
+            .ok()
+            .and_then(|storage| storage.restore::<Result<String, _>>(SESSION_KEY).ok())
+    }
+
+    /// Stores a session token in the browser.
+    pub fn set_session_token(token: &str) -> Res<()> {
+        let mut storage = StorageService::new(Area::Local)?;
+        storage.store(SESSION_KEY, Ok(token.to_string()));
+        Ok(())
     }
 }
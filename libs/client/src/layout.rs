@@ -91,7 +91,16 @@ pub fn render(model: &Model) -> Html {
                     if let Some(load_info) = model.progress.as_ref() {
                         progress::render(load_info)
                     } else {
-                        model.charts.render(model)
+                        html! {
+                            <>
+                                {model.traces.render()}
+                                {model.charts.render(model)}
+                                {model.inspector.render(model)}
+                                {model.snapshots.render()}
+                                {model.leaks.render()}
+                                {model.share.render()}
+                            </>
+                        }
                     }
                 }
             </div>
@@ -131,7 +131,13 @@ define_style! {
 }
 
 /// Renders a chart.
-fn render_chart(_model: &Model, chart: &Chart) -> Html {
+///
+/// Clicking on the chart inspects the allocations live at the time clicked. Dragging selects a
+/// time window, or pans the chart when shift is pressed, and the mouse wheel zooms in and out.
+/// Hovering over the chart shows the value of each visible filter at the time hovered, and
+/// alt-clicking adds an annotation at the time clicked.
+fn render_chart(model: &Model, chart: &Chart) -> Html {
+    let chart_uid = chart.uid();
     let visible = chart.is_visible();
     let canvas_id = chart.canvas_id();
     // let collapsed_canvas_id = chart.collapsed_canvas_id();
@@ -159,6 +165,28 @@ fn render_chart(_model: &Model, chart: &Chart) -> Html {
             } else {
                 &*COLLAPSED_CHART_CONTAINER_STYLE
             }
+            onmousedown = model.link.callback(move |e: yew::events::MouseEvent| {
+                if e.button() == 0 && e.alt_key() {
+                    e.prevent_default();
+                    msg::ChartMsg::annotate(chart_uid, e.offset_x()).into()
+                } else if e.button() == 0 {
+                    e.prevent_default();
+                    msg::ChartMsg::drag_start(chart_uid, e.offset_x(), e.shift_key()).into()
+                } else {
+                    Msg::Noop
+                }
+            })
+            onmousemove = model.link.callback(move |e: yew::events::MouseEvent| {
+                msg::ChartMsg::mouse_move(chart_uid, e.offset_x())
+            })
+            onmouseup = model.link.callback(move |e: yew::events::MouseEvent| {
+                msg::ChartMsg::drag_end(chart_uid, e.offset_x())
+            })
+            onmouseleave = model.link.callback(move |_| msg::ChartMsg::mouse_leave(chart_uid))
+            onwheel = model.link.callback(move |e: yew::events::WheelEvent| {
+                e.prevent_default();
+                msg::ChartMsg::zoom(chart_uid, e.offset_x(), e.delta_y() < 0.)
+            })
         >
             {inner}
         </div>
@@ -451,6 +479,8 @@ pub mod settings {
 
                 { title(model, chart) }
                 { options(model, chart) }
+                { downsampling(model, chart) }
+                { time_window(model, chart) }
             </div>
         }
     }
@@ -512,6 +542,88 @@ pub mod settings {
             html!()
         }
     }
+
+    /// Renders the chart's downsampling settings.
+    pub fn downsampling(model: &Model, chart: &Chart) -> Html {
+        let chart_uid = chart.uid();
+        let current = chart.settings().downsampling();
+        let mut row = layout::table::TableRow::new_menu(false, html! { "downsampling" })
+            .black_sep()
+            .height_px(LINE_HEIGHT_PX);
+
+        let select = html! {
+            <>
+                {for chart::settings::Downsampling::all().into_iter().enumerate().map(|(idx, mode)| {
+                    layout::input::radio(
+                        mode == current,
+                        format!("chart_{}_{}_downsampling", chart_uid, mode.to_uname()),
+                        mode.desc(),
+                        model.link.callback(move |_| {
+                            msg::ChartSettingsMsg::set_downsampling::<msg::ChartsMsg>(
+                                chart_uid, mode
+                            )
+                        }),
+                        model.link.callback(move |_| {
+                            msg::ChartSettingsMsg::set_downsampling::<msg::ChartsMsg>(
+                                chart_uid, mode
+                            )
+                        }),
+                        idx > 0,
+                    )
+                })}
+            </>
+        };
+        row.push_single_value(select);
+        row.render()
+    }
+
+    /// Renders the chart's time window settings.
+    pub fn time_window(model: &Model, chart: &Chart) -> Html {
+        let chart_uid = chart.uid();
+        let is_local = chart.has_local_window();
+        let mut row = layout::table::TableRow::new_menu(false, html! { "time window" })
+            .black_sep()
+            .height_px(LINE_HEIGHT_PX);
+
+        let radio = |local: bool, name: &str, pre_space: bool| {
+            let checked = local == is_local;
+            let (onchange, onclick) = if checked {
+                (Callback::noop(), Callback::noop())
+            } else {
+                (
+                    model
+                        .link
+                        .callback(move |_| msg::ChartMsg::toggle_local_window(chart_uid)),
+                    model
+                        .link
+                        .callback(move |_| msg::ChartMsg::toggle_local_window(chart_uid)),
+                )
+            };
+            layout::input::radio(
+                checked,
+                format!("chart_{}_{}_window", chart_uid, name),
+                name,
+                onchange,
+                onclick,
+                pre_space,
+            )
+        };
+        let select_window = html! {
+            <>
+                {radio(false, "global", false)}
+                {radio(true, "local", true)}
+            </>
+        };
+        row.push_value(select_window);
+        row.push_sep(html! {});
+        row.push_button(
+            "reset zoom",
+            model
+                .link
+                .callback(move |_| msg::ChartMsg::reset_zoom(chart_uid)),
+        );
+        row.render()
+    }
 }
 
 /// Filter tabs (bottom) rendering.
@@ -0,0 +1,236 @@
+/*<LICENSE>
+    This file is part of Memthol.
+
+    Copyright (C) 2020 OCamlPro.
+
+    Memthol is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Memthol is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Memthol.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Leak suspects.
+//!
+//! Lists the allocation sites the server suspects to leak, and lets users turn each of them into a
+//! filter.
+
+prelude! {}
+
+use charts::leak::{Report, Suspect};
+
+define_style! {
+    CONTAINER_STYLE = {
+        block,
+        margin(0%, 1%, 2%, 1%),
+    };
+    TABLE_STYLE = {
+        width(100%),
+        text_align(center),
+    };
+    HEADER_STYLE = {
+        bold,
+    };
+    SITE_STYLE = {
+        font(code),
+        text_align(left),
+    };
+    NAV_STYLE = {
+        pointer,
+        padding(0%, 10 px),
+        underline,
+    };
+}
+
+/// Messages acting on the leak suspects.
+#[derive(Clone, Debug)]
+pub enum Msg {
+    /// Asks the server for the leak suspects.
+    Request,
+    /// Creates a filter for a suspect.
+    AddFilter(usize),
+    /// Closes the leak suspects.
+    Close,
+}
+base::implement! {
+    impl Msg {
+        Display {
+            |&self, fmt| match self {
+                Self::Request => write!(fmt, "request"),
+                Self::AddFilter(index) => write!(fmt, "add filter {}", index),
+                Self::Close => write!(fmt, "close"),
+            }
+        }
+    }
+}
+
+/// Leak suspects.
+pub struct Leaks {
+    /// Link to the model.
+    link: Link,
+    /// True if a request was sent to the server and was not answered yet.
+    pending: bool,
+    /// Last report received from the server.
+    report: Option<Report>,
+}
+
+impl Leaks {
+    /// Constructor.
+    pub fn new(link: Link) -> Self {
+        Self {
+            link,
+            pending: false,
+            report: None,
+        }
+    }
+
+    /// Handles a leak suspects message.
+    pub fn update(&mut self, msg: Msg) -> Res<ShouldRender> {
+        let redraw = match msg {
+            Msg::Request => {
+                self.pending = true;
+                self.link
+                    .send_message(msg::Msg::ToServer(msg::to_server::Msg::leaks()));
+                true
+            }
+            Msg::AddFilter(index) => {
+                let suspect = self
+                    .report
+                    .as_ref()
+                    .and_then(|report| report.suspects.get(index))
+                    .ok_or_else(|| format!("unknown leak suspect #{}", index))?;
+                let filter = suspect
+                    .filter
+                    .clone()
+                    .ok_or("cannot create a filter for an unknown allocation site")?;
+                self.link.send_message(msg::Msg::ToServer(
+                    msg::to_server::FiltersMsg::request_new_with(suspect.filter_name(), filter),
+                ));
+                false
+            }
+            Msg::Close => {
+                let redraw = self.pending || self.report.is_some();
+                self.pending = false;
+                self.report = None;
+                redraw
+            }
+        };
+        Ok(redraw)
+    }
+
+    /// Handles a report from the server.
+    ///
+    /// Reports that were not requested, or that arrive after the suspects were closed, are
+    /// ignored.
+    pub fn server_update(&mut self, report: Report) -> ShouldRender {
+        if !self.pending {
+            return false;
+        }
+        self.pending = false;
+        self.report = Some(report);
+        true
+    }
+
+    /// Renders the leak suspects.
+    pub fn render(&self) -> Html {
+        let nav = |label: &str, msg: Msg| {
+            html! {
+                <span
+                    style = NAV_STYLE
+                    onclick = self.link.callback(move |_| msg.clone())
+                >
+                    {label}
+                </span>
+            }
+        };
+
+        let report = match self.report.as_ref() {
+            _ if self.pending => {
+                return html! { <center>{"looking for leak suspects..."}</center> };
+            }
+            Some(report) => report,
+            None => return html! { <center>{nav("look for leak suspects", Msg::Request)}</center> },
+        };
+
+        let rows = report
+            .suspects
+            .iter()
+            .enumerate()
+            .map(|(index, suspect)| self.render_suspect(index, suspect));
+
+        html! {
+            <div
+                style = CONTAINER_STYLE
+            >
+                {layout::section_title("Leak suspects")}
+                <center>
+                    {format!(
+                        "{} suspect(s) out of {} allocation site(s)",
+                        report.suspect_count, report.site_count,
+                    )}
+                    {nav("refresh", Msg::Request)}
+                    {nav("close", Msg::Close)}
+                </center>
+                <table
+                    style = TABLE_STYLE
+                >
+                    <tr
+                        style = HEADER_STYLE
+                    >
+                        <td>{"allocation site"}</td>
+                        <td>{"reasons"}</td>
+                        <td>{"allocations"}</td>
+                        <td>{"never freed"}</td>
+                        <td>{"growth"}</td>
+                        <td>{"average lifetime"}</td>
+                        <td/>
+                    </tr>
+                    {for rows}
+                </table>
+            </div>
+        }
+    }
+
+    /// Renders a suspect as a table row.
+    fn render_suspect(&self, index: usize, suspect: &Suspect) -> Html {
+        let reasons: Vec<_> = suspect.reasons.iter().map(|reason| reason.desc()).collect();
+        let lifetimes = suspect
+            .lifetimes
+            .map(|(early, late)| format!("{} → {}", early, late))
+            .unwrap_or_else(|| "_".into());
+        let filter = if suspect.filter.is_some() {
+            html! {
+                <span
+                    style = NAV_STYLE
+                    onclick = self.link.callback(move |_| Msg::AddFilter(index))
+                >
+                    {"add filter"}
+                </span>
+            }
+        } else {
+            html! {}
+        };
+        html! {
+            <tr>
+                <td
+                    style = SITE_STYLE
+                >
+                    {suspect.site.as_deref().unwrap_or("<unknown>")}
+                </td>
+                <td>{reasons.join(", ")}</td>
+                <td>{format!("{} ({}B)", suspect.count, suspect.bytes)}</td>
+                <td>{format!("{}% ({}B)", suspect.never_freed(), suspect.live_bytes)}</td>
+                <td>{format!("{}%", suspect.growth)}</td>
+                <td>{lifetimes}</td>
+                <td>{filter}</td>
+            </tr>
+        }
+    }
+}
@@ -31,8 +31,13 @@ pub mod macros;
 pub mod js;
 pub mod layout;
 
+pub mod inspector;
+pub mod leaks;
 pub mod model;
 pub mod settings;
+pub mod share;
+pub mod snapshot;
+pub mod traces;
 
 pub mod chart;
 pub mod cst;
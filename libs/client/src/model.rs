@@ -21,12 +21,17 @@
 
 prelude! {}
 
+/// Time to wait before trying to reconnect to the server when the connection is lost.
+const RECONNECT_DELAY: std::time::Duration = std::time::Duration::from_secs(2);
+
 /// Model of the client.
 pub struct Model {
     /// Component link.
     pub link: Link,
     /// Socket task for receiving/sending messages from/to the server.
     pub socket_task: Option<WebSocketTask>,
+    /// Pending reconnection to the server, if the connection was lost.
+    pub reconnect_task: Option<TimeoutTask>,
     /// Errors.
     pub errors: Vec<err::Error>,
     /// Collection of charts.
@@ -47,6 +52,18 @@ pub struct Model {
 
     /// Global chart settings.
     pub settings: settings::Settings,
+
+    /// Traces of the trace directory, if any.
+    pub traces: traces::Traces,
+
+    /// Allocation inspector.
+    pub inspector: inspector::Inspector,
+    /// Heap snapshot view.
+    pub snapshots: snapshot::Snapshots,
+    /// Leak suspects.
+    pub leaks: leaks::Leaks,
+    /// View sharing.
+    pub share: share::Share,
 }
 
 impl Model {
@@ -77,8 +94,17 @@ impl Model {
     /// Activates the websocket to receive data from the server.
     fn activate_ws(link: &mut Link) -> Res<WebSocketTask> {
         log::info!("fetching server's websocket info");
-        let (addr, port) = js::server::address()?;
-        let addr = format!("ws://{}:{}", addr, port + 1);
+        let mut addr = js::server::ws_address()?;
+        let mut params = vec![];
+        if let Some(token) = js::server::session_token() {
+            params.push(format!("session={}", token))
+        }
+        if let Some(id) = js::server::view_id()? {
+            params.push(format!("{}={}", charts::view::QUERY_KEY, id))
+        }
+        if !params.is_empty() {
+            addr.push_str(&format!("?{}", params.join("&")))
+        }
         log::info!("websocket: {:?}", addr);
         let callback = link.callback(|msg| Msg::FromServer(msg));
         let notification = link.callback(|status| Msg::ConnectionStatus(status));
@@ -142,6 +168,29 @@ impl Model {
                 self.progress = None;
                 Ok(redraw)
             }
+            Msg::Traces { traces, active } => Ok(self.traces.server_update(traces, active)),
+            Msg::Session(token) => {
+                js::server::set_session_token(&token)?;
+                // The view in the URL, if any, was applied: reloading the page should reattach the
+                // session rather than re-open the view.
+                js::server::forget_view_id()?;
+                // The state of the session follows, drop the current one.
+                self.charts.clear();
+                self.inspector.update(inspector::Msg::Close)?;
+                self.snapshots.update(snapshot::Msg::Close)?;
+                self.leaks.update(leaks::Msg::Close)?;
+                self.share.update(share::Msg::Close)?;
+                Ok(true)
+            }
+            Msg::Settings(settings) => {
+                let render = self.settings.server_update(settings);
+                let annotations_changed = self.charts.set_annotations(self.settings.annotations());
+                Ok(render || annotations_changed)
+            }
+            Msg::Inspect(page) => Ok(self.inspector.server_update(page)),
+            Msg::Snapshot(snapshot) => Ok(self.snapshots.server_update(snapshot)),
+            Msg::Leaks(report) => Ok(self.leaks.server_update(report)),
+            Msg::Shared(id) => self.share.server_update(id),
         }
     }
 }
@@ -171,9 +220,15 @@ impl Component for Model {
         let filters = filter::FilterInfo::new(link.clone());
         let settings = settings::Settings::new(link.clone());
         let header = header::Header::new(link.clone());
+        let traces = traces::Traces::new(link.clone());
+        let inspector = inspector::Inspector::new(link.clone());
+        let snapshots = snapshot::Snapshots::new(link.clone());
+        let leaks = leaks::Leaks::new(link.clone());
+        let share = share::Share::new(link.clone());
         Model {
             link,
             socket_task,
+            reconnect_task: None,
             errors,
             charts,
 
@@ -185,6 +240,11 @@ impl Component for Model {
             progress: Some(LoadInfo::unknown()),
             alloc_stats: None,
             settings,
+            traces,
+            inspector,
+            snapshots,
+            leaks,
+            share,
         }
     }
 
@@ -206,9 +266,30 @@ impl Component for Model {
             Msg::ConnectionStatus(status) => {
                 use WebSocketStatus::*;
                 match status {
-                    Opened => log::debug!("successfully established connection with the server"),
-                    Closed => log::warn!("connection with the server was closed"),
-                    Error => alert!("failed to connect with the server"),
+                    Opened => {
+                        log::debug!("successfully established connection with the server");
+                        self.reconnect_task = None
+                    }
+                    Closed => {
+                        log::warn!("connection with the server was closed, reconnecting");
+                        self.socket_task = None;
+                        self.reconnect_task = Some(TimeoutService::spawn(
+                            RECONNECT_DELAY,
+                            self.link.callback(|_| Msg::Reconnect),
+                        ))
+                    }
+                    // Only report errors for the first connection, not when trying to reconnect.
+                    Error if self.reconnect_task.is_none() => {
+                        alert!("failed to connect with the server")
+                    }
+                    Error => log::warn!("failed to reconnect with the server"),
+                }
+                false
+            }
+            Msg::Reconnect => {
+                match Self::activate_ws(&mut self.link) {
+                    Ok(socket_task) => self.socket_task = Some(socket_task),
+                    Err(e) => self.link.send_message(e),
                 }
                 false
             }
@@ -223,8 +304,24 @@ impl Component for Model {
             Msg::Filter(msg) => unwrap_or_send_err!(
                 self.filters.update(msg) => self default false
             ),
-            Msg::Settings(msg) => unwrap_or_send_err!(
-                self.settings.update(msg) => self default false
+            Msg::Settings(msg) => {
+                let render = unwrap_or_send_err!(
+                    self.settings.update(msg) => self default false
+                );
+                let annotations_changed = self.charts.set_annotations(self.settings.annotations());
+                render || annotations_changed
+            }
+            Msg::Inspector(msg) => unwrap_or_send_err!(
+                self.inspector.update(msg) => self default false
+            ),
+            Msg::Snapshot(msg) => unwrap_or_send_err!(
+                self.snapshots.update(msg) => self default false
+            ),
+            Msg::Leaks(msg) => unwrap_or_send_err!(
+                self.leaks.update(msg) => self default false
+            ),
+            Msg::Share(msg) => unwrap_or_send_err!(
+                self.share.update(msg) => self default false
             ),
 
             // Basic communication messages.
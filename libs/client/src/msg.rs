@@ -31,6 +31,22 @@ pub mod filter {
 pub mod settings {
     pub use crate::settings::Msg;
 }
+/// Allocation inspector messages.
+pub mod inspector {
+    pub use crate::inspector::Msg;
+}
+/// Leak suspects messages.
+pub mod leaks {
+    pub use crate::leaks::Msg;
+}
+/// Heap snapshot messages.
+pub mod snapshot {
+    pub use crate::snapshot::Msg;
+}
+/// View sharing messages.
+pub mod share {
+    pub use crate::share::Msg;
+}
 
 /// Internal model messages.
 ///
@@ -53,6 +69,14 @@ pub enum Msg {
     Filter(filter::Msg),
     /// Settings operations.
     Settings(settings::Msg),
+    /// Allocation inspector operations.
+    Inspector(inspector::Msg),
+    /// Heap snapshot operations.
+    Snapshot(snapshot::Msg),
+    /// Leak suspects operations.
+    Leaks(leaks::Msg),
+    /// View sharing operations.
+    Share(share::Msg),
 
     /// A message to print in the JS console.
     Msg(String),
@@ -61,6 +85,9 @@ pub enum Msg {
     /// An error.
     Err(err::Error),
 
+    /// Tries to reconnect to the server.
+    Reconnect,
+
     /// A message that does nothing.
     Noop,
 }
@@ -148,6 +175,37 @@ pub enum ChartMsg {
     FilterToggleVisible(uid::Line),
     /// Updates the chart's settings.
     SettingsUpdate(ChartSettingsMsg),
+
+    /// Starts a mouse drag at some x-position (in pixels) on the chart's canvas.
+    DragStart {
+        /// X-position of the mouse.
+        x: i32,
+        /// Pan the chart if true, select a time window otherwise.
+        pan: bool,
+    },
+    /// The mouse moved to some x-position on the chart's canvas.
+    ///
+    /// Updates the current mouse drag if any, moves the hover crosshair otherwise.
+    MouseMove(i32),
+    /// Ends a mouse drag at some x-position on the chart's canvas.
+    ///
+    /// If the mouse did not really move, this inspects the allocations at that position.
+    DragEnd(i32),
+    /// The mouse left the chart's canvas, cancels the current mouse drag and hides the crosshair.
+    MouseLeave,
+    /// Zooms around some x-position on the chart's canvas.
+    Zoom {
+        /// X-position of the mouse.
+        x: i32,
+        /// Zoom in if true, out otherwise.
+        zoom_in: bool,
+    },
+    /// Adds an annotation at some x-position on the chart's canvas, asks the user for its label.
+    Annotate(i32),
+    /// Resets the time window of the chart.
+    ResetZoom,
+    /// Toggles between the global time window and a local one for the chart.
+    ToggleLocalWindow,
 }
 
 impl ChartMsg {
@@ -159,6 +217,39 @@ impl ChartMsg {
     pub fn filter_toggle_visible(uid: uid::Chart, line: uid::Line) -> ChartsMsg {
         (uid, Self::FilterToggleVisible(line)).into()
     }
+
+    /// Starts a mouse drag.
+    pub fn drag_start(uid: uid::Chart, x: i32, pan: bool) -> ChartsMsg {
+        (uid, Self::DragStart { x, pan }).into()
+    }
+    /// The mouse moved.
+    pub fn mouse_move(uid: uid::Chart, x: i32) -> ChartsMsg {
+        (uid, Self::MouseMove(x)).into()
+    }
+    /// Ends a mouse drag.
+    pub fn drag_end(uid: uid::Chart, x: i32) -> ChartsMsg {
+        (uid, Self::DragEnd(x)).into()
+    }
+    /// The mouse left the chart.
+    pub fn mouse_leave(uid: uid::Chart) -> ChartsMsg {
+        (uid, Self::MouseLeave).into()
+    }
+    /// Adds an annotation at some x-position.
+    pub fn annotate(uid: uid::Chart, x: i32) -> ChartsMsg {
+        (uid, Self::Annotate(x)).into()
+    }
+    /// Zooms around some x-position.
+    pub fn zoom(uid: uid::Chart, x: i32, zoom_in: bool) -> ChartsMsg {
+        (uid, Self::Zoom { x, zoom_in }).into()
+    }
+    /// Resets the time window of a chart.
+    pub fn reset_zoom(uid: uid::Chart) -> ChartsMsg {
+        (uid, Self::ResetZoom).into()
+    }
+    /// Toggles between the global time window and a local one.
+    pub fn toggle_local_window(uid: uid::Chart) -> ChartsMsg {
+        (uid, Self::ToggleLocalWindow).into()
+    }
 }
 
 /// Footer operation.
@@ -191,9 +282,14 @@ base::implement! {
                 Self::Footer(footer_msg) => write!(fmt, "footer, {}", footer_msg),
                 Self::Filter(filter_msg) => write!(fmt, "filter, {}", filter_msg),
                 Self::Settings(settings_msg) => write!(fmt, "settings, {}", settings_msg),
+                Self::Inspector(inspector_msg) => write!(fmt, "inspector, {}", inspector_msg),
+                Self::Snapshot(snapshot_msg) => write!(fmt, "snapshot, {}", snapshot_msg),
+                Self::Leaks(leaks_msg) => write!(fmt, "leaks, {}", leaks_msg),
+                Self::Share(share_msg) => write!(fmt, "share, {}", share_msg),
                 Self::Msg(_) => write!(fmt, "info"),
                 Self::Warn(_) => write!(fmt, "warning"),
                 Self::Err(_) => write!(fmt, "error"),
+                Self::Reconnect => write!(fmt, "reconnect"),
                 Self::Noop => write!(fmt, "noop"),
             }
         }
@@ -209,6 +305,10 @@ base::implement! {
             },
             from FooterMsg => |msg| Self::Footer(msg),
             from settings::Msg => |msg| Self::Settings(msg),
+            from inspector::Msg => |msg| Self::Inspector(msg),
+            from snapshot::Msg => |msg| Self::Snapshot(msg),
+            from leaks::Msg => |msg| Self::Leaks(msg),
+            from share::Msg => |msg| Self::Share(msg),
         }
     }
 
@@ -239,6 +339,14 @@ base::implement! {
                 Self::SettingsToggleVisible => write!(fmt, "settings toggle visible"),
                 Self::FilterToggleVisible(l_uid) => write!(fmt, "filter toggle visible {}", l_uid),
                 Self::SettingsUpdate(msg) => write!(fmt, "{}", msg),
+                Self::DragStart { x, pan } => write!(fmt, "drag start {} (pan: {})", x, pan),
+                Self::MouseMove(x) => write!(fmt, "mouse move {}", x),
+                Self::DragEnd(x) => write!(fmt, "drag end {}", x),
+                Self::MouseLeave => write!(fmt, "mouse leave"),
+                Self::Zoom { x, zoom_in } => write!(fmt, "zoom {} (in: {})", x, zoom_in),
+                Self::Annotate(x) => write!(fmt, "annotate {}", x),
+                Self::ResetZoom => write!(fmt, "reset zoom"),
+                Self::ToggleLocalWindow => write!(fmt, "toggle local window"),
             }
         }
 
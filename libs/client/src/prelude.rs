@@ -21,7 +21,10 @@
 pub use yew::{
     html,
     html::ChangeData,
-    services::websocket::{WebSocketService, WebSocketStatus, WebSocketTask},
+    services::{
+        timeout::{TimeoutService, TimeoutTask},
+        websocket::{WebSocketService, WebSocketStatus, WebSocketTask},
+    },
     Callback, Component, Renderable, ShouldRender,
 };
 pub use yew_components::Select;
@@ -31,7 +34,8 @@ pub use base::prelude::*;
 pub use charts::{
     palette,
     prelude::{
-        alloc, filter::stats::AllFilterStats, num_fmt, time, Alloc, AllocStats, LoadInfo, Regex,
+        alloc, filter::stats::AllFilterStats, marker, num_fmt, time, Alloc, AllocStats, LoadInfo,
+        Regex, TimeWindopt, TraceInfo,
     },
 };
 
@@ -73,17 +77,18 @@ pub fn msg_of_res(res: Res<Msg>) -> Msg {
 
 /// Re-exports from `charts::point`.
 pub mod point {
-    pub use charts::point::{Point, Points, TimePoints};
+    pub use charts::point::{Point, Points, PointsPatch, TimePoints};
 }
 pub use point::Point;
 
 pub use crate::{
     chart::{self, Chart, Charts},
-    cst, filter, js,
+    cst, filter, inspector, js,
     layout::{self, footer, header},
+    leaks,
     model::Model,
     msg::{self, Msg},
-    settings,
+    settings, share, snapshot, traces,
 };
 
 /// Component link to the model, can send messages to the model.
@@ -48,7 +48,7 @@ impl DisplayMode {
     pub fn line_count(&self) -> usize {
         match self {
             Self::Collapsed => 0,
-            Self::Expanded(_) => 1,
+            Self::Expanded(_) => 2,
         }
     }
 
@@ -160,10 +160,22 @@ impl Settings {
         html! {
             <>
                 {self.time_window_line(model)}
+                {self.annotations_line()}
             </>
         }
     }
 
+    /// Overwrites the global charts settings with the ones from the server.
+    pub fn server_update(&mut self, settings: charts::chart::settings::Charts) -> ShouldRender {
+        self.charts_settings = Memory::new(settings);
+        true
+    }
+
+    /// User annotations, as applied on the server.
+    pub fn annotations(&self) -> &[marker::Marker] {
+        self.charts_settings.reference().annotations()
+    }
+
     /// True if the current settings are different form the server ones.
     pub fn has_changed(&self) -> bool {
         // Exhaustive deconstruction so that this breaks when new fields are added to `Self`.
@@ -325,6 +337,74 @@ impl Settings {
         )
     }
 
+    /// Generates the annotations line.
+    pub fn annotations_line(&self) -> Html {
+        const BORDER_HEIGHT_PX: usize = 2;
+        const LINE_HEIGHT_PX: usize = header::HEADER_LINE_HEIGHT_PX - BORDER_HEIGHT_PX;
+        const BUTTON_HEIGHT_PX: usize = LINE_HEIGHT_PX / 2;
+        define_style! {
+            ANNOTATION = {
+                inline block,
+                margin(0 px, 10 px),
+            };
+            SETTINGS_LINE = {
+                border(bottom, {BORDER_HEIGHT_PX}px, {layout::LIGHT_BLUE_FG}),
+                height({LINE_HEIGHT_PX}px),
+            };
+        }
+
+        let annotations = self.annotations();
+        let content = if annotations.is_empty() {
+            html! { "none, alt-click on a chart to add one" }
+        } else {
+            html! {
+                { for annotations.iter().enumerate().map(|(index, annotation)| html! {
+                    <div
+                        style = ANNOTATION
+                    >
+                        { layout::header::code(format!("{}s", annotation.time)) }
+                        { " " }
+                        { &annotation.label }
+                        { layout::button::img::close(
+                            Some(BUTTON_HEIGHT_PX),
+                            format!("header_settings_annotation_{}", index),
+                            Some(self.link.callback(
+                                move |_| msg::Msg::from(Msg::RemoveAnnotation(index))
+                            )),
+                            "remove annotation",
+                        ) }
+                    </div>
+                }) }
+            }
+        };
+
+        header::Header::three_part_line_with(
+            &*SETTINGS_LINE,
+            html! {},
+            header::Header::center(html! {
+                <div>
+                    { layout::header::emph("annotations") }
+                    { ": " }
+                    { content }
+                </div>
+            }),
+            html! {},
+        )
+    }
+
+    /// Applies an action to the annotations, and sends them to the server right away.
+    ///
+    /// Other pending changes to the settings are not applied.
+    fn update_annotations(
+        &mut self,
+        action: impl FnMut(&mut charts::chart::settings::Charts),
+    ) -> ShouldRender {
+        self.charts_settings.do_both(action);
+        let msg = msg::to_server::ChartsMsg::settings(self.charts_settings.reference().clone());
+        self.link.send_message(msg::Msg::ToServer(msg));
+        true
+    }
+
     /// Updates itself given a settings message.
     pub fn update(&mut self, msg: Msg) -> Res<ShouldRender> {
         let res = match msg {
@@ -365,6 +445,17 @@ impl Settings {
                 let changed = self.display_mode.dec();
                 Ok(changed)
             }
+            Msg::ApplyTimeWindow(window) => {
+                self.update(Msg::TimeWindowLb(window.lbound))?;
+                self.update(Msg::TimeWindowUb(window.ubound))?;
+                self.update(Msg::Save)
+            }
+            Msg::Annotate(annotation) => {
+                Ok(self.update_annotations(|settings| settings.add_annotation(annotation.clone())))
+            }
+            Msg::RemoveAnnotation(index) => Ok(self.update_annotations(|settings| {
+                let _ = settings.remove_annotation(index);
+            })),
             Msg::Revert => {
                 self.charts_settings.reset();
                 Ok(true)
@@ -399,6 +490,12 @@ pub enum Msg {
     TimeWindowLb(Option<time::SinceStart>),
     /// Updates the time window's upper bound.
     TimeWindowUb(Option<time::SinceStart>),
+    /// Sets the time window and applies the settings right away.
+    ApplyTimeWindow(TimeWindopt),
+    /// Adds an annotation and applies it right away.
+    Annotate(marker::Marker),
+    /// Removes the annotation with some index and applies it right away.
+    RemoveAnnotation(usize),
     /// Reverts the settings.
     Revert,
     /// Saves the current settings.
@@ -426,6 +523,20 @@ base::implement! {
                         .map(|ub| ub.to_string())
                         .unwrap_or("_".into()),
                 ),
+                Self::ApplyTimeWindow(window) => write!(
+                    fmt,
+                    "apply time window: [{}, {}]",
+                    window
+                        .lbound
+                        .map(|lb| lb.to_string())
+                        .unwrap_or("_".into()),
+                    window
+                        .ubound
+                        .map(|ub| ub.to_string())
+                        .unwrap_or("_".into()),
+                ),
+                Self::Annotate(annotation) => write!(fmt, "annotate: {}", annotation),
+                Self::RemoveAnnotation(index) => write!(fmt, "remove annotation #{}", index),
                 Self::Revert => write!(fmt, "revert"),
                 Self::Save => write!(fmt, "save"),
                 Self::Expand => write!(fmt, "expand"),
@@ -0,0 +1,140 @@
+/*<LICENSE>
+    This file is part of Memthol.
+
+    Copyright (C) 2020 OCamlPro.
+
+    Memthol is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Memthol is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Memthol.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! View sharing.
+//!
+//! Asks the server to store the current view, and shows a link to it. Opening the link opens
+//! memthol on the same charts, filters and time window. Changes that were not applied yet are not
+//! part of the view.
+
+prelude! {}
+
+define_style! {
+    NAV_STYLE = {
+        pointer,
+        padding(0%, 10 px),
+        underline,
+    };
+    URL_STYLE = {
+        font(code),
+        width(50%),
+    };
+}
+
+/// Messages acting on view sharing.
+#[derive(Clone, Debug)]
+pub enum Msg {
+    /// Asks the server for the id of the current view.
+    Request,
+    /// Hides the link.
+    Close,
+}
+base::implement! {
+    impl Msg {
+        Display {
+            |&self, fmt| match self {
+                Self::Request => write!(fmt, "request"),
+                Self::Close => write!(fmt, "close"),
+            }
+        }
+    }
+}
+
+/// View sharing.
+pub struct Share {
+    /// Link to the model.
+    link: Link,
+    /// True if a request was sent to the server and was not answered yet.
+    pending: bool,
+    /// Link to the last view shared.
+    url: Option<String>,
+}
+
+impl Share {
+    /// Constructor.
+    pub fn new(link: Link) -> Self {
+        Self {
+            link,
+            pending: false,
+            url: None,
+        }
+    }
+
+    /// Handles a view sharing message.
+    pub fn update(&mut self, msg: Msg) -> Res<ShouldRender> {
+        let redraw = match msg {
+            Msg::Request => {
+                self.pending = true;
+                self.link
+                    .send_message(msg::Msg::ToServer(msg::to_server::Msg::share()));
+                true
+            }
+            Msg::Close => {
+                let redraw = self.pending || self.url.is_some();
+                self.pending = false;
+                self.url = None;
+                redraw
+            }
+        };
+        Ok(redraw)
+    }
+
+    /// Handles the id of a view from the server.
+    ///
+    /// Ids that were not requested are ignored.
+    pub fn server_update(&mut self, id: String) -> Res<ShouldRender> {
+        if !self.pending {
+            return Ok(false);
+        }
+        self.pending = false;
+        self.url = Some(js::server::view_url(&id)?);
+        Ok(true)
+    }
+
+    /// Renders the view sharing link.
+    pub fn render(&self) -> Html {
+        let nav = |label: &str, msg: Msg| {
+            html! {
+                <span
+                    style = NAV_STYLE
+                    onclick = self.link.callback(move |_| msg.clone())
+                >
+                    {label}
+                </span>
+            }
+        };
+
+        match self.url.as_ref() {
+            _ if self.pending => html! { <center>{"sharing view..."}</center> },
+            Some(url) => html! {
+                <center>
+                    {"link to this view: "}
+                    <input
+                        type = "text"
+                        readonly = true
+                        style = URL_STYLE
+                        value = url
+                    />
+                    {nav("close", Msg::Close)}
+                </center>
+            },
+            None => html! { <center>{nav("share this view", Msg::Request)}</center> },
+        }
+    }
+}
@@ -0,0 +1,383 @@
+/*<LICENSE>
+    This file is part of Memthol.
+
+    Copyright (C) 2020 OCamlPro.
+
+    Memthol is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Memthol is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Memthol.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Heap snapshots.
+//!
+//! Displays the allocations live at some point in time aggregated by allocation site and
+//! callstack, or the difference between two points in time if a base time is set.
+
+prelude! {}
+
+use charts::snapshot::{Live, Query, Site, Snapshot};
+
+define_style! {
+    CONTAINER_STYLE = {
+        block,
+        margin(0%, 1%, 2%, 1%),
+    };
+    TABLE_STYLE = {
+        width(100%),
+        text_align(center),
+    };
+    HEADER_STYLE = {
+        bold,
+    };
+    ROW_STYLE = {
+        pointer,
+    };
+    EXPANDED_ROW_STYLE = {
+        bold,
+        fg({"#c8f5fd"}),
+        bg({layout::DARK_GREY_BG}),
+    };
+    NAV_STYLE = {
+        pointer,
+        padding(0%, 10 px),
+        underline,
+    };
+    SITE_STYLE = {
+        font(code),
+        text_align(left),
+    };
+    TRACE_STYLE = {
+        font(code),
+        text_align(left),
+        margin(1%, 5%, 0%, 5%),
+    };
+}
+
+/// Messages acting on the heap snapshot view.
+#[derive(Clone, Debug)]
+pub enum Msg {
+    /// Takes a snapshot at some point in time, diffed against the base time if any.
+    Take(time::SinceStart),
+    /// Uses the time of the current snapshot as the base time for the next snapshots.
+    SetBase,
+    /// Clears the base time.
+    ClearBase,
+    /// Expands or collapses the callstacks of a site.
+    Expand(usize),
+    /// Closes the snapshot view.
+    Close,
+}
+base::implement! {
+    impl Msg {
+        Display {
+            |&self, fmt| match self {
+                Self::Take(time) => write!(fmt, "take {}", time),
+                Self::SetBase => write!(fmt, "set base"),
+                Self::ClearBase => write!(fmt, "clear base"),
+                Self::Expand(site) => write!(fmt, "expand {}", site),
+                Self::Close => write!(fmt, "close"),
+            }
+        }
+    }
+}
+
+/// Heap snapshot view.
+pub struct Snapshots {
+    /// Link to the model.
+    link: Link,
+    /// Last query sent to the server, `None` if the view is closed.
+    query: Option<Query>,
+    /// Last snapshot received from the server.
+    snapshot: Option<Snapshot>,
+    /// Base time for diffs.
+    base: Option<time::SinceStart>,
+    /// Index of the site expanded, if any.
+    expanded: Option<usize>,
+}
+
+impl Snapshots {
+    /// Constructor.
+    pub fn new(link: Link) -> Self {
+        Self {
+            link,
+            query: None,
+            snapshot: None,
+            base: None,
+            expanded: None,
+        }
+    }
+
+    /// Sends a query to the server.
+    fn send(&mut self, query: Query) -> ShouldRender {
+        self.query = Some(query);
+        self.expanded = None;
+        self.link
+            .send_message(msg::Msg::ToServer(msg::to_server::Msg::snapshot(query)));
+        true
+    }
+
+    /// Handles a snapshot message.
+    pub fn update(&mut self, msg: Msg) -> Res<ShouldRender> {
+        let redraw = match msg {
+            Msg::Take(time) => {
+                let query = Query::new(time, self.base);
+                if self.query == Some(query) {
+                    false
+                } else {
+                    self.send(query)
+                }
+            }
+            Msg::SetBase => match self.query {
+                Some(query) => {
+                    let redraw = self.base != Some(query.time);
+                    self.base = Some(query.time);
+                    redraw
+                }
+                None => bail!("cannot set the base time of a closed snapshot view"),
+            },
+            Msg::ClearBase => match self.query {
+                Some(query) if query.base.is_some() => {
+                    self.base = None;
+                    self.send(Query::new(query.time, None))
+                }
+                _ => {
+                    let redraw = self.base.is_some();
+                    self.base = None;
+                    redraw
+                }
+            },
+            Msg::Expand(site) => {
+                self.expanded = if self.expanded == Some(site) {
+                    None
+                } else {
+                    Some(site)
+                };
+                true
+            }
+            Msg::Close => {
+                let redraw = self.query.is_some();
+                self.query = None;
+                self.snapshot = None;
+                self.expanded = None;
+                redraw
+            }
+        };
+        Ok(redraw)
+    }
+
+    /// Handles a snapshot from the server.
+    ///
+    /// Snapshots answering a query other than the last one sent are ignored.
+    pub fn server_update(&mut self, snapshot: Snapshot) -> ShouldRender {
+        if self.query != Some(snapshot.query) {
+            return false;
+        }
+        self.snapshot = Some(snapshot);
+        true
+    }
+
+    /// Renders the snapshot view.
+    pub fn render(&self) -> Html {
+        let query = if let Some(query) = self.query {
+            query
+        } else {
+            return html! {};
+        };
+
+        let title = match query.base {
+            Some(base) => format!("Heap diff between {} and {}", base, query.time),
+            None => format!("Heap snapshot at {}", query.time),
+        };
+
+        let nav = |label: String, msg: Msg| {
+            html! {
+                <span
+                    style = NAV_STYLE
+                    onclick = self.link.callback(move |_| msg.clone())
+                >
+                    {label}
+                </span>
+            }
+        };
+        let base = match self.base {
+            Some(base) if base == query.time => html! {
+                <span>{"base time set, take another snapshot to diff"}</span>
+            },
+            Some(base) => nav(format!("clear base ({})", base), Msg::ClearBase),
+            None => nav("use as base".into(), Msg::SetBase),
+        };
+
+        html! {
+            <div
+                style = CONTAINER_STYLE
+            >
+                {layout::section_title(&title)}
+                <center>
+                    {base}
+                    {nav("close".into(), Msg::Close)}
+                </center>
+                {self.render_snapshot(query)}
+            </div>
+        }
+    }
+
+    /// Renders the current snapshot, if it answers the current query.
+    fn render_snapshot(&self, query: Query) -> Html {
+        let snapshot = match self.snapshot.as_ref() {
+            Some(snapshot) if snapshot.query == query => snapshot,
+            _ => return html! { <center>{"loading..."}</center> },
+        };
+        let diff = snapshot.is_diff();
+
+        let columns = |live: &Live, base: &Live| {
+            if diff {
+                html! {
+                    <>
+                        <td>{format!("{:+}", live.delta_count(base))}</td>
+                        <td>{format!("{:+}B", live.delta_bytes(base))}</td>
+                        <td>{format!("{}B", base.bytes)}</td>
+                        <td>{format!("{}B", live.bytes)}</td>
+                    </>
+                }
+            } else {
+                html! {
+                    <>
+                        <td>{live.count}</td>
+                        <td>{format!("{}B", live.bytes)}</td>
+                    </>
+                }
+            }
+        };
+
+        let rows = snapshot.sites.iter().enumerate().map(|(index, site)| {
+            let expanded = self.expanded == Some(index);
+            let style: &str = if expanded {
+                &EXPANDED_ROW_STYLE
+            } else {
+                &ROW_STYLE
+            };
+            html! {
+                <>
+                    <tr
+                        style = style
+                        onclick = self.link.callback(move |_| Msg::Expand(index))
+                    >
+                        <td
+                            style = SITE_STYLE
+                        >
+                            {site.site.as_ref().map(String::as_str).unwrap_or("<unknown>")}
+                        </td>
+                        {columns(&site.live, &site.base)}
+                        <td>{site.stack_count}</td>
+                    </tr>
+                    {
+                        if expanded {
+                            self.render_stacks(site, diff)
+                        } else {
+                            html! {}
+                        }
+                    }
+                </>
+            }
+        });
+
+        let header = if diff {
+            html! {
+                <>
+                    <td>{"count delta"}</td>
+                    <td>{"size delta"}</td>
+                    <td>{"size before"}</td>
+                    <td>{"size after"}</td>
+                </>
+            }
+        } else {
+            html! {
+                <>
+                    <td>{"count"}</td>
+                    <td>{"size"}</td>
+                </>
+            }
+        };
+
+        let summary = if diff {
+            format!(
+                "{:+} allocations, {:+}B over {} sites (top {} shown)",
+                snapshot.live.delta_count(&snapshot.base),
+                snapshot.live.delta_bytes(&snapshot.base),
+                snapshot.site_count,
+                snapshot.sites.len(),
+            )
+        } else {
+            format!(
+                "{} allocations, {}B over {} sites (top {} shown)",
+                snapshot.live.count,
+                snapshot.live.bytes,
+                snapshot.site_count,
+                snapshot.sites.len(),
+            )
+        };
+
+        html! {
+            <>
+                <center>{summary}</center>
+                <table
+                    style = TABLE_STYLE
+                >
+                    <tr
+                        style = HEADER_STYLE
+                    >
+                        <td>{"allocation site"}</td>
+                        {header}
+                        <td>{"callstacks"}</td>
+                    </tr>
+                    {for rows}
+                </table>
+            </>
+        }
+    }
+
+    /// Renders the callstacks of a site, as an extra table row.
+    fn render_stacks(&self, site: &Site, diff: bool) -> Html {
+        let stacks = site.stacks.iter().map(|stack| {
+            let size = if diff {
+                format!(
+                    "{:+} allocations, {:+}B",
+                    stack.live.delta_count(&stack.base),
+                    stack.live.delta_bytes(&stack.base),
+                )
+            } else {
+                format!("{} allocations, {}B", stack.live.count, stack.live.bytes)
+            };
+            html! {
+                <div
+                    style = TRACE_STYLE
+                >
+                    <div
+                        style = HEADER_STYLE
+                    >
+                        {size}
+                    </div>
+                    { for stack.trace.iter().map(|loc| html! { <div>{loc}</div> }) }
+                </div>
+            }
+        });
+        html! {
+            <tr>
+                <td
+                    colspan = if diff { 6 } else { 4 }
+                >
+                    {for stacks}
+                </td>
+            </tr>
+        }
+    }
+}
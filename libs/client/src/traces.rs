@@ -0,0 +1,140 @@
+/*<LICENSE>
+    This file is part of Memthol.
+
+    Copyright (C) 2020 OCamlPro.
+
+    Memthol is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Memthol is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Memthol.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Trace directory browser.
+//!
+//! Lists the traces of the trace directory the server runs on, if any, and lets users switch the
+//! trace currently loaded.
+
+prelude! {}
+
+define_style! {
+    TABLE_STYLE = {
+        width(100%),
+        text_align(center),
+    };
+    HEADER_STYLE = {
+        bold,
+    };
+    ROW_STYLE = {
+        pointer,
+    };
+    ACTIVE_ROW_STYLE = {
+        bold,
+        fg({"#c8f5fd"}),
+        bg({layout::DARK_GREY_BG}),
+    };
+}
+
+/// Traces of the trace directory.
+pub struct Traces {
+    /// Link to the model.
+    link: Link,
+    /// Traces of the trace directory, empty if the server does not run on a trace directory.
+    traces: Vec<TraceInfo>,
+    /// Name of the trace currently loaded.
+    active: Option<String>,
+}
+
+impl Traces {
+    /// Constructor.
+    pub fn new(link: Link) -> Self {
+        Self {
+            link,
+            traces: vec![],
+            active: None,
+        }
+    }
+
+    /// True if there are no traces to browse.
+    pub fn is_empty(&self) -> bool {
+        self.traces.is_empty()
+    }
+
+    /// Handles a trace list update from the server.
+    pub fn server_update(&mut self, traces: Vec<TraceInfo>, active: Option<String>) -> ShouldRender {
+        let redraw = self.traces != traces || self.active != active;
+        self.traces = traces;
+        self.active = active;
+        redraw
+    }
+
+    /// Renders the traces.
+    pub fn render(&self) -> Html {
+        static LOCAL: time::chrono::Local = time::chrono::Local;
+
+        if self.is_empty() {
+            return html! {};
+        }
+
+        let rows = self.traces.iter().map(|info| {
+            let start = info.start_date.date().with_timezone(&LOCAL);
+            let is_active = self.active.as_ref() == Some(&info.file);
+            let style: &str = if is_active {
+                &*ACTIVE_ROW_STYLE
+            } else {
+                &*ROW_STYLE
+            };
+            let onclick = if is_active {
+                self.link.callback(|_| Msg::Noop)
+            } else {
+                let file = info.file.clone();
+                self.link.callback(move |_| {
+                    Msg::ToServer(msg::to_server::Msg::load_trace(file.clone()))
+                })
+            };
+            html! {
+                <tr
+                    style = style
+                    onclick = onclick
+                >
+                    <td>{&info.file}</td>
+                    <td>{&info.exe}</td>
+                    <td>{format!(
+                        "{} {}",
+                        start.date().naive_local(),
+                        start.time().format("%H:%M:%S"),
+                    )}</td>
+                    <td>{info.duration}</td>
+                    <td>{num_fmt::str_do(info.alloc_count as f64, identity)}</td>
+                </tr>
+            }
+        });
+
+        html! {
+            <>
+                {layout::section_title("Traces")}
+                <table
+                    style = TABLE_STYLE
+                >
+                    <tr
+                        style = HEADER_STYLE
+                    >
+                        <td>{"file"}</td>
+                        <td>{"executable"}</td>
+                        <td>{"date (LT)"}</td>
+                        <td>{"duration"}</td>
+                        <td>{"allocations"}</td>
+                    </tr>
+                    {for rows}
+                </table>
+            </>
+        }
+    }
+}
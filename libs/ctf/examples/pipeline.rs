@@ -0,0 +1,129 @@
+/*<LICENSE>
+    This file is part of Memthol.
+
+    Copyright (C) 2020 OCamlPro.
+
+    Memthol is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Memthol is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Memthol.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Compares the running time of `ctf::parse` and `ctf::pipelined_parse` on a trace.
+//!
+//! Usage: `cargo run --release --example pipeline -- <ctf file> [<runs>]`.
+
+use ctf::prelude::*;
+
+fn main() {
+    match run() {
+        Ok(()) => std::process::exit(0),
+        Err(e) => {
+            eprintln!("|===| Error");
+            for e in e.iter() {
+                let mut pref = "| - ";
+                for line in e.to_string().lines() {
+                    eprintln!("{}{}", pref, line);
+                    pref = "|   "
+                }
+            }
+            eprintln!("|===|");
+            std::process::exit(2)
+        }
+    }
+}
+
+fn run() -> Res<()> {
+    let (path, runs) = get_args()?;
+
+    println!("running on {:?}, {} run(s) per parser", path, runs);
+
+    let data = ctf::compress::read_file(&path, |_| ())?;
+
+    let mut sequential = std::time::Duration::from_secs(0);
+    let mut pipelined = std::time::Duration::from_secs(0);
+    for _ in 0..runs {
+        sequential += time_parse(&data, false)?;
+        pipelined += time_parse(&data, true)?;
+    }
+
+    println!("{:>15}: {:?} on average", "parse", sequential / runs);
+    println!(
+        "{:>15}: {:?} on average",
+        "pipelined_parse",
+        pipelined / runs
+    );
+
+    Ok(())
+}
+
+/// Parses some data, and returns the time it took.
+///
+/// The actions only count the allocations and deaths, so that what is measured is mostly decoding
+/// and trace building.
+fn time_parse(data: &[u8], pipelined: bool) -> Res<std::time::Duration> {
+    let mut factory = Box::new(alloc_data::mem::Factory::new(false));
+    let (mut new_count, mut dead_count) = (0usize, 0usize);
+
+    let start = std::time::Instant::now();
+    if pipelined {
+        ctf::pipelined_parse(
+            data,
+            &mut factory,
+            |_| (),
+            |_, _| (),
+            |_, _| new_count += 1,
+            |_, _, _| dead_count += 1,
+            |_, _| (),
+        )?;
+    } else {
+        ctf::parse(
+            data,
+            &mut factory,
+            |_| (),
+            |_, _| (),
+            |_, _| new_count += 1,
+            |_, _, _| dead_count += 1,
+            |_, _| (),
+        )?;
+    }
+    let elapsed = start.elapsed();
+
+    println!(
+        "{:>15}: {:?} ({} allocations, {} deaths)",
+        if pipelined {
+            "pipelined_parse"
+        } else {
+            "parse"
+        },
+        elapsed,
+        new_count,
+        dead_count,
+    );
+    Ok(elapsed)
+}
+
+fn get_args() -> Res<(String, u32)> {
+    let mut args = std::env::args();
+    args.next();
+    let path = args
+        .next()
+        .ok_or_else(|| err::Error::from("expected file path as argument, found nothing"))?;
+    let runs = match args.next() {
+        Some(runs) => runs
+            .parse::<u32>()
+            .ok()
+            .filter(|runs| *runs > 0)
+            .ok_or_else(|| format!("expected a positive number of runs, found `{}`", runs))?,
+        None => 3,
+    };
+    Ok((path, runs))
+}
@@ -25,6 +25,7 @@ prelude! {}
 const CACHE_SIZE: usize = 1 << 14;
 
 /// Backtrace parsing context.
+#[derive(Clone)]
 pub struct Cxt {
     /// Location cache.
     cache_loc: Vec<usize>,
@@ -102,6 +103,31 @@ impl Cxt {
         nencoded: usize,
         common_pref_len: usize,
     ) -> Res<Vec<usize>> {
+        let len = self.decode(parser, nencoded, common_pref_len)?;
+        Ok(self.last_backtrace[0..len].to_vec())
+    }
+
+    /// Decodes a backtrace at the current position in the input parser, only updates the caches.
+    ///
+    /// Same as [`get_backtrace`] without building the backtrace.
+    ///
+    /// [`get_backtrace`]: #method.get_backtrace (The get_backtrace method)
+    pub fn skim_backtrace<'data>(
+        &mut self,
+        parser: &mut impl CanParse<'data>,
+        nencoded: usize,
+        common_pref_len: usize,
+    ) -> Res<()> {
+        self.decode(parser, nencoded, common_pref_len).map(|_| ())
+    }
+
+    /// Decodes a backtrace in `last_backtrace`, returns its length.
+    fn decode<'data>(
+        &mut self,
+        parser: &mut impl CanParse<'data>,
+        nencoded: usize,
+        common_pref_len: usize,
+    ) -> Res<usize> {
         assert!(common_pref_len <= self.last_backtrace.len());
 
         let Self {
@@ -119,7 +145,7 @@ impl Cxt {
 
         let res = 'decode: loop {
             if decode_current == 0 {
-                break 'decode buf[0..pos].iter().cloned().collect();
+                break 'decode pos;
             }
 
             let codeword = parser.u16()?;
@@ -189,9 +215,20 @@ impl Cxt {
 
     /// Parses cache-verification data and checks the internal cache is consistent with it.
     pub fn check_cache_verifier<'data>(&self, parser: &mut impl CanParse<'data>) -> Res<()> {
-        let ix: usize = convert(parser.u16()?, "check_cache_verifier: ix");
-        let pred = parser.u16()? as usize;
+        let ix = parser.u16()?;
+        let pred = parser.u16()?;
         let value = parser.u64()?;
+        self.check_cache(&ast::CacheCheck { ix, pred, value })
+    }
+
+    /// Checks the internal cache is consistent with some cache-verification data.
+    ///
+    /// Packet headers carry cache-verification data describing the state of the cache at the
+    /// beginning of the packet.
+    pub fn check_cache(&self, check: &ast::CacheCheck) -> Res<()> {
+        let ix: usize = convert(check.ix, "check_cache: ix");
+        let pred = check.pred as usize;
+        let value = check.value;
 
         macro_rules! error {
             ($($blah:tt)*) => {
@@ -220,7 +257,7 @@ impl Cxt {
                 ix, self.cache_pred[ix], pred,
             )
         }
-        if self.cache_loc[ix] != convert(value, "check_cache_verifier: value") {
+        if self.cache_loc[ix] != convert(value, "check_cache: value") {
             error!(
                 "expected cache_loc[ix] == value, got cache_loc[{}] = {} != {}",
                 ix, self.cache_loc[ix], value,
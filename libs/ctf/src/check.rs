@@ -0,0 +1,432 @@
+/*<LICENSE>
+    This file is part of Memthol.
+
+    Copyright (C) 2020 OCamlPro.
+
+    Memthol is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Memthol is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Memthol.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Validation of memtrace CTF files.
+//!
+//! The [`check`] function walks a whole trace and reports all the problems it finds, with their
+//! byte offsets, instead of stopping at the first one like [`parse`] does.
+//!
+//! [`check`]: fn.check.html (The check function)
+//! [`parse`]: ../fn.parse.html (The parse function)
+
+prelude! {}
+
+/// Maximum number of problems stored in a report, further problems are only counted.
+pub const MAX_PROBLEMS: usize = 1_000;
+
+/// A problem in a trace.
+#[derive(Debug, Clone)]
+pub struct Problem {
+    /// Offset of the problem in the trace, in bytes.
+    pub offset: usize,
+    /// Index of the packet the problem is in, if any.
+    pub packet: Option<usize>,
+    /// Description of the problem.
+    pub desc: String,
+}
+
+base::implement! {
+    impl Display for Problem {
+        |&self, fmt| {
+            write!(fmt, "@{:>10}", self.offset)?;
+            if let Some(packet) = self.packet {
+                write!(fmt, " packet {:>6}", packet)?
+            } else {
+                write!(fmt, " {:>13}", "")?
+            }
+            write!(fmt, " | {}", self.desc)
+        }
+    }
+}
+
+/// Result of checking a trace.
+#[derive(Debug, Clone)]
+pub struct Report {
+    /// Length of the trace in bytes.
+    pub len: usize,
+    /// Number of packets found.
+    pub packet_count: usize,
+    /// Number of events parsed.
+    pub event_count: usize,
+    /// Number of allocations parsed.
+    pub alloc_count: usize,
+    /// Number of collections parsed.
+    pub collection_count: usize,
+    /// Length of the longest prefix of the trace that has no problem, in bytes.
+    ///
+    /// This prefix always ends at the end of a packet, meaning it is a legal trace on its own.
+    /// Equal to `len` if the trace has no problem. Zero if the header of the trace itself is
+    /// illegal.
+    pub valid_len: usize,
+    /// Problems found, at most [`MAX_PROBLEMS`].
+    ///
+    /// [`MAX_PROBLEMS`]: constant.MAX_PROBLEMS.html (The MAX_PROBLEMS constant)
+    pub problems: Vec<Problem>,
+    /// Number of problems found but not stored, see [`MAX_PROBLEMS`].
+    ///
+    /// [`MAX_PROBLEMS`]: constant.MAX_PROBLEMS.html (The MAX_PROBLEMS constant)
+    pub ignored_problems: usize,
+}
+
+impl Report {
+    /// Constructor.
+    fn new(len: usize) -> Self {
+        Self {
+            len,
+            packet_count: 0,
+            event_count: 0,
+            alloc_count: 0,
+            collection_count: 0,
+            valid_len: 0,
+            problems: vec![],
+            ignored_problems: 0,
+        }
+    }
+
+    /// True if no problem was found.
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty() && self.ignored_problems == 0
+    }
+
+    /// Total number of problems found.
+    pub fn problem_count(&self) -> usize {
+        self.problems.len() + self.ignored_problems
+    }
+
+    /// Registers a problem.
+    fn problem(&mut self, offset: usize, packet: Option<usize>, desc: impl Into<String>) {
+        if self.problems.len() < MAX_PROBLEMS {
+            self.problems.push(Problem {
+                offset,
+                packet,
+                desc: desc.into(),
+            })
+        } else {
+            self.ignored_problems += 1
+        }
+    }
+
+    /// Registers an error as a problem.
+    fn error(&mut self, offset: usize, packet: Option<usize>, e: err::Error) {
+        self.problem(offset, packet, error_desc(&e))
+    }
+}
+
+base::implement! {
+    impl Display for Report {
+        |&self, fmt| {
+            writeln!(fmt, "|===| Trace check")?;
+            writeln!(fmt, "| {:>11} bytes", self.len)?;
+            writeln!(fmt, "| {:>11} packet(s)", self.packet_count)?;
+            writeln!(fmt, "| {:>11} event(s)", self.event_count)?;
+            writeln!(fmt, "| {:>11} allocation(s)", self.alloc_count)?;
+            writeln!(fmt, "| {:>11} collection(s)", self.collection_count)?;
+            if self.is_ok() {
+                writeln!(fmt, "| no problem found")?;
+            } else {
+                writeln!(fmt, "|===| {} problem(s)", self.problem_count())?;
+                for problem in &self.problems {
+                    writeln!(fmt, "| {}", problem)?;
+                }
+                if self.ignored_problems > 0 {
+                    writeln!(fmt, "| ... and {} more", self.ignored_problems)?;
+                }
+                writeln!(
+                    fmt,
+                    "|===| first {} bytes of the trace have no problem",
+                    self.valid_len
+                )?;
+            }
+            write!(fmt, "|===|")
+        }
+    }
+}
+
+/// Single-line description of an error.
+///
+/// Multi-line errors in the chain are either context dumps, which are ignored, or descriptions
+/// preceded by context information, in which case only their last line is kept.
+fn error_desc(e: &err::Error) -> String {
+    let mut desc = String::new();
+    // Outermost error last, it is the one giving the most context.
+    for e in e.iter().collect::<Vec<_>>().into_iter().rev() {
+        let e = e.to_string();
+        let mut lines = e.lines();
+        let first = lines.next().unwrap_or("");
+        let line = match lines.last() {
+            None => first,
+            // Context dump.
+            Some(_) if first.trim_end().ends_with('{') => continue,
+            Some(last) => last,
+        };
+        if !desc.is_empty() {
+            desc.push_str(", ");
+        }
+        desc.push_str(line.trim())
+    }
+    desc
+}
+
+/// Checks a trace.
+///
+/// Checks
+///
+/// - the magic numbers and the headers of the trace and of its packets,
+/// - that packets and events are ordered by timestamp,
+/// - that the allocation UID spans of the packets follow each other and match the allocations of
+///   the packets,
+/// - the backtrace cache verification data of each packet,
+/// - that collections and promotions only concern live allocations, and that no allocation is
+///   collected twice.
+///
+/// When the events of a packet cannot be parsed, the rest of the packet is skipped. When a packet
+/// header cannot be parsed, the check resumes at the next magic number in the trace, unless the
+/// packet is the incomplete last packet of a truncated trace. Note that skipping events
+/// desynchronizes the location and backtrace caches, so one problem usually causes more problems
+/// down the line: the first problem is the one to look at.
+pub fn check(bytes: &[u8]) -> Report {
+    let mut report = Report::new(bytes.len());
+
+    let res = (|| -> Res<()> {
+        parse! {
+            bytes => |mut parser| {
+                check_packets(&mut parser, &mut report);
+                Ok(())
+            }
+        }
+    })();
+    if let Err(e) = res {
+        report.error(0, None, e.chain_err(|| "while parsing the trace header"))
+    }
+
+    report
+}
+
+/// Checks the packets of a trace.
+fn check_packets<'data, Endian>(parser: &mut parse::CtfParser<'data, Endian>, report: &mut Report)
+where
+    parse::Parser<'data, Endian>: CanParse<'data>,
+{
+    // Magic number starting the trace, and thus all packets.
+    let magic: Vec<u8> = parser.data().iter().take(4).cloned().collect();
+
+    // Timestamp of the last event/packet.
+    let mut last_time = parser.header().timestamp.lbound;
+    // Upper bound of the allocation UID span of the previous packet.
+    let mut last_alloc_ubound = None;
+    // Allocations alive, and allocations collected.
+    let (mut live, mut dead) = (HSet::new(), HSet::new());
+    // True if no problem was found so far.
+    let mut valid = true;
+
+    loop {
+        let start = *parser.pos();
+        let packet_id = report.packet_count;
+        let problem_count = report.problem_count();
+
+        let mut packet_parser = match parser.next_packet() {
+            Ok(Some(packet_parser)) => packet_parser,
+            Ok(None) => break,
+            Err(e) => {
+                report.error(start, Some(packet_id), e);
+                report.packet_count += 1;
+                valid = false;
+
+                if parser.at_truncated_packet() {
+                    report.problem(
+                        start,
+                        Some(packet_id),
+                        "last packet is incomplete, the trace is truncated",
+                    );
+                    break;
+                }
+
+                // Look for the next packet.
+                let next = parser.data()[start + 1..]
+                    .windows(magic.len())
+                    .position(|bytes| bytes == &magic[..]);
+                if let Some(next) = next {
+                    report.problem(
+                        start,
+                        Some(packet_id),
+                        format!("skipping {} bytes to the next magic number", next + 1),
+                    );
+                    parser.take(next + 1);
+                    continue;
+                } else {
+                    break;
+                }
+            }
+        };
+        report.packet_count += 1;
+
+        let header = packet_parser.header().clone();
+
+        if header.timestamp.lbound > header.timestamp.ubound {
+            report.problem(
+                start,
+                Some(packet_id),
+                format!("illegal packet time span {}", header.timestamp),
+            )
+        }
+        if header.timestamp.lbound < last_time {
+            report.problem(
+                start,
+                Some(packet_id),
+                format!(
+                    "packet starts at {}, before the previous packet/event ({})",
+                    header.timestamp.lbound, last_time,
+                ),
+            )
+        }
+        if header.alloc_id.lbound > header.alloc_id.ubound {
+            report.problem(
+                start,
+                Some(packet_id),
+                format!("illegal packet allocation UID span {}", header.alloc_id),
+            )
+        }
+        if let Some(last) = last_alloc_ubound {
+            if header.alloc_id.lbound != last {
+                report.problem(
+                    start,
+                    Some(packet_id),
+                    format!(
+                        "packet allocation UIDs start at {}, expected {} from the previous packet",
+                        header.alloc_id.lbound, last,
+                    ),
+                )
+            }
+        }
+        last_alloc_ubound = Some(header.alloc_id.ubound);
+        last_time = std::cmp::max(last_time, header.timestamp.lbound);
+
+        if let Err(e) = packet_parser.check_cache() {
+            report.error(start, Some(packet_id), e)
+        }
+
+        let mut alloc_count = 0;
+        // Offset of the first allocation with a UID outside of the packet's span, and number of
+        // such allocations.
+        let mut outside_span = None;
+        loop {
+            let offset = packet_parser.real_position().0;
+            let (clock, event) = match packet_parser.next_event() {
+                Ok(Some(event)) => event,
+                Ok(None) => break,
+                Err(e) => {
+                    report.error(offset, Some(packet_id), e);
+                    report.problem(offset, Some(packet_id), "skipping the rest of the packet");
+                    break;
+                }
+            };
+            report.event_count += 1;
+
+            if !header.timestamp.contains(clock) {
+                // Not used as the new reference for event ordering, it would cause problems for
+                // all the events that follow.
+                report.problem(
+                    offset,
+                    Some(packet_id),
+                    format!(
+                        "event timestamp {} is outside the packet time span {}",
+                        clock, header.timestamp,
+                    ),
+                )
+            } else if clock < last_time {
+                report.problem(
+                    offset,
+                    Some(packet_id),
+                    format!(
+                        "event timestamp {} is before the previous event ({})",
+                        clock, last_time,
+                    ),
+                )
+            } else {
+                last_time = clock
+            }
+
+            use ast::event::Event;
+            match event {
+                Event::Alloc(alloc) => {
+                    report.alloc_count += 1;
+                    alloc_count += 1;
+                    if alloc.id < header.alloc_id.lbound || alloc.id >= header.alloc_id.ubound {
+                        let (_, count) = outside_span.get_or_insert((offset, 0));
+                        *count += 1
+                    }
+                    live.insert(alloc.id);
+                }
+                Event::Collection(uid) => {
+                    report.collection_count += 1;
+                    if !live.remove(&uid) {
+                        let desc = if dead.contains(&uid) {
+                            format!("allocation {} is collected twice", uid)
+                        } else {
+                            format!("collection of unknown allocation {}", uid)
+                        };
+                        report.problem(offset, Some(packet_id), desc)
+                    }
+                    dead.insert(uid);
+                }
+                Event::Promotion(uid) => {
+                    if !live.contains(&uid) {
+                        report.problem(
+                            offset,
+                            Some(packet_id),
+                            format!("promotion of dead or unknown allocation {}", uid),
+                        )
+                    }
+                }
+                Event::Locs(_) => (),
+            }
+        }
+
+        if let Some((offset, count)) = outside_span {
+            report.problem(
+                offset,
+                Some(packet_id),
+                format!(
+                    "{} allocation UID(s) outside the packet allocation UID span {}",
+                    count, header.alloc_id,
+                ),
+            )
+        }
+
+        let expected = header
+            .alloc_id
+            .ubound
+            .saturating_sub(header.alloc_id.lbound);
+        if alloc_count != expected {
+            report.problem(
+                start,
+                Some(packet_id),
+                format!(
+                    "packet has {} allocation(s), expected {} from its allocation UID span {}",
+                    alloc_count, expected, header.alloc_id,
+                ),
+            )
+        }
+
+        valid = valid && report.problem_count() == problem_count;
+        if valid {
+            report.valid_len = *parser.pos()
+        }
+    }
+}
@@ -0,0 +1,341 @@
+/*<LICENSE>
+    This file is part of Memthol.
+
+    Copyright (C) 2020 OCamlPro.
+
+    Memthol is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Memthol is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Memthol.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Handles compressed memtrace CTF files.
+//!
+//! Compression is detected from the first bytes of a file (its *magic number*), not from its
+//! extension. Supported formats are gzip and zstd.
+
+prelude! {}
+
+use std::{io::Read, path::Path};
+
+/// Gzip magic number.
+pub const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+/// Zstd magic number.
+pub const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Extensions of CTF files, compressed or not.
+pub const EXTENSIONS: [&str; 3] = ["ctf", "ctf.gz", "ctf.zst"];
+
+/// Size of the chunks read when decompressing.
+const CHUNK_SIZE: usize = 1 << 16;
+
+/// Compression format of a CTF file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// Not compressed.
+    None,
+    /// Gzip compression.
+    Gzip,
+    /// Zstd compression.
+    Zstd,
+}
+
+base::implement! {
+    impl Display for Compression {
+        |&self, fmt| match self {
+            Self::None => "uncompressed".fmt(fmt),
+            Self::Gzip => "gzip".fmt(fmt),
+            Self::Zstd => "zstd".fmt(fmt),
+        }
+    }
+}
+
+impl Compression {
+    /// Detects the compression format from the first bytes of some data.
+    ///
+    /// ```rust
+    /// use ctf::compress::Compression;
+    /// assert_eq!(Compression::detect(&[0x1f, 0x8b, 0x08]), Compression::Gzip);
+    /// assert_eq!(Compression::detect(&[0x28, 0xb5, 0x2f, 0xfd, 0]), Compression::Zstd);
+    /// assert_eq!(Compression::detect(&[0xc1, 0x1f, 0xfc, 0xc1]), Compression::None);
+    /// assert_eq!(Compression::detect(&[0x1f]), Compression::None);
+    /// ```
+    pub fn detect(bytes: &[u8]) -> Self {
+        if bytes.starts_with(&GZIP_MAGIC) {
+            Self::Gzip
+        } else if bytes.starts_with(&ZSTD_MAGIC) {
+            Self::Zstd
+        } else {
+            Self::None
+        }
+    }
+
+    /// Detects the compression format of a file.
+    pub fn of_file(path: impl AsRef<Path>) -> Res<Self> {
+        let path = path.as_ref();
+        let mut magic = [0; 4];
+        let mut len = 0;
+        let mut file = std::fs::File::open(path)
+            .chain_err(|| format!("while opening `{}`", path.display()))?;
+        while len < magic.len() {
+            let read = file
+                .read(&mut magic[len..])
+                .chain_err(|| format!("while reading `{}`", path.display()))?;
+            if read == 0 {
+                break;
+            }
+            len += read
+        }
+        Ok(Self::detect(&magic[..len]))
+    }
+}
+
+/// True if a path has a CTF extension, see [`EXTENSIONS`].
+///
+/// ```rust
+/// assert!(ctf::compress::has_ctf_extension("dir/trace.ctf"));
+/// assert!(ctf::compress::has_ctf_extension("dir/trace.ctf.gz"));
+/// assert!(ctf::compress::has_ctf_extension("trace.ctf.zst"));
+/// assert!(!ctf::compress::has_ctf_extension("trace.ctf.cache"));
+/// assert!(!ctf::compress::has_ctf_extension("trace.gz"));
+/// ```
+///
+/// [`EXTENSIONS`]: constant.EXTENSIONS.html (The EXTENSIONS constant)
+pub fn has_ctf_extension(path: impl AsRef<Path>) -> bool {
+    path.as_ref()
+        .file_name()
+        .map(|name| {
+            let name = name.to_string_lossy();
+            EXTENSIONS
+                .iter()
+                .any(|ext| name.ends_with(&format!(".{}", ext)))
+        })
+        .unwrap_or(false)
+}
+
+/// Wraps a reader and reports the number of bytes read so far.
+struct Progress<R, F> {
+    /// Underlying reader.
+    reader: R,
+    /// Number of bytes read so far.
+    count: usize,
+    /// Called on the number of bytes read so far after each read.
+    progress: F,
+}
+impl<R: Read, F: FnMut(usize)> Read for Progress<R, F> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.reader.read(buf)?;
+        self.count += read;
+        (self.progress)(self.count);
+        Ok(read)
+    }
+}
+
+/// Decompressing reader over some bytes, given their compression format.
+fn decoder<'a>(
+    reader: impl std::io::BufRead + 'a,
+    compression: Compression,
+) -> Res<Box<dyn Read + 'a>> {
+    let decoder: Box<dyn Read + 'a> = match compression {
+        Compression::None => Box::new(reader),
+        Compression::Gzip => Box::new(flate2::bufread::MultiGzDecoder::new(reader)),
+        Compression::Zstd => Box::new(
+            zstd::stream::read::Decoder::with_buffer(reader)
+                .chain_err(|| format!("while reading {} data", compression))?,
+        ),
+    };
+    Ok(decoder)
+}
+
+/// Copies the bytes of a decompressing reader to a writer, returns the number of bytes copied.
+///
+/// Compressed traces that are cut short, typically because the program writing them was killed or
+/// is still running, cannot be decompressed entirely. Decompression then stops with a warning and
+/// keeps the bytes decompressed so far, the parser ignores the incomplete packet they end with.
+fn copy(
+    reader: &mut impl Read,
+    compression: Compression,
+    writer: &mut impl std::io::Write,
+) -> Res<u64> {
+    let mut buf = vec![0; CHUNK_SIZE];
+    let mut count = 0;
+    loop {
+        let read = match reader.read(&mut buf) {
+            Ok(0) => return Ok(count),
+            Ok(read) => read,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) if compression != Compression::None => {
+                log::warn!(
+                    "{} data is incomplete, keeping the {} bytes decompressed so far: {}",
+                    compression,
+                    count,
+                    e
+                );
+                return Ok(count);
+            }
+            Err(e) => return Err(e).chain_err(|| format!("while reading {} data", compression)),
+        };
+        writer.write_all(&buf[..read])?;
+        count += read as u64
+    }
+}
+
+/// Reads all the bytes of a reader, decompressing them if needed.
+///
+/// The compression format is detected from the first bytes read. Decompression is streamed:
+/// compressed bytes are never all in memory. Compressed data that is cut short yields the bytes
+/// decompressed before the cut. `progress` is called regularly on the number of (compressed) bytes
+/// read so far.
+pub fn read_all(reader: impl Read, progress: impl FnMut(usize)) -> Res<Vec<u8>> {
+    let mut reader = std::io::BufReader::with_capacity(
+        CHUNK_SIZE,
+        Progress {
+            reader,
+            count: 0,
+            progress,
+        },
+    );
+    let compression = {
+        use std::io::BufRead;
+        let magic = reader
+            .fill_buf()
+            .chain_err(|| "while reading the magic number")?;
+        Compression::detect(magic)
+    };
+
+    let mut bytes = Vec::with_capacity(CHUNK_SIZE);
+    copy(&mut decoder(reader, compression)?, compression, &mut bytes)?;
+
+    Ok(bytes)
+}
+
+/// Reads a CTF file, decompressing it if needed.
+///
+/// See [`read_all`] for details, `progress` is called on the number of bytes of the file read so
+/// far.
+///
+/// [`read_all`]: fn.read_all.html (The read_all function)
+pub fn read_file(path: impl AsRef<Path>, progress: impl FnMut(usize)) -> Res<Vec<u8>> {
+    let path = path.as_ref();
+    let file =
+        std::fs::File::open(path).chain_err(|| format!("while opening `{}`", path.display()))?;
+    read_all(file, progress).chain_err(|| format!("while reading `{}`", path.display()))
+}
+
+/// Reads the (decompressed) bytes of a CTF file that start at some offset.
+///
+/// Reads at most `max_len` bytes if any. Returns `None` if the decompressed trace is shorter than
+/// `offset`. Compressed data that is cut short is handled as in [`read_all`]. Uncompressed files are read from `offset` directly, compressed ones are decompressed
+/// from the start and the bytes before `offset` are dropped as they are decompressed.
+///
+/// [`read_all`]: fn.read_all.html (The read_all function)
+pub fn read_file_from(
+    path: impl AsRef<Path>,
+    offset: usize,
+    max_len: Option<usize>,
+) -> Res<Option<Vec<u8>>> {
+    use std::io::{Seek, SeekFrom};
+    let path = path.as_ref();
+    let res = || -> Res<Option<Vec<u8>>> {
+        let compression = Compression::of_file(path)?;
+        let mut file = std::fs::File::open(path)?;
+        let reader = if compression == Compression::None {
+            if file.metadata()?.len() < offset as u64 {
+                return Ok(None);
+            }
+            file.seek(SeekFrom::Start(offset as u64))?;
+            decoder(
+                std::io::BufReader::with_capacity(CHUNK_SIZE, file),
+                compression,
+            )?
+        } else {
+            let mut reader = decoder(
+                std::io::BufReader::with_capacity(CHUNK_SIZE, file),
+                compression,
+            )?;
+            let skipped = copy(
+                &mut reader.by_ref().take(offset as u64),
+                compression,
+                &mut std::io::sink(),
+            )?;
+            if skipped < offset as u64 {
+                return Ok(None);
+            }
+            reader
+        };
+        let mut bytes = vec![];
+        let max_len = max_len.map(|max_len| max_len as u64).unwrap_or(u64::MAX);
+        copy(&mut reader.take(max_len), compression, &mut bytes)?;
+        Ok(Some(bytes))
+    };
+    res().chain_err(|| format!("while reading `{}`", path.display()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A small trace.
+    const TRACE: &[u8] = include_bytes!("../rsc/lists_and_sets.ctf");
+
+    /// Parses some bytes, returns the number of allocations and the truncation if any.
+    fn parse(bytes: &[u8]) -> (usize, Option<crate::Truncation>) {
+        let mut factory = Box::new(alloc_data::mem::Factory::new(false));
+        let mut count = 0;
+        let (truncation, _) = crate::parse(
+            bytes,
+            &mut factory,
+            |_| (),
+            |_, _| (),
+            |_, _| count += 1,
+            |_, _, _| (),
+            |_, _| (),
+        )
+        .unwrap();
+        (count, truncation)
+    }
+
+    /// Checks that a compressed trace cut short yields a prefix of the trace.
+    fn check_cut(compressed: Vec<u8>) {
+        let (full_count, _) = parse(TRACE);
+        assert_eq!(read_all(&compressed[..], |_| ()).unwrap(), TRACE);
+
+        let cut = &compressed[..compressed.len() * 2 / 3];
+        let bytes = read_all(cut, |_| ()).unwrap();
+        assert!(!bytes.is_empty());
+        assert!(bytes.len() < TRACE.len());
+        assert!(TRACE.starts_with(&bytes));
+
+        let (count, truncation) = parse(&bytes);
+        assert!(truncation.is_some());
+        assert!(count < full_count);
+    }
+
+    #[test]
+    fn gzip_cut_short() {
+        use std::io::Write;
+        let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+        encoder.write_all(TRACE).unwrap();
+        check_cut(encoder.finish().unwrap())
+    }
+
+    #[test]
+    fn zstd_cut_short() {
+        use std::io::Write;
+        // Zstd only decompresses whole blocks, flush regularly to have more than one.
+        let mut encoder = zstd::stream::write::Encoder::new(vec![], 0).unwrap();
+        for chunk in TRACE.chunks(1 << 10) {
+            encoder.write_all(chunk).unwrap();
+            encoder.flush().unwrap()
+        }
+        check_cut(encoder.finish().unwrap())
+    }
+}
@@ -34,6 +34,8 @@ pub mod prelude;
 
 pub mod ast;
 pub mod btrace;
+pub mod check;
+pub mod compress;
 pub mod loc;
 pub mod parse;
 
@@ -58,11 +60,162 @@ impl<'data, T> EventAction<'data> for T where
 {
 }
 
-pub use diff_parse::parse;
+pub use diff_parse::{
+    parse, parse_appended, parse_resumed, pipelined_parse, Resume, PACKET_BUFFER_COUNT,
+};
+
+/// Information about a trace that ends with an incomplete packet.
+///
+/// See [`parse`] for details.
+///
+/// [`parse`]: fn.parse.html (The parse function)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Truncation {
+    /// Offset of the incomplete packet, *i.e.* length of the part of the trace that was parsed.
+    pub offset: usize,
+    /// Length of the trace.
+    pub len: usize,
+}
+impl Truncation {
+    /// Turns a packet-parsing error into a truncation if the rest of the input is an incomplete
+    /// packet, returns the error otherwise.
+    fn of_error<'data, Endian>(
+        parser: &mut parse::CtfParser<'data, Endian>,
+        e: err::Error,
+    ) -> Res<Self>
+    where
+        parse::Parser<'data, Endian>: CanParse<'data>,
+    {
+        if parser.at_truncated_packet() {
+            // The parser may only have the end of the trace, see `parse::CtfParser::resume`.
+            let (offset, _) = parser.real_position();
+            let len = offset + (parser.data().len() - *parser.pos());
+            Ok(Self { offset, len })
+        } else {
+            Err(e)
+        }
+    }
+}
+base::implement! {
+    impl Display for Truncation {
+        |&self, fmt| write!(
+            fmt,
+            "trace is truncated, ignoring the incomplete packet at byte {} (of {})",
+            self.offset, self.len,
+        )
+    }
+}
+
+/// Summary of a memtrace CTF dump.
+///
+/// Produced by [`summary`], which only parses the header of each packet.
+///
+/// [`summary`]: fn.summary.html (The summary function)
+#[derive(Debug, Clone)]
+pub struct Summary {
+    /// Name of the executable.
+    pub exe_name: String,
+    /// PID of the process.
+    pub pid: Pid,
+    /// Start time of the run.
+    pub start_time: time::Date,
+    /// Duration of the run.
+    pub duration: time::SinceStart,
+    /// Number of (sampled) allocations in the dump.
+    pub alloc_count: u64,
+}
+
+/// Computes the summary of a CTF dump.
+///
+/// Packets are skipped without parsing their events, which is much cheaper than [`parse`]. Like
+/// [`parse`], ignores the last packet of a truncated trace.
+///
+/// [`parse`]: fn.parse.html (The parse function)
+pub fn summary(bytes: &[u8]) -> Res<Summary> {
+    parse! {
+        bytes => |mut parser| {
+            let start = parser.header().timestamp.lbound;
+            let mut end = parser.header().timestamp.ubound;
+            let mut alloc_count = 0;
+            let exe_name = parser.trace_info().exe_name.clone();
+            let pid = parser.trace_info().pid;
+
+            loop {
+                let error = match parser.next_packet() {
+                    Ok(Some(packet_parser)) => {
+                        let header = packet_parser.header();
+                        end = std::cmp::max(end, header.timestamp.ubound);
+                        alloc_count = std::cmp::max(alloc_count, header.alloc_id.ubound);
+                        continue;
+                    }
+                    Ok(None) => break,
+                    Err(e) => e,
+                };
+                // Summarize truncated traces up to their last complete packet.
+                Truncation::of_error(&mut parser, error)?;
+                break;
+            }
+
+            let start_time = time::Date::from_micros(convert(start, "summary: start"));
+            let end_time = time::Date::from_micros(convert(end, "summary: end"));
+            Ok(Summary {
+                exe_name,
+                pid,
+                start_time,
+                duration: end_time - start_time,
+                alloc_count,
+            })
+        }
+    }
+}
+
+/// Identifies the run that produced a memtrace CTF dump.
+///
+/// Two dumps with the same run identifier are the same trace, possibly at different stages of
+/// being written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RunId {
+    /// PID of the process.
+    pub pid: Pid,
+    /// Start time of the run.
+    pub start: Clock,
+}
+
+/// Retrieves the run identifier of a CTF dump, only parses the trace header.
+pub fn run_id(bytes: &[u8]) -> Res<RunId> {
+    parse! {
+        bytes => |parser| Ok(RunId {
+            pid: parser.trace_info().pid,
+            start: parser.header().timestamp.lbound,
+        })
+    }
+}
+
+/// Retrieves the run identifier of a CTF file, only reads and parses the trace header.
+pub fn run_id_of_file(path: impl AsRef<std::path::Path>) -> Res<RunId> {
+    let path = path.as_ref();
+    // The header of the trace is followed by the trace info, which has no maximal length.
+    let mut len = 1 << 12;
+    loop {
+        let head = compress::read_file_from(path, 0, Some(len))?.unwrap_or_default();
+        match run_id(&head) {
+            Ok(run) => return Ok(run),
+            Err(e) if head.len() < len => {
+                return Err(e).chain_err(|| format!("while parsing `{}`", path.display()))
+            }
+            Err(_) => len *= 4,
+        }
+    }
+}
 
 mod diff_parse {
     use alloc_data::prelude::*;
 
+    use crate::{
+        parse::{CtfParser, Saved},
+        RunId, Truncation,
+    };
+
     /// Type of an encoded location.
     type EncodedLoc = u64;
     /// Maps encoded locations to vectors of locations.
@@ -193,8 +346,237 @@ mod diff_parse {
         time::Date::from_micros(convert(date, "date_from_microsecs"))
     }
 
+    /// State of the parser at the end of a trace.
+    ///
+    /// Produced by all the parsing functions, and used by [`parse_resumed`] to parse the packets
+    /// appended to the trace later on without decoding the packets before them again.
+    ///
+    /// [`parse_resumed`]: fn.parse_resumed.html (The parse_resumed function)
+    pub struct Resume {
+        /// State of the CTF parser.
+        parser: Saved,
+        /// Builds the traces of the allocations from the last trace built.
+        trace_builder: TraceBuilder,
+        /// Maps location encoded identifiers to actual locations.
+        loc_map: LocMap,
+    }
+    impl Resume {
+        /// Length of the part of the (decompressed) trace parsed.
+        ///
+        /// This is the offset of the incomplete packet if the trace is truncated, and the length of
+        /// the trace otherwise.
+        pub fn offset(&self) -> usize {
+            self.parser.offset()
+        }
+        /// Identifier of the run the trace comes from.
+        pub fn run_id(&self) -> RunId {
+            RunId {
+                pid: self.parser.trace_info().pid,
+                start: self.parser.header().timestamp.lbound,
+            }
+        }
+    }
+
+    /// Where parsing starts.
+    pub(crate) enum Start {
+        /// At the beginning of the trace, the packets starting before `replay` are replayed: they
+        /// are decoded and their traces are built, but the actions do not see them.
+        Beginning {
+            /// Offset of the first packet handed to the actions.
+            replay: usize,
+        },
+        /// After the last packet parsed by a previous parse, the input only has the bytes of the
+        /// trace that follow it.
+        Resume(Resume),
+    }
+
     /// Parses a CTF file (memtrace format).
+    ///
+    /// A trace whose last packet is incomplete, typically because the program writing it was
+    /// killed, is parsed up to its last complete packet. In this case, a non-fatal error is
+    /// registered (see [`err::register_non_fatal`]) and the truncation information is returned.
+    ///
+    /// Also returns the state of the parser, see [`parse_resumed`].
+    ///
+    /// [`err::register_non_fatal`]: ../base/err/fn.register_non_fatal.html
+    /// (The register_non_fatal function)
+    /// [`parse_resumed`]: fn.parse_resumed.html (The parse_resumed function)
     pub fn parse<'a, F>(
+        bytes: &[u8],
+        factory: &mut F,
+        bytes_progress: impl FnMut(usize),
+        init_action: impl FnOnce(&mut F, Init),
+        new_action: impl FnMut(&mut F, alloc_data::Builder),
+        dead_action: impl FnMut(&mut F, time::SinceStart, uid::Alloc),
+        mark_timestamp: impl FnMut(&mut F, time::SinceStart),
+    ) -> Res<(Option<Truncation>, Resume)>
+    where
+        F: std::ops::DerefMut<Target = mem::Factory<'a>>,
+    {
+        parse_with(
+            None,
+            Start::Beginning { replay: 0 },
+            bytes,
+            factory,
+            bytes_progress,
+            init_action,
+            new_action,
+            dead_action,
+            mark_timestamp,
+        )
+    }
+
+    /// Parses a CTF file (memtrace format), decoding packets in parallel.
+    ///
+    /// Decoding the events of a packet depends on the location (MTF) and backtrace caches built by
+    /// all the previous packets. A scanning thread skims over the packets to update these caches,
+    /// which is cheaper than decoding them, and hands each packet with a copy of the caches it
+    /// starts from to one of the decoding threads. The current thread gets the decoded packets
+    /// back in order, builds the traces and runs the actions. Use the `pipeline` example of this
+    /// crate to compare it with [`parse`] on a given trace.
+    ///
+    /// There are as many decoding threads as available cores minus two, and at least one. At most
+    /// [`PACKET_BUFFER_COUNT`] packets are waiting to be decoded or handled at any time, not
+    /// counting the packets being decoded. Behaves exactly like [`parse`] otherwise.
+    ///
+    /// [`parse`]: fn.parse.html (The parse function)
+    /// [`PACKET_BUFFER_COUNT`]: constant.PACKET_BUFFER_COUNT.html (The PACKET_BUFFER_COUNT constant)
+    pub fn pipelined_parse<'a, F>(
+        bytes: &[u8],
+        factory: &mut F,
+        bytes_progress: impl FnMut(usize),
+        init_action: impl FnOnce(&mut F, Init),
+        new_action: impl FnMut(&mut F, alloc_data::Builder),
+        dead_action: impl FnMut(&mut F, time::SinceStart, uid::Alloc),
+        mark_timestamp: impl FnMut(&mut F, time::SinceStart),
+    ) -> Res<(Option<Truncation>, Resume)>
+    where
+        F: std::ops::DerefMut<Target = mem::Factory<'a>>,
+    {
+        parse_with(
+            Some(decoder_count()),
+            Start::Beginning { replay: 0 },
+            bytes,
+            factory,
+            bytes_progress,
+            init_action,
+            new_action,
+            dead_action,
+            mark_timestamp,
+        )
+    }
+
+    /// Parses the packets appended to a CTF file (memtrace format) since it was last parsed.
+    ///
+    /// `resume` is the length of the part of the trace that was parsed before, *i.e.* the offset
+    /// of the incomplete packet if the trace was truncated, and its length otherwise. The packets
+    /// before `resume` are decoded again, since decoding depends on the location and backtrace
+    /// caches they build, but their allocations, deaths and timestamps are not handed to the
+    /// actions. The trace header must not have changed since the last parse, see [`run_id`].
+    ///
+    /// Only use this when the state of the last parse is not available, *e.g.* because the data
+    /// was loaded from a cache, as [`parse_resumed`] only decodes the new packets.
+    ///
+    /// Behaves like [`parse`] otherwise, except that there is no init action and that truncation is
+    /// only logged: the trace is most likely still being written.
+    ///
+    /// [`parse`]: fn.parse.html (The parse function)
+    /// [`parse_resumed`]: fn.parse_resumed.html (The parse_resumed function)
+    /// [`run_id`]: ../fn.run_id.html (The run_id function)
+    pub fn parse_appended<'a, F>(
+        resume: usize,
+        bytes: &[u8],
+        factory: &mut F,
+        bytes_progress: impl FnMut(usize),
+        new_action: impl FnMut(&mut F, alloc_data::Builder),
+        dead_action: impl FnMut(&mut F, time::SinceStart, uid::Alloc),
+        mark_timestamp: impl FnMut(&mut F, time::SinceStart),
+    ) -> Res<(Option<Truncation>, Resume)>
+    where
+        F: std::ops::DerefMut<Target = mem::Factory<'a>>,
+    {
+        parse_with(
+            None,
+            Start::Beginning { replay: resume },
+            bytes,
+            factory,
+            bytes_progress,
+            |_, _| (),
+            new_action,
+            dead_action,
+            mark_timestamp,
+        )
+    }
+
+    /// Parses the packets appended to a CTF file (memtrace format) after a previous parse.
+    ///
+    /// `bytes` are the bytes of the trace that follow the part parsed before, *i.e.* they start at
+    /// [`Resume::offset`]. Only these bytes are decoded. The trace header must not have changed
+    /// since the last parse, see [`run_id`].
+    ///
+    /// Behaves like [`parse_appended`] otherwise. Offsets in the truncation information, if any,
+    /// are relative to the start of the whole trace.
+    ///
+    /// [`parse_appended`]: fn.parse_appended.html (The parse_appended function)
+    /// [`Resume::offset`]: struct.Resume.html#method.offset (The offset method of Resume)
+    /// [`run_id`]: ../fn.run_id.html (The run_id function)
+    pub fn parse_resumed<'a, F>(
+        resume: Resume,
+        bytes: &[u8],
+        factory: &mut F,
+        bytes_progress: impl FnMut(usize),
+        new_action: impl FnMut(&mut F, alloc_data::Builder),
+        dead_action: impl FnMut(&mut F, time::SinceStart, uid::Alloc),
+        mark_timestamp: impl FnMut(&mut F, time::SinceStart),
+    ) -> Res<(Option<Truncation>, Resume)>
+    where
+        F: std::ops::DerefMut<Target = mem::Factory<'a>>,
+    {
+        parse_with(
+            None,
+            Start::Resume(resume),
+            bytes,
+            factory,
+            bytes_progress,
+            |_, _| (),
+            new_action,
+            dead_action,
+            mark_timestamp,
+        )
+    }
+
+    /// Maximum number of packets waiting to be decoded or handled in [`pipelined_parse`].
+    ///
+    /// [`pipelined_parse`]: fn.pipelined_parse.html (The pipelined_parse function)
+    pub const PACKET_BUFFER_COUNT: usize = 64;
+
+    /// Number of decoding threads in [`pipelined_parse`].
+    ///
+    /// Leaves a core for the scanning thread and one for the current thread.
+    ///
+    /// [`pipelined_parse`]: fn.pipelined_parse.html (The pipelined_parse function)
+    fn decoder_count() -> usize {
+        std::thread::available_parallelism()
+            .map(|count| count.get().saturating_sub(2))
+            .unwrap_or(1)
+            .max(1)
+    }
+
+    /// Parses a CTF file, see [`parse`], [`pipelined_parse`], [`parse_appended`] and
+    /// [`parse_resumed`].
+    ///
+    /// Packets are decoded in parallel by `decoders` threads if any, and sequentially on the
+    /// current thread otherwise. The init action only runs when parsing starts at the beginning of
+    /// the trace.
+    ///
+    /// [`parse`]: fn.parse.html (The parse function)
+    /// [`pipelined_parse`]: fn.pipelined_parse.html (The pipelined_parse function)
+    /// [`parse_appended`]: fn.parse_appended.html (The parse_appended function)
+    /// [`parse_resumed`]: fn.parse_resumed.html (The parse_resumed function)
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn parse_with<'a, F>(
+        decoders: Option<usize>,
+        start: Start,
         bytes: &[u8],
         mut factory: &mut F,
         mut bytes_progress: impl FnMut(usize),
@@ -202,7 +584,7 @@ mod diff_parse {
         mut new_action: impl FnMut(&mut F, alloc_data::Builder),
         mut dead_action: impl FnMut(&mut F, time::SinceStart, uid::Alloc),
         mut mark_timestamp: impl FnMut(&mut F, time::SinceStart),
-    ) -> Res<()>
+    ) -> Res<(Option<Truncation>, Resume)>
     where
         F: std::ops::DerefMut<Target = mem::Factory<'a>>,
     {
@@ -222,13 +604,34 @@ mod diff_parse {
         let mut prof = Prof::new();
         prof.total.start();
 
-        let mut trace_builder = TraceBuilder::new();
+        // Appending to a trace that is still being written usually ends with an incomplete packet,
+        // only report truncations when parsing a whole trace.
+        let report_truncation = matches!(start, Start::Beginning { replay: 0 });
+
+        // Packets starting before `replay` are only decoded to build the caches and traces, `saved`
+        // is the state of the parser when resuming a previous parse.
+        let (replay, mut trace_builder, mut loc_id_to_loc, saved) = match start {
+            Start::Beginning { replay } => (
+                replay,
+                TraceBuilder::new(),
+                // Maps location encoded identifiers to actual locations.
+                LocMap::with_capacity(1001),
+                None,
+            ),
+            Start::Resume(Resume {
+                parser,
+                trace_builder,
+                loc_map,
+            }) => (0, trace_builder, loc_map, Some(parser)),
+        };
 
-        // Maps location encoded identifiers to actual locations.
-        let mut loc_id_to_loc = LocMap::with_capacity(1001);
+        let parser = match saved.as_ref() {
+            None => CtfParser::new(bytes)?,
+            Some(saved) => CtfParser::resume(bytes, saved),
+        };
 
-        parse! {
-            bytes => |mut parser| {
+        parser_do! {
+            parser => join |mut parser| {
                 prof.basic_parsing.start();
 
                 let header = parser.header();
@@ -238,24 +641,18 @@ mod diff_parse {
                 let start_time = date_from_microsecs(header.timestamp.lbound);
                 // let end_time = date_from_microsecs(header.header.timestamp.end).sub(start_time)?;
 
-                // Init info.
-                let init = parser.trace_info().to_init(start_time);
-
-                init_action(factory, init);
+                if saved.is_none() {
+                    // Init info.
+                    let init = parser.trace_info().to_init(start_time);
+                    init_action(factory, init);
+                }
                 prof.basic_parsing.stop();
 
-                // Iterate over the packet of the trace.
-                while let Some(mut packet_parser) = prof.packet_parsing.time(
-                    || parser.next_packet()
-                )? {
-                    if packet_parser.header().id() % 10 == 9 {
-                        bytes_progress(packet_parser.real_position().0);
-                    }
-
-                    // Iterate over the events of the packet.
-                    while let Some((clock, event)) = prof.event_parsing.time(
-                        || packet_parser.next_event()
-                    )? {
+                // Handles a decoded event, `replay` is true for the events of packets before
+                // `replay`.
+                macro_rules! handle_event {
+                    ($clock:expr, $event:expr, $replay:expr) => {{
+                        let (clock, event, replay) = ($clock, $event, $replay);
                         use crate::ast::event::Event;
 
                         match event {
@@ -271,6 +668,11 @@ mod diff_parse {
                                     ))?
                                 };
 
+                                // Replayed traces only keep the trace builder in sync.
+                                if replay {
+                                    continue;
+                                }
+
                                 prof.alloc.start();
 
                                 // Build the allocation.
@@ -295,6 +697,7 @@ mod diff_parse {
                                 prof.alloc_action.time(|| new_action(factory, alloc))
                             },
 
+                            Event::Collection(_) if replay => (),
                             Event::Collection(alloc_uid) => {
                                 prof.dead.start();
 
@@ -309,7 +712,7 @@ mod diff_parse {
                                 prof.locations.start();
 
                                 let locs = locs.into_iter().map(|loc| {
-                                    let file = factory.register_str(loc.file_path);
+                                    let file = factory.register_file(loc.file_path);
                                     let line = loc.line;
                                     let col = loc.col;
 
@@ -333,15 +736,175 @@ mod diff_parse {
                                 ()
                             },
                         }
+                    }};
+                }
+
+                // Handles the end of a packet, `position` is the position of the packet in the
+                // input bytes.
+                macro_rules! end_packet {
+                    ($header:expr, $position:expr) => {{
+                        let (header, position): (&crate::ast::header::Packet, usize) =
+                            ($header, $position);
+                        if header.id() % 10 == 9 {
+                            bytes_progress(position);
+                        }
+                        if position >= replay {
+                            let packet_end =
+                                date_from_microsecs(header.timestamp.ubound) - start_time;
+                            mark_timestamp(factory, packet_end)
+                        }
+                    }};
+                }
+
+                let (truncation, parser) = if let Some(decoder_count) = decoders {
+                    std::thread::scope(|scope| -> Res<(Option<Truncation>, Saved)> {
+                        use std::sync::mpsc::sync_channel;
+
+                        let decoder_count = decoder_count.max(1);
+                        let buffer_len = (PACKET_BUFFER_COUNT / decoder_count).max(1);
+
+                        // Packet `n` goes to decoding thread `n % decoder_count` through
+                        // `jobs[n % decoder_count]`, and comes back decoded through
+                        // `results[n % decoder_count]`.
+                        let mut jobs = Vec::with_capacity(decoder_count);
+                        let mut results = Vec::with_capacity(decoder_count);
+                        let mut decoder_threads = Vec::with_capacity(decoder_count);
+
+                        for _ in 0..decoder_count {
+                            let (job_sender, job_receiver) =
+                                sync_channel::<crate::parse::DetachedPacket<_>>(buffer_len);
+                            let (res_sender, res_receiver) = sync_channel(buffer_len);
+                            let mut event_parsing = prof.event_parsing.clone();
+
+                            // Decoding thread.
+                            decoder_threads.push(scope.spawn(move || {
+                                let decode = || -> Res<()> {
+                                    for mut packet in job_receiver {
+                                        let mut events = Vec::with_capacity(64);
+                                        let mut packet_parser = packet.parser();
+                                        let position = packet_parser.real_position().0;
+                                        while let Some(event) = event_parsing.time(
+                                            || packet_parser.next_event()
+                                        )? {
+                                            events.push(event)
+                                        }
+                                        let header = packet.header().clone();
+                                        if res_sender.send((header, position, events)).is_err() {
+                                            // The receiver stopped, it will report the problem.
+                                            break;
+                                        }
+                                    }
+                                    Ok(())
+                                };
+                                (decode(), event_parsing)
+                            }));
+                            jobs.push(job_sender);
+                            results.push(res_receiver);
+                        }
+
+                        // Skimming happens on the scanning thread, so does the packet stopwatch.
+                        let mut packet_parsing = prof.packet_parsing.clone();
+
+                        // Scanning thread: skims over packets to compute the context each of them
+                        // starts from, and dispatches them to the decoding threads.
+                        let scanner = scope.spawn(move || {
+                            let mut scan = || -> Res<Option<Truncation>> {
+                                let mut jobs = jobs.iter().cycle();
+                                loop {
+                                    let error = match packet_parsing.time(|| {
+                                        parser
+                                            .next_packet()?
+                                            .map(|packet_parser| packet_parser.detach())
+                                            .transpose()
+                                    }) {
+                                        Ok(Some(packet)) => {
+                                            let sent = jobs
+                                                .next()
+                                                .map(|job| job.send(packet).is_ok())
+                                                .unwrap_or(false);
+                                            if !sent {
+                                                // The receiver stopped, it will report the problem.
+                                                return Ok(None);
+                                            }
+                                            continue;
+                                        }
+                                        Ok(None) => return Ok(None),
+                                        Err(e) => e,
+                                    };
+                                    return Truncation::of_error(&mut parser, error).map(Some);
+                                }
+                            };
+                            let truncation = scan();
+                            // Let the decoding threads know there are no more packets.
+                            drop(jobs);
+                            (truncation, parser.save(), packet_parsing)
+                        });
+
+                        // Handle decoded packets in order, stops as soon as a decoding thread
+                        // stops.
+                        for results in results.iter().cycle() {
+                            let (header, position, events) = match results.recv() {
+                                Ok(packet) => packet,
+                                Err(_) => break,
+                            };
+                            for (clock, event) in events {
+                                handle_event!(clock, event, position < replay)
+                            }
+                            end_packet!(&header, position)
+                        }
+                        // Unblocks the decoding threads still sending packets, if any.
+                        drop(results);
+
+                        for (index, decoder) in decoder_threads.into_iter().enumerate() {
+                            let (res, event_parsing) = match decoder.join() {
+                                Ok(res) => res,
+                                Err(_) => bail!("a CTF decoding thread panicked"),
+                            };
+                            res?;
+                            // Stopwatches cannot be merged, only report the first decoding thread.
+                            if index == 0 {
+                                prof.event_parsing = event_parsing
+                            }
+                        }
+                        let (truncation, parser, packet_parsing) = match scanner.join() {
+                            Ok(res) => res,
+                            Err(_) => bail!("the CTF scanning thread panicked"),
+                        };
+                        prof.packet_parsing = packet_parsing;
+                        Ok((truncation?, parser))
+                    })?
+                } else {
+                    // Iterate over the packet of the trace.
+                    loop {
+                        let error = match prof.packet_parsing.time(|| parser.next_packet()) {
+                            Ok(Some(mut packet_parser)) => {
+                                let position = packet_parser.real_position().0;
+
+                                // Iterate over the events of the packet.
+                                while let Some((clock, event)) = prof.event_parsing.time(
+                                    || packet_parser.next_event()
+                                )? {
+                                    handle_event!(clock, event, position < replay)
+                                }
+
+                                end_packet!(packet_parser.header(), position);
+                                continue;
+                            }
+                            Ok(None) => break (None, parser.save()),
+                            Err(e) => e,
+                        };
+                        let truncation = Truncation::of_error(&mut parser, error)?;
+                        break (Some(truncation), parser.save());
                     }
+                };
 
-                    let packet_end = date_from_microsecs(
-                        packet_parser.header().timestamp.ubound
-                    ) - start_time;
-                    mark_timestamp(
-                        factory,
-                        packet_end,
-                    )
+                if let Some(truncation) = truncation {
+                    if report_truncation {
+                        base::log::warn!("{}", truncation);
+                        err::register_non_fatal(truncation.to_string())
+                    } else {
+                        base::log::info!("{}", truncation)
+                    }
                 }
 
                 prof.all_do(
@@ -349,8 +912,148 @@ mod diff_parse {
                     |desc, sw| base::log::info!("| {:>25}: {}", desc, sw),
                 );
 
-                Ok(())
+                Ok((truncation, Resume { parser, trace_builder, loc_map: loc_id_to_loc }))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A small trace.
+    const TRACE: &[u8] = include_bytes!("../rsc/lists_and_sets.ctf");
+    /// A trace with many packets.
+    const BIG_TRACE: &[u8] = include_bytes!("../rsc/ocamlopt.ctf");
+
+    /// Events handed to the actions while parsing a trace.
+    #[derive(Debug, Default, PartialEq)]
+    struct Events {
+        /// New allocations, as debug strings.
+        new: Vec<String>,
+        /// Dead allocations.
+        dead: Vec<(time::SinceStart, uid::Alloc)>,
+    }
+
+    /// Parses some bytes, resuming a previous parse if any.
+    ///
+    /// Decodes packets with `decoders` threads if any, see [`diff_parse::parse_with`].
+    fn parse_events(
+        bytes: &[u8],
+        resume: Option<Resume>,
+        decoders: Option<usize>,
+        events: &mut Events,
+    ) -> Res<(Option<Truncation>, Resume)> {
+        let mut factory = Box::new(alloc_data::mem::Factory::new(false));
+        let Events { new, dead } = events;
+        let start = match resume {
+            None => diff_parse::Start::Beginning { replay: 0 },
+            Some(resume) => diff_parse::Start::Resume(resume),
+        };
+        diff_parse::parse_with(
+            decoders,
+            start,
+            bytes,
+            &mut factory,
+            |_| (),
+            |_, _| (),
+            |_: &mut _, builder| new.push(format!("{:?}", builder)),
+            |_: &mut _, tod, uid| dead.push((tod, uid)),
+            |_, _| (),
+        )
+    }
+
+    #[test]
+    fn truncated_mid_packet() {
+        // Events start and length of the packets of the trace, with the number of allocations up
+        // to the end of each packet.
+        let packets = || -> Res<Vec<(usize, usize, u64)>> {
+            parse! {
+                BIG_TRACE => |mut parser| {
+                    let mut packets = vec![];
+                    while let Some(packet) = parser.next_packet()? {
+                        let (start, len) = packet.real_position();
+                        packets.push((start, len, packet.header().alloc_id.ubound))
+                    }
+                    Ok(packets)
+                }
             }
+        };
+        let packets = packets().unwrap();
+        assert!(packets.len() > 3, "test trace has too few packets");
+
+        // Cut in the middle of the third packet.
+        let (start, len, _) = packets[2];
+        let (_, _, expected) = packets[1];
+        let cut = start + len / 2;
+
+        let mut events = Events::default();
+        let (truncation, resume) =
+            parse_events(&BIG_TRACE[..cut], None, None, &mut events).unwrap();
+        assert_eq!(
+            truncation,
+            Some(Truncation {
+                offset: start - parse::PACKET_HEADER_LEN,
+                len: cut,
+            })
+        );
+        assert_eq!(resume.offset(), start - parse::PACKET_HEADER_LEN);
+        assert_eq!(events.new.len() as u64, expected);
+    }
+
+    #[test]
+    fn resumed_parse_is_full_parse() {
+        let mut full = Events::default();
+        let (truncation, resume) = parse_events(TRACE, None, None, &mut full).unwrap();
+        assert_eq!(truncation, None);
+        assert_eq!(resume.offset(), TRACE.len());
+        assert_eq!(resume.run_id(), run_id(TRACE).unwrap());
+        assert!(!full.new.is_empty());
+        assert!(!full.dead.is_empty());
+
+        let mut resumed = Events::default();
+        let cut = TRACE.len() / 2;
+        let (truncation, resume) = parse_events(&TRACE[..cut], None, None, &mut resumed).unwrap();
+        let truncation = truncation.expect("cutting a trace in the middle truncates it");
+        assert_eq!(truncation.len, cut);
+        assert_eq!(resume.offset(), truncation.offset);
+        assert!(resumed.new.len() < full.new.len());
+
+        let (truncation, resume) =
+            parse_events(&TRACE[resume.offset()..], Some(resume), None, &mut resumed).unwrap();
+        assert_eq!(truncation, None);
+        assert_eq!(resume.offset(), TRACE.len());
+        assert_eq!(resumed, full);
+    }
+
+    #[test]
+    fn pipelined_parse_is_parse() {
+        let mut sequential = Events::default();
+        let (truncation, resume) = parse_events(BIG_TRACE, None, None, &mut sequential).unwrap();
+        assert_eq!(truncation, None);
+        assert!(!sequential.new.is_empty());
+        let offset = resume.offset();
+
+        for &decoders in &[1, 3] {
+            let mut pipelined = Events::default();
+            let (truncation, resume) =
+                parse_events(BIG_TRACE, None, Some(decoders), &mut pipelined).unwrap();
+            assert_eq!(truncation, None);
+            assert_eq!(resume.offset(), offset);
+            assert_eq!(pipelined, sequential, "{} decoding thread(s)", decoders);
         }
+
+        // Truncated in the middle of the trace.
+        let cut = BIG_TRACE.len() / 2;
+        let mut sequential = Events::default();
+        let (expected, _) = parse_events(&BIG_TRACE[..cut], None, None, &mut sequential).unwrap();
+        let mut pipelined = Events::default();
+        let (truncation, resume) =
+            parse_events(&BIG_TRACE[..cut], None, Some(3), &mut pipelined).unwrap();
+        assert!(truncation.is_some());
+        assert_eq!(truncation, expected);
+        assert_eq!(resume.offset(), truncation.unwrap().offset);
+        assert_eq!(pipelined, sequential);
     }
 }
@@ -228,6 +228,7 @@ impl<'data, T> std::ops::IndexMut<Idx> for MtfMap<'data, T> {
 /// Location parsing context.
 ///
 /// Wrapper around an MTF map.
+#[derive(Clone)]
 pub struct Cxt<'data> {
     /// The MTF map.
     map: MtfMap<'data, MtfMap<'data, ()>>,
@@ -238,6 +239,50 @@ impl<'data> Cxt<'data> {
         Self { map: MtfMap::new() }
     }
 
+    /// Owned copy of the context, see [`restore`].
+    ///
+    /// [`restore`]: #method.restore (The restore method)
+    pub fn save(&self) -> Saved {
+        let map = self
+            .map
+            .vec
+            .iter()
+            .map(|entry| {
+                entry.as_ref().map(|(key, sub_map)| {
+                    let sub_keys = sub_map
+                        .vec
+                        .iter()
+                        .map(|entry| entry.as_ref().map(|(key, ())| key.to_string()))
+                        .collect();
+                    (key.to_string(), sub_keys)
+                })
+            })
+            .collect();
+        Saved { map }
+    }
+
+    /// Context borrowing the strings of a saved context, see [`save`].
+    ///
+    /// [`save`]: #method.save (The save method)
+    pub fn restore(saved: &'data Saved) -> Self {
+        let map = saved
+            .map
+            .iter()
+            .map(|entry| {
+                entry.as_ref().map(|(key, sub_keys)| {
+                    let sub_map = sub_keys
+                        .iter()
+                        .map(|entry| entry.as_ref().map(|key| (key.as_str(), ())))
+                        .collect();
+                    (key.as_str(), MtfMap { vec: sub_map })
+                })
+            })
+            .collect();
+        Self {
+            map: MtfMap { vec: map },
+        }
+    }
+
     /// Multi-line string representation of the context.
     pub fn to_ml_string(&self) -> String {
         let mut s = format!("{{");
@@ -275,6 +320,15 @@ impl<'data> Cxt<'data> {
     }
 }
 
+/// Owned version of a location context, see [`Cxt::save`].
+///
+/// [`Cxt::save`]: struct.Cxt.html#method.save (The save method of Cxt)
+#[derive(Debug, Clone)]
+pub struct Saved {
+    /// Entries of the MTF map, with the keys of their own MTF map.
+    map: Vec<Option<(String, Vec<Option<String>>)>>,
+}
+
 /// A list of locations.
 pub type Locs<'data> = Vec<Location<'data>>;
 
@@ -118,6 +118,16 @@ impl<'data> Cxt<'data> {
     pub fn peek_next_alloc_id(&self) -> u64 {
         self.alloc_count
     }
+
+    /// Copy of the context, with fresh profiling data.
+    pub fn checkpoint(&self) -> Self {
+        Self {
+            loc: self.loc.clone(),
+            btrace: self.btrace.clone(),
+            alloc_count: self.alloc_count,
+            prof: Prof::new(),
+        }
+    }
 }
 
 /// Raw parser.
@@ -769,6 +779,44 @@ decl_impl_trait! {
         ) -> Res<ast::event::Alloc> {
             pinfo!(self, "parsing alloc");
             let alloc_id = cxt.next_alloc_id();
+            let (len, nsamples, is_major, common_pref_len, nencoded) = self.alloc_header(short)?;
+
+            let backtrace =
+                cxt.btrace
+                    .get_backtrace(self, nencoded, common_pref_len)?;
+
+            let alloc_time = time::Duration::from_micros(timestamp);
+
+            Ok(ast::event::Alloc {
+                id: alloc_id,
+                alloc_time,
+                len,
+                nsamples,
+                is_major,
+                common_pref_len,
+                backtrace,
+            })
+        }
+
+        /// Skims over an allocation, only updates the context.
+        ///
+        /// Context-sensitive, same as [`alloc`] without building the allocation.
+        ///
+        /// [`alloc`]: #method.alloc (The alloc method)
+        fn skim_alloc(&mut self, cxt: &mut Cxt<'data>, short: Option<usize>) -> Res<()> {
+            pinfo!(self, "skimming alloc");
+            let _ = cxt.next_alloc_id();
+            let (_, _, _, common_pref_len, nencoded) = self.alloc_header(short)?;
+            cxt.btrace.skim_backtrace(self, nencoded, common_pref_len)
+        }
+
+        /// Parses the part of an allocation before its backtrace.
+        ///
+        /// Yields the length, number of samples, major flag, common prefix length and number of
+        /// encoded frames of the allocation.
+        fn alloc_header(
+            &mut self, short: Option<usize>
+        ) -> Res<(usize, usize, bool, usize, usize)> {
             let (is_short, len, nsamples, is_major) = if let Some(len) = short {
                 (true, len, 1, false)
             } else {
@@ -797,21 +845,7 @@ decl_impl_trait! {
                 common_pref_len
             );
 
-            let backtrace =
-                cxt.btrace
-                    .get_backtrace(self, nencoded, common_pref_len)?;
-
-            let alloc_time = time::Duration::from_micros(timestamp);
-
-            Ok(ast::event::Alloc {
-                id: alloc_id,
-                alloc_time,
-                len,
-                nsamples,
-                is_major,
-                common_pref_len,
-                backtrace,
-            })
+            Ok((len, nsamples, is_major, common_pref_len, nencoded))
         }
 
         /// Parses an allocation UID from a delta *w.r.t.* the most recent UID generated.
@@ -1059,6 +1093,12 @@ decl_impl_trait! {
     }
 }
 
+/// Length of a packet header in bytes, including the magic number.
+///
+/// Magic number (4), packet size (4), time span (16), flush duration (4), version (2), pid (8),
+/// cache-check information (12), allocation UID span (16).
+pub const PACKET_HEADER_LEN: usize = 66;
+
 /// Top-level parser.
 pub struct CtfParser<'data, Endian> {
     parser: Parser<'data, Endian>,
@@ -1112,6 +1152,50 @@ impl<'data, Endian> std::ops::DerefMut for CtfParser<'data, Endian> {
     }
 }
 
+/// State of a [`CtfParser`] between two packets, owns its data.
+///
+/// Produced by [`CtfParser::save`], and used by [`CtfParser::resume`] to parse the packets
+/// appended to a trace without parsing the packets before them again.
+///
+/// [`CtfParser`]: struct.CtfParser.html (CtfParser struct)
+/// [`CtfParser::save`]: struct.CtfParser.html#method.save (The save method of CtfParser)
+/// [`CtfParser::resume`]: struct.CtfParser.html#method.resume (The resume function of CtfParser)
+#[derive(Clone)]
+pub struct Saved {
+    /// CTF header.
+    header: header::Ctf,
+    /// Trace info, without its context.
+    trace_info: ast::event::Info<'static>,
+    /// Context of the trace info.
+    context: Option<String>,
+    /// Location context.
+    loc: loc::Saved,
+    /// Backtrace context.
+    btrace: btrace::Cxt,
+    /// Allocation UID counter.
+    alloc_count: u64,
+    /// Number of packets parsed.
+    packet_count: usize,
+    /// Offset of the next packet in the trace.
+    offset: usize,
+}
+impl Saved {
+    /// Offset of the next packet in the trace, *i.e.* length of the part of the trace parsed.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+    /// CTF header.
+    pub fn header(&self) -> &header::Ctf {
+        &self.header
+    }
+    /// Trace info.
+    pub fn trace_info(&self) -> ast::event::Info<'_> {
+        let mut trace_info: ast::event::Info = self.trace_info.clone();
+        trace_info.context = self.context.as_deref();
+        trace_info
+    }
+}
+
 /// Low-endian CTF parser.
 pub type LeCtfParser<'data> = CtfParser<'data, LowEndian>;
 /// Big-endian CTF parser.
@@ -1128,6 +1212,73 @@ impl<'data, Endian> CtfParser<'data, Endian> {
     }
 }
 
+impl<'data> CtfParser<'data, ()> {
+    /// Parser for the packets following a saved state, see [`save`].
+    ///
+    /// `bytes` are the bytes of the trace that follow the last packet parsed before saving, *i.e.*
+    /// starting at [`Saved::offset`]. Positions and truncations are relative to the start of the
+    /// whole trace.
+    ///
+    /// [`save`]: #method.save (The save method)
+    /// [`Saved::offset`]: struct.Saved.html#method.offset (The offset method of Saved)
+    pub fn resume(
+        bytes: &'data [u8],
+        saved: &'data Saved,
+    ) -> Either<BeCtfParser<'data>, LeCtfParser<'data>> {
+        macro_rules! resume {
+            ($parser:expr) => {
+                CtfParser {
+                    parser: $parser,
+                    header: saved.header.clone(),
+                    trace_info: saved.trace_info(),
+                    cxt: Cxt {
+                        loc: loc::Cxt::restore(&saved.loc),
+                        btrace: saved.btrace.clone(),
+                        alloc_count: saved.alloc_count,
+                        prof: Prof::new(),
+                    },
+                    packet_count: saved.packet_count,
+                }
+            };
+        }
+        if saved.header.is_be() {
+            Either::Left(resume!(BeParser::new(bytes, saved.offset)))
+        } else {
+            Either::Right(resume!(LeParser::new(bytes, saved.offset)))
+        }
+    }
+}
+
+impl<'data, Endian> CtfParser<'data, Endian> {
+    /// Saves the state of the parser, see [`resume`].
+    ///
+    /// Must be called between two packets, *i.e.* not while a [`PacketParser`] is alive.
+    ///
+    /// [`resume`]: #method.resume (The resume function)
+    /// [`PacketParser`]: struct.PacketParser.html (PacketParser struct)
+    pub fn save(&self) -> Saved {
+        let info = &self.trace_info;
+        Saved {
+            header: self.header.clone(),
+            trace_info: ast::event::Info {
+                sample_rate: info.sample_rate,
+                word_size: info.word_size,
+                exe_name: info.exe_name.clone(),
+                host_name: info.host_name.clone(),
+                exe_params: info.exe_params.clone(),
+                pid: info.pid,
+                context: None,
+            },
+            context: info.context.map(str::to_string),
+            loc: self.cxt.loc.save(),
+            btrace: self.cxt.btrace.clone(),
+            alloc_count: self.cxt.alloc_count,
+            packet_count: self.packet_count,
+            offset: self.parser.real_position().0,
+        }
+    }
+}
+
 /// Pseudo-parsers: parses a very tiny amout of data to produce a subparser.
 impl<'data, Endian> CtfParser<'data, Endian>
 where
@@ -1135,6 +1286,8 @@ where
 {
     /// Yields a [`PacketParser`] for the next packet, if any.
     ///
+    /// On error, the parser is left at the beginning of the packet.
+    ///
     /// [`PacketParser`]: struct.PacketParser.html (PacketParser struct)
     pub fn next_packet<'me>(&'me mut self) -> Res<Option<PacketParser<'me, 'data, Endian>>> {
         let parser = &mut self.parser;
@@ -1150,7 +1303,14 @@ where
         }
         pinfo!(parser, "parsing packet header");
 
-        let packet_header = parser.packet_header(*packet_count)?;
+        let start = parser.pos();
+        let packet_header = match parser.packet_header(*packet_count) {
+            Ok(header) => header,
+            Err(e) => {
+                parser.backtrack(start);
+                return Err(e);
+            }
+        };
         let content_len: usize = convert(packet_header.content_size, "next_packet: content_len");
         pinfo!(
             parser,
@@ -1160,20 +1320,54 @@ where
             parser.data().len()
         );
         if *parser.pos() + content_len > parser.data().len() {
+            let remaining = parser.data().len() - *parser.pos();
+            parser.backtrack(start);
             bail!(parse_error!(expected format!(
                 "legal packet size: not enough data left ({}/{})",
-                content_len, parser.data().len() - *parser.pos(),
+                content_len, remaining,
             )))
         }
 
+        let offset = parser.real_position().0;
         let event_bytes = parser.take(content_len);
-        let next = PacketParser::<Endian>::new(event_bytes, *parser.pos(), packet_header, cxt);
+        let next = PacketParser::<Endian>::new(event_bytes, offset, packet_header, cxt);
         *packet_count += 1;
 
         Ok(Some(next))
     }
 }
 
+impl<'data, Endian> CtfParser<'data, Endian>
+where
+    Parser<'data, Endian>: CanParse<'data>,
+{
+    /// True if the rest of the input is an incomplete packet.
+    ///
+    /// This happens when the program writing the trace is killed, or is still running. Typically
+    /// called after [`next_packet`] fails, to decide whether the trace is corrupted or just
+    /// truncated. Does not consume anything.
+    ///
+    /// [`next_packet`]: #method.next_packet (The next_packet method)
+    pub fn at_truncated_packet(&mut self) -> bool {
+        let parser = &mut self.parser;
+        if parser.is_eof() {
+            return false;
+        }
+        let start = parser.pos();
+        let remaining = parser.data().len() - *start;
+        let truncated = match parser.packet_header(self.packet_count) {
+            Ok(header) => {
+                let content_len: usize =
+                    convert(header.content_size, "at_truncated_packet: content_len");
+                *parser.pos() + content_len > parser.data().len()
+            }
+            Err(_) => remaining < PACKET_HEADER_LEN,
+        };
+        parser.backtrack(start);
+        truncated
+    }
+}
+
 /// Packet parser.
 ///
 /// Thin wrapper around a [`RawParser`] over the bytes of the events of the packet. Also stores the
@@ -1243,6 +1437,55 @@ where
         &self.header
     }
 
+    /// Checks the backtrace cache against the cache-verification data of the packet header.
+    ///
+    /// Only meaningful before the first event of the packet is parsed.
+    pub fn check_cache(&self) -> Res<()> {
+        self.cxt.btrace.check_cache(self.header.cache_check())
+    }
+
+    /// Skims over the events of the packet, only updates the parsing context.
+    ///
+    /// Yields the packet along with a checkpoint of the context before the packet, so that its
+    /// events can be decoded later, *e.g.* on another thread, with [`DetachedPacket::parser`].
+    /// Skimming is cheaper than decoding: neither allocations nor backtraces are built.
+    ///
+    /// Must be called before any event of the packet is parsed.
+    ///
+    /// [`DetachedPacket::parser`]: struct.DetachedPacket.html#method.parser
+    /// (The parser method of DetachedPacket)
+    pub fn detach(mut self) -> Res<DetachedPacket<'data, Endian>> {
+        debug_assert_eq!(self.event_cnt, 0);
+        let checkpoint = self.cxt.checkpoint();
+        let (input, offset) = (self.parser.data(), self.parser.real_position().0);
+
+        while !self.is_eof() {
+            let (event_kind, _) = self.parser.event_kind(&self.header)?;
+            let parser = &mut self.parser;
+            let cxt = &mut self.cxt;
+            match event_kind {
+                event::Kind::Alloc => parser.skim_alloc(cxt, None)?,
+                event::Kind::SmallAlloc(n) => {
+                    parser.skim_alloc(cxt, Some(convert(n, "skim: SmallAlloc(n)")))?
+                }
+                event::Kind::Promotion | event::Kind::Collection => destroy(parser.v_usize()?),
+                event::Kind::Locs => destroy(parser.locs(cxt)?),
+                // Can't have more than two info events.
+                event::Kind::Info => bail!(parse_error!(
+                    expected "non-info event: having more than two info events is illegal"
+                )),
+            }
+        }
+
+        Ok(DetachedPacket {
+            input,
+            offset,
+            header: self.header,
+            cxt: checkpoint,
+            _phantom: std::marker::PhantomData,
+        })
+    }
+
     /// Returns the next event of the packet, if any.
     pub fn next_event(&mut self) -> Res<Option<(Clock, Event<'data>)>> {
         if self.is_eof() {
@@ -1305,3 +1548,41 @@ where
         Ok(Some((event_timestamp, event)))
     }
 }
+
+/// Packet detached from its [`CtfParser`], see [`PacketParser::detach`].
+///
+/// Owns a copy of the parsing context as it was before the packet, and can thus be decoded
+/// independently of the other packets.
+///
+/// [`CtfParser`]: struct.CtfParser.html (CtfParser struct)
+/// [`PacketParser::detach`]: struct.PacketParser.html#method.detach
+/// (The detach method of PacketParser)
+pub struct DetachedPacket<'data, Endian> {
+    /// Bytes of the events of the packet.
+    input: &'data [u8],
+    /// Offset of the events from the start of the original input.
+    offset: usize,
+    /// Packet header.
+    header: header::Packet,
+    /// Parsing context before the packet.
+    cxt: Cxt<'data>,
+    _phantom: std::marker::PhantomData<Endian>,
+}
+
+impl<'data, Endian> DetachedPacket<'data, Endian>
+where
+    Parser<'data, Endian>: CanParse<'data>,
+{
+    /// Header accessor.
+    pub fn header(&self) -> &header::Packet {
+        &self.header
+    }
+
+    /// Parser for the events of the packet.
+    ///
+    /// Yields the same events as the parser the packet was detached from would have. Decoding
+    /// updates the context of the detached packet, so this should only be called once.
+    pub fn parser(&mut self) -> PacketParser<'_, 'data, Endian> {
+        PacketParser::new(self.input, self.offset, self.header.clone(), &mut self.cxt)
+    }
+}
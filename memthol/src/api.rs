@@ -0,0 +1,578 @@
+/*<LICENSE>
+    This file is part of Memthol.
+
+    Copyright (C) 2020 OCamlPro.
+
+    Memthol is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Memthol is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Memthol.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Read-only JSON API over the data memthol is serving.
+//!
+//! The websocket protocol is designed for the client, this API is meant for scripts and
+//! dashboards. All endpoints answer `GET` requests with JSON. Times are amounts of seconds since
+//! the start of the run, and sizes are in bytes. Errors are reported as `{ "error": <message> }`
+//! with an appropriate status code; in particular, endpoints answer `503` until the data is
+//! loaded, and `400` on illegal query parameters. Query parameters are percent-decoded. Endpoints
+//! run on the blocking thread pool since most of them go through the whole data. When memthol
+//! requires an access token, requests must carry it as the `token` query parameter or in an
+//! `Authorization: Bearer <token>` header (see the [`auth` module]).
+//!
+//! - `/api/stats?top=<n>`: statistics over the whole trace, including the `n` top allocation
+//!   sites (default `10`);
+//! - `/api/filters`: the filters generated for the trace, as a new client would see them, each
+//!   with a key (`everything`, `filter_<index>` or `catch_all`);
+//! - `/api/points`: points of a chart for each filter key, along with the markers of the run
+//!   (start, end and collection bursts). Query parameters, all optional, are
+//!   - `x_axis` and `y_axis`: axes of the chart (default `time` and `total_size`), spaces in axis
+//!     descriptions are written `_`;
+//!   - `filter_gen`: filter generator, with the syntax of `--filter_gen` (default: the one memthol
+//!     runs with);
+//!   - `lines`: comma-separated filter keys of the lines to report (default: all of them);
+//!   - `width` and `height`: resolution of the chart (default `1000`x`500`);
+//!   - `from` and `to`: time window of the points (default: the whole run);
+//! - `/api/sites?top=<n>`: the `n` allocation sites that allocate the most bytes (default `10`);
+//! - `/api/allocs/<uid>`: allocation with UID `uid`;
+//! - `/api/snapshot?at=<secs>&base=<secs>`: allocations live at time `at` aggregated by allocation
+//!   site and callstack, diffed against the ones live at time `base` if any.
+//!
+//! [`auth` module]: ../auth/index.html (The auth module)
+
+use crate::prelude::*;
+
+use charts::{
+    chart::axis::XAxis,
+    data,
+    filter::{gen::FilterGen, FilterSpec, Filters},
+    marker::Marker,
+    point::{Points, TimePoints},
+    snapshot::{self, Live, Snapshot},
+};
+use gotham::{
+    handler::HandlerFuture,
+    hyper::{
+        header::{self, HeaderValue},
+        Body, Response, StatusCode, Uri,
+    },
+    state::{FromState, State},
+};
+use serde_json::{json, Value};
+use std::pin::Pin;
+use time::DurationExt;
+
+/// Default number of allocation sites reported.
+pub const DEFAULT_TOP: usize = 10;
+/// Default chart resolution for points.
+pub const DEFAULT_RESOLUTION: (u32, u32) = (1000, 500);
+
+/// An error, and the status to answer with.
+struct ApiError {
+    /// Status of the answer.
+    status: StatusCode,
+    /// Actual error.
+    error: err::Error,
+}
+impl ApiError {
+    /// Constructor.
+    fn new(status: StatusCode, error: impl Into<err::Error>) -> Self {
+        Self {
+            status,
+            error: error.into(),
+        }
+    }
+}
+impl From<err::Error> for ApiError {
+    fn from(error: err::Error) -> Self {
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, error)
+    }
+}
+
+/// Result type for the API.
+type ApiRes<T> = Result<T, ApiError>;
+
+/// Builds a JSON response.
+fn json_response(status: StatusCode, value: &Value) -> Response<Body> {
+    let mut response = Response::new(Body::from(value.to_string()));
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    response
+}
+
+/// Turns the result of an endpoint into a response.
+fn respond(state: State, res: ApiRes<Value>) -> (State, Response<Body>) {
+    let response = match res {
+        Ok(value) => json_response(StatusCode::OK, &value),
+        Err(ApiError { status, error }) => {
+            if status == StatusCode::INTERNAL_SERVER_ERROR {
+                log::error!("API request failed: {}", error.to_pretty())
+            }
+            json_response(status, &json!({ "error": error.to_pretty() }))
+        }
+    };
+    (state, response)
+}
+
+/// Runs an endpoint on the blocking thread pool and turns its result into a response.
+fn respond_blocking(
+    state: State,
+    endpoint: impl FnOnce() -> ApiRes<Value> + Send + 'static,
+) -> Pin<Box<HandlerFuture>> {
+    Box::pin(async move {
+        let res = match tokio::task::spawn_blocking(endpoint).await {
+            Ok(res) => res,
+            Err(e) => Err(err::Error::from(format!("API request handler failed: {}", e)).into()),
+        };
+        Ok(respond(state, res))
+    })
+}
+
+/// Percent-decodes a query parameter key or value, `+` is a space.
+///
+/// Illegal escapes are kept as they are.
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut res = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+    while idx < bytes.len() {
+        match bytes[idx] {
+            b'+' => res.push(b' '),
+            b'%' => {
+                let escape = s
+                    .get(idx + 1..idx + 3)
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                if let Some(byte) = escape {
+                    res.push(byte);
+                    idx += 3;
+                    continue;
+                } else {
+                    res.push(b'%')
+                }
+            }
+            byte => res.push(byte),
+        }
+        idx += 1
+    }
+    String::from_utf8_lossy(&res).into_owned()
+}
+
+/// Query parameters of a request.
+struct Query {
+    /// Map from parameter names to values.
+    params: BTMap<String, String>,
+}
+impl Query {
+    /// Extracts the query parameters of a request.
+    fn new(state: &State) -> Self {
+        let params = Uri::borrow_from(state)
+            .query()
+            .unwrap_or("")
+            .split('&')
+            .filter(|param| !param.is_empty())
+            .map(|param| {
+                let mut subs = param.splitn(2, '=');
+                let key = percent_decode(subs.next().unwrap_or(""));
+                let val = percent_decode(subs.next().unwrap_or(""));
+                (key, val)
+            })
+            .collect();
+        Self { params }
+    }
+
+    /// Parses a parameter, `default` if the parameter is absent.
+    fn get<T>(&self, key: &str, default: T) -> ApiRes<T>
+    where
+        T: std::str::FromStr,
+    {
+        match self.params.get(key) {
+            None => Ok(default),
+            Some(val) => val.parse().map_err(|_| {
+                ApiError::new(
+                    StatusCode::BAD_REQUEST,
+                    format!("illegal value `{}` for query parameter `{}`", val, key),
+                )
+            }),
+        }
+    }
+
+    /// Parses a parameter with a custom parser, `None` if the parameter is absent.
+    fn get_with<T>(
+        &self,
+        key: &str,
+        parse: impl FnOnce(&str) -> Result<T, String>,
+    ) -> ApiRes<Option<T>> {
+        match self.params.get(key) {
+            None => Ok(None),
+            Some(val) => parse(val).map(Some).map_err(|e| {
+                ApiError::new(
+                    StatusCode::BAD_REQUEST,
+                    format!(
+                        "illegal value `{}` for query parameter `{}`: {}",
+                        val, key, e
+                    ),
+                )
+            }),
+        }
+    }
+
+    /// Parses a parameter as an amount of seconds since the start of the run.
+    fn get_secs(&self, key: &str) -> ApiRes<Option<time::SinceStart>> {
+        match self.params.get(key) {
+            None => Ok(None),
+            Some(val) => time::SinceStart::parse_secs(val).map(Some).map_err(|e| {
+                ApiError::new(
+                    StatusCode::BAD_REQUEST,
+                    e.chain_err(|| format!("illegal value for query parameter `{}`", key)),
+                )
+            }),
+        }
+    }
+}
+
+/// Amount of seconds of a duration.
+fn secs(duration: impl DurationExt) -> f64 {
+    duration.as_duration().as_secs_f64()
+}
+
+/// Fails with `503` if the data is not loaded yet.
+fn check_loaded() -> ApiRes<()> {
+    if data::get()?.stats().is_none() {
+        Err(ApiError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "the data is not loaded yet",
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Generates the filters a new client would see.
+fn gen_filters() -> ApiRes<Filters> {
+    gen_filters_with(charts::filter::gen::get())
+}
+
+/// Generates filters with some filter generator.
+fn gen_filters_with(generator: FilterGen) -> ApiRes<Filters> {
+    let data = data::get()?;
+    let (filters, _) = Filters::auto_gen(&data, generator)?;
+    Ok(filters)
+}
+
+/// Key of an axis in query parameters, its description with `_` instead of spaces.
+fn axis_key(desc: &str) -> String {
+    desc.replace(' ', "_")
+}
+
+/// Keys identifying the lines of some filters in JSON answers.
+///
+/// Filters are re-generated for each request, with fresh UIDs. Keys only depend on the position
+/// of the filter so that they are consistent across requests.
+pub(crate) fn line_keys(filters: &Filters) -> BTMap<uid::Line, String> {
+    let mut keys = BTMap::new();
+    keys.insert(filters.everything().uid(), "everything".to_string());
+    for (idx, filter) in filters.filters().iter().enumerate() {
+        keys.insert(filter.spec().uid(), format!("filter_{}", idx));
+    }
+    keys.insert(filters.catch_all().uid(), "catch_all".to_string());
+    keys
+}
+
+/// JSON representation of a filter specification.
+fn filter_json(spec: &FilterSpec, key: &str, subfilters: Vec<String>) -> Value {
+    json!({
+        "key": key,
+        "name": spec.name(),
+        "color": spec.color().to_string(),
+        "subfilters": subfilters,
+    })
+}
+
+/// JSON representation of some filters, in the order the client displays them.
+fn filters_json(filters: &Filters, keys: &BTMap<uid::Line, String>) -> Value {
+    let key = |spec: &FilterSpec| keys.get(&spec.uid()).cloned().unwrap_or_default();
+    let everything = filters.everything();
+    let mut json = vec![filter_json(everything, &key(everything), vec![])];
+    for filter in filters.filters() {
+        let subfilters = filter.iter().map(|sub| sub.raw().to_string()).collect();
+        json.push(filter_json(filter.spec(), &key(filter.spec()), subfilters))
+    }
+    let catch_all = filters.catch_all();
+    json.push(filter_json(catch_all, &key(catch_all), vec![]));
+    Value::Array(json)
+}
+
+/// JSON representation of an allocation.
+fn alloc_json(alloc: &Alloc) -> Value {
+    let trace: Vec<String> = alloc.trace().iter().map(|loc| loc.to_string()).collect();
+    let labels: Vec<String> = alloc
+        .labels()
+        .iter()
+        .map(|label| label.to_string())
+        .collect();
+    json!({
+        "uid": alloc.uid.get(),
+        "kind": alloc.kind.as_str(),
+        "size": alloc.size,
+        "real_size": alloc.real_size,
+        "nsamples": alloc.nsamples,
+        "toc": secs(alloc.toc),
+        "tod": alloc.tod.map(secs),
+        "lifetime": alloc.tod.map(|tod| secs(tod - alloc.toc)),
+        "trace": trace,
+        "labels": labels,
+    })
+}
+
+/// Statistics over the whole trace.
+fn stats(query: &Query) -> ApiRes<Value> {
+    check_loaded()?;
+    let top = query.get("top", DEFAULT_TOP)?;
+    let stats = data::stats::TraceStats::get(top)?;
+    let json = serde_json::to_value(&stats).chain_err(|| "while serializing statistics to JSON")?;
+    Ok(json)
+}
+
+/// Filters generated for the trace.
+fn filters() -> ApiRes<Value> {
+    check_loaded()?;
+    let filters = gen_filters()?;
+    Ok(filters_json(&filters, &line_keys(&filters)))
+}
+
+/// JSON representation of a marker.
+fn marker_json(marker: &Marker) -> Value {
+    json!({
+        "kind": marker.kind.desc(),
+        "time": secs(marker.time),
+        "until": marker.until.map(secs),
+        "label": marker.label,
+    })
+}
+
+/// Points of a chart for each filter.
+fn points(query: &Query) -> ApiRes<Value> {
+    check_loaded()?;
+    let x_axis = query
+        .get_with("x_axis", |val| {
+            XAxis::all()
+                .into_iter()
+                .find(|x_axis| axis_key(x_axis.desc()) == val)
+                .ok_or_else(|| "unknown x-axis".to_string())
+        })?
+        .unwrap_or_default();
+    let y_axis = query.get_with("y_axis", |val| {
+        x_axis
+            .y_axes()
+            .into_iter()
+            .find(|y_axis| axis_key(y_axis.desc()) == val)
+            .ok_or_else(|| format!("unknown y-axis, or incompatible with x-axis `{}`", x_axis))
+    })?;
+    let y_axis = if let Some(y_axis) = y_axis {
+        y_axis
+    } else {
+        x_axis.y_axes().into_iter().next().ok_or_else(|| {
+            err::Error::from(format!("x-axis `{}` has no compatible y-axis", x_axis))
+        })?
+    };
+    let generator = query.get_with("filter_gen", |val| {
+        FilterGen::from_cla(val).map_err(|e| e.to_pretty())
+    })?;
+    let (default_width, default_height) = DEFAULT_RESOLUTION;
+    let width = query.get("width", default_width)?;
+    let height = query.get("height", default_height)?;
+    let time_windopt = Range::new(query.get_secs("from")?, query.get_secs("to")?);
+
+    let mut filters = if let Some(generator) = generator {
+        gen_filters_with(generator)?
+    } else {
+        gen_filters()?
+    };
+    let keys = line_keys(&filters);
+
+    let lines = query.get_with("lines", |val| {
+        let mut lines = BTSet::new();
+        for line in val.split(',').filter(|line| !line.is_empty()) {
+            let uid = keys
+                .iter()
+                .find(|(_, key)| key.as_str() == line)
+                .map(|(uid, _)| *uid)
+                .ok_or_else(|| format!("unknown filter key `{}`", line))?;
+            lines.insert(uid);
+        }
+        Ok(lines)
+    })?;
+    let active = if let Some(lines) = lines.as_ref() {
+        let mut active = filters.uid_map(false);
+        for (uid, is_active) in active.iter_mut() {
+            *is_active = lines.contains(uid)
+        }
+        active
+    } else {
+        filters.uid_map(true)
+    };
+    let is_active = |uid: &uid::Line| active.get(uid).cloned().unwrap_or(false);
+
+    let mut chart = charts::chart::Chart::new(&filters, x_axis, y_axis, active.clone())?;
+    chart.settings_mut().set_resolution((width, height).into());
+    let points = chart
+        .new_points(true, &mut filters, &time_windopt)
+        .chain_err(|| "while generating points")?;
+
+    let points: Vec<Value> = match points {
+        None => vec![],
+        Some(Points::Time(TimePoints::Size(points))) => points
+            .iter()
+            .map(|point| {
+                let values: serde_json::Map<String, Value> = point
+                    .vals
+                    .map
+                    .iter()
+                    .filter(|(uid, _)| is_active(uid))
+                    .filter_map(|(uid, size)| {
+                        keys.get(uid).map(|key| (key.clone(), json!(size.size)))
+                    })
+                    .collect();
+                json!({ "time": secs(point.key), "values": values })
+            })
+            .collect(),
+    };
+    let markers: Vec<Value> = Marker::of_data(&*data::get()?, 0)
+        .iter()
+        .map(marker_json)
+        .collect();
+
+    Ok(json!({
+        "x_axis": x_axis.desc(),
+        "y_axis": y_axis.desc(),
+        "filters": filters_json(&filters, &keys),
+        "points": points,
+        "markers": markers,
+    }))
+}
+
+/// Top allocation sites.
+fn sites(query: &Query) -> ApiRes<Value> {
+    check_loaded()?;
+    let top = query.get("top", DEFAULT_TOP)?;
+    let stats = data::stats::TraceStats::get(top)?;
+    let json = serde_json::to_value(&stats.top_sites)
+        .chain_err(|| "while serializing allocation sites to JSON")?;
+    Ok(json)
+}
+
+/// A single allocation, `uid` is the last segment of the request's `path`.
+fn alloc(path: &str) -> ApiRes<Value> {
+    check_loaded()?;
+    let uid = path.rsplit('/').next().unwrap_or("");
+    let uid: u64 = uid.parse().map_err(|_| {
+        ApiError::new(
+            StatusCode::BAD_REQUEST,
+            format!("illegal allocation UID `{}`", uid),
+        )
+    })?;
+    let uid = uid::Alloc::from(uid);
+    let data = data::get()?;
+    let alloc = data.get_alloc(uid).ok_or_else(|| {
+        ApiError::new(
+            StatusCode::NOT_FOUND,
+            format!("unknown allocation UID #{}", uid),
+        )
+    })?;
+    Ok(alloc_json(alloc))
+}
+
+/// JSON representation of some live allocations.
+fn live_json(live: &Live) -> Value {
+    json!({ "count": live.count, "bytes": live.bytes })
+}
+
+/// Heap snapshot at some time, diffed against a base time if any.
+fn snapshot(query: &Query) -> ApiRes<Value> {
+    check_loaded()?;
+    let time = query
+        .get_secs("at")?
+        .ok_or_else(|| ApiError::new(StatusCode::BAD_REQUEST, "missing query parameter `at`"))?;
+    let base = query.get_secs("base")?;
+    let snapshot = Snapshot::get(snapshot::Query::new(time, base))?;
+
+    let sites: Vec<Value> = snapshot
+        .sites
+        .iter()
+        .map(|site| {
+            let stacks: Vec<Value> = site
+                .stacks
+                .iter()
+                .map(|stack| {
+                    json!({
+                        "trace": stack.trace,
+                        "live": live_json(&stack.live),
+                        "base": base.map(|_| live_json(&stack.base)),
+                    })
+                })
+                .collect();
+            json!({
+                "site": site.site,
+                "live": live_json(&site.live),
+                "base": base.map(|_| live_json(&site.base)),
+                "stack_count": site.stack_count,
+                "stacks": stacks,
+            })
+        })
+        .collect();
+
+    Ok(json!({
+        "at": secs(time),
+        "base": base.map(secs),
+        "live": live_json(&snapshot.live),
+        "base_live": base.map(|_| live_json(&snapshot.base)),
+        "site_count": snapshot.site_count,
+        "sites": sites,
+    }))
+}
+
+/// Handlers for the API's endpoints.
+pub mod handlers {
+    use super::*;
+
+    /// Answers `/api/stats`.
+    pub fn stats(state: State) -> Pin<Box<HandlerFuture>> {
+        let query = Query::new(&state);
+        respond_blocking(state, move || super::stats(&query))
+    }
+    /// Answers `/api/filters`.
+    pub fn filters(state: State) -> Pin<Box<HandlerFuture>> {
+        respond_blocking(state, super::filters)
+    }
+    /// Answers `/api/points`.
+    pub fn points(state: State) -> Pin<Box<HandlerFuture>> {
+        let query = Query::new(&state);
+        respond_blocking(state, move || super::points(&query))
+    }
+    /// Answers `/api/sites`.
+    pub fn sites(state: State) -> Pin<Box<HandlerFuture>> {
+        let query = Query::new(&state);
+        respond_blocking(state, move || super::sites(&query))
+    }
+    /// Answers `/api/allocs/<uid>`.
+    pub fn alloc(state: State) -> Pin<Box<HandlerFuture>> {
+        let path = Uri::borrow_from(&state).path().to_string();
+        respond_blocking(state, move || super::alloc(&path))
+    }
+    /// Answers `/api/snapshot`.
+    pub fn snapshot(state: State) -> Pin<Box<HandlerFuture>> {
+        let query = Query::new(&state);
+        respond_blocking(state, move || super::snapshot(&query))
+    }
+}
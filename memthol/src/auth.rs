@@ -0,0 +1,238 @@
+/*<LICENSE>
+    This file is part of Memthol.
+
+    Copyright (C) 2020 OCamlPro.
+
+    Memthol is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Memthol is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Memthol.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Access token.
+//!
+//! When memthol runs with an access token, the HTTP routes and the websocket handshake reject all
+//! requests that do not carry it. The token is generated at startup and embedded in the URL
+//! memthol prints. Requests give it either as the [`QUERY_KEY`] query parameter, through the
+//! [`COOKIE`] cookie, which the server sets when a page is requested with a valid token, or in an
+//! `Authorization: Bearer <token>` header, which is what scripts and HTTP clients usually send.
+//! Cookies are shared by all the ports of a host, so the browser also sends it when opening the
+//! websocket.
+//!
+//! [`QUERY_KEY`]: constant.QUERY_KEY.html (The QUERY_KEY constant)
+//! [`COOKIE`]: constant.COOKIE.html (The COOKIE constant)
+
+use crate::prelude::*;
+
+use gotham::{
+    handler::HandlerFuture,
+    hyper::{
+        header::{self, HeaderMap, HeaderValue},
+        Body, Response, StatusCode, Uri,
+    },
+    middleware::{Middleware, NewMiddleware},
+    state::{FromState, State},
+};
+use std::pin::Pin;
+
+/// Name of the query parameter carrying the access token.
+pub const QUERY_KEY: &str = "token";
+
+/// Name of the cookie carrying the access token.
+pub const COOKIE: &str = "memthol_token";
+
+lazy_static! {
+    /// Access token, if any.
+    static ref TOKEN: sync::RwLock<Option<String>> = sync::RwLock::new(None);
+}
+
+/// Generates a fresh access token.
+pub fn fresh_token() -> String {
+    format!("{:032x}", base::rand::random::<u128>())
+}
+
+/// Sets the access token, `None` to let all requests through.
+pub fn set(token: Option<String>) -> Res<()> {
+    let mut current = TOKEN
+        .write()
+        .map_err(|e| {
+            let e: err::Error = e.to_string().into();
+            e
+        })
+        .chain_err(|| "while setting the access token")?;
+    *current = token;
+    Ok(())
+}
+
+/// True if an address is a loopback address.
+///
+/// # Examples
+///
+/// ```rust
+/// use memthol::auth::is_loopback;
+/// assert!(is_loopback("localhost"));
+/// assert!(is_loopback("127.0.0.1"));
+/// assert!(is_loopback("::1"));
+/// assert!(!is_loopback("0.0.0.0"));
+/// assert!(!is_loopback("192.168.0.12"));
+/// ```
+pub fn is_loopback(addr: &str) -> bool {
+    addr == "localhost"
+        || addr
+            .parse::<std::net::IpAddr>()
+            .map(|ip| ip.is_loopback())
+            .unwrap_or(false)
+}
+
+/// Extracts the access token from the value of a `Cookie` header, if any.
+///
+/// # Examples
+///
+/// ```rust
+/// use memthol::auth::token_of_cookies;
+/// assert_eq!(token_of_cookies("memthol_token=7f3a"), Some("7f3a"));
+/// assert_eq!(token_of_cookies("a=b; memthol_token=7f3a"), Some("7f3a"));
+/// assert_eq!(token_of_cookies("a=b"), None);
+/// ```
+pub fn token_of_cookies(cookies: &str) -> Option<&str> {
+    cookies
+        .split(';')
+        .filter_map(|cookie| {
+            let mut kv = cookie.trim().splitn(2, '=');
+            match (kv.next(), kv.next()) {
+                (Some(COOKIE), Some(token)) => Some(token),
+                _ => None,
+            }
+        })
+        .next()
+}
+
+/// Extracts the access token from the value of an `Authorization` header, if any.
+///
+/// Only the `Bearer` scheme is supported, the scheme is case-insensitive.
+///
+/// # Examples
+///
+/// ```rust
+/// use memthol::auth::token_of_authorization;
+/// assert_eq!(token_of_authorization("Bearer 7f3a"), Some("7f3a"));
+/// assert_eq!(token_of_authorization("bearer   7f3a "), Some("7f3a"));
+/// assert_eq!(token_of_authorization("Basic 7f3a"), None);
+/// assert_eq!(token_of_authorization("Bearer"), None);
+/// ```
+pub fn token_of_authorization(authorization: &str) -> Option<&str> {
+    let mut parts = authorization.trim().splitn(2, ' ');
+    let scheme = parts.next()?;
+    let token = parts.next()?.trim();
+    if scheme.eq_ignore_ascii_case("bearer") && !token.is_empty() {
+        Some(token)
+    } else {
+        None
+    }
+}
+
+/// Checks a token against the access token.
+///
+/// Always succeeds if there is no access token.
+pub fn check(candidate: Option<&str>) -> Res<bool> {
+    let token = TOKEN
+        .read()
+        .map_err(|e| {
+            let e: err::Error = e.to_string().into();
+            e
+        })
+        .chain_err(|| "while checking the access token")?;
+    let (token, candidate) = match (token.as_ref(), candidate) {
+        (None, _) => return Ok(true),
+        (Some(_), None) => return Ok(false),
+        (Some(token), Some(candidate)) => (token.as_bytes(), candidate.as_bytes()),
+    };
+    // Compare all the bytes whatever happens, so that timing does not leak the token.
+    let valid = candidate.len() == token.len()
+        && candidate
+            .iter()
+            .zip(token)
+            .fold(0, |acc, (lft, rgt)| acc | (lft ^ rgt))
+            == 0;
+    Ok(valid)
+}
+
+/// Checks the token of a request, given its query, `Cookie` header and `Authorization` header.
+///
+/// Returns whether the request is authorized, and whether it is because of the token in the query.
+pub fn check_request(
+    query: Option<&str>,
+    cookies: Option<&str>,
+    authorization: Option<&str>,
+) -> Res<(bool, bool)> {
+    let by_query = check(query.and_then(|query| crate::session::query_param(query, QUERY_KEY)))?;
+    let authorized = by_query
+        || check(cookies.and_then(token_of_cookies))?
+        || check(authorization.and_then(token_of_authorization))?;
+    Ok((authorized, by_query))
+}
+
+/// Middleware rejecting the HTTP requests that do not carry the access token.
+///
+/// Requests carrying a valid token in their query get a cookie with the token in their response,
+/// so that the resources the page loads next, and the websocket, get through.
+#[derive(Clone, Copy, Debug)]
+pub struct Guard;
+
+impl NewMiddleware for Guard {
+    type Instance = Self;
+    fn new_middleware(&self) -> gotham::anyhow::Result<Self> {
+        Ok(*self)
+    }
+}
+
+impl Middleware for Guard {
+    fn call<Chain>(self, state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+    where
+        Chain: FnOnce(State) -> Pin<Box<HandlerFuture>> + Send + 'static,
+    {
+        let query = Uri::borrow_from(&state).query();
+        let headers = HeaderMap::borrow_from(&state);
+        let cookies = headers
+            .get(header::COOKIE)
+            .and_then(|cookies| cookies.to_str().ok());
+        let authorization = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|authorization| authorization.to_str().ok());
+        let (status, msg) = match check_request(query, cookies, authorization) {
+            Ok((true, by_query)) => {
+                let cookie = query
+                    .filter(|_| by_query)
+                    .and_then(|query| crate::session::query_param(query, QUERY_KEY))
+                    .map(|token| format!("{}={}; Path=/; HttpOnly; SameSite=Strict", COOKIE, token))
+                    .and_then(|cookie| HeaderValue::from_str(&cookie).ok());
+                return Box::pin(async move {
+                    let (state, mut response) = chain(state).await?;
+                    if let Some(cookie) = cookie {
+                        let _ = response.headers_mut().insert(header::SET_COOKIE, cookie);
+                    }
+                    Ok((state, response))
+                });
+            }
+            Ok((false, _)) => (
+                StatusCode::UNAUTHORIZED,
+                "missing or invalid access token, use the URL memthol printed at startup".into(),
+            ),
+            Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_pretty()),
+        };
+        let mut response = Response::new(Body::from(msg));
+        *response.status_mut() = status;
+        let _ = response
+            .headers_mut()
+            .insert(header::CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+        Box::pin(std::future::ready(Ok((state, response))))
+    }
+}
@@ -50,9 +50,13 @@
 #[macro_use]
 pub mod prelude;
 
+pub mod api;
 pub mod assets;
+pub mod auth;
+pub mod metrics;
 pub mod msg;
 pub mod router;
+pub mod session;
 pub mod socket;
 
 use prelude::*;
@@ -145,4 +149,148 @@ pub mod clap {
             std::process::exit(code)
         }
     }
+
+    /// Handles path-remapping-related CLAs.
+    ///
+    /// The `<from>=<to>` rules in `rules` are tried first, in order, then the prefixes in `strip`.
+    /// Exits with code `2` if a rule is illegal.
+    pub fn remap(rules: Vec<&str>, strip: Vec<&str>, source_root: Option<&str>) {
+        use charts::alloc_data::remap;
+
+        let res = rules
+            .into_iter()
+            .map(remap::Rule::parse)
+            .collect::<Res<Vec<_>>>()
+            .and_then(|mut rules| {
+                rules.extend(strip.into_iter().map(remap::Rule::strip));
+                remap::set(remap::Remap::new(rules, source_root.map(Into::into)))
+            })
+            .chain_err(|| "while setting up path remapping");
+        if let Err(e) = res {
+            exit_on_error(e)
+        }
+    }
+
+    /// Logs an error and `std::process::exit`s with code `2`.
+    fn exit_on_error(e: err::Error) -> ! {
+        for (idx, line) in e.to_pretty().lines().enumerate() {
+            if idx == 0 {
+                log::error!("|===| {}", line)
+            } else {
+                log::error!("| {}", line)
+            }
+        }
+        log::error!("|===|");
+        std::process::exit(2)
+    }
+
+    /// Handles the `check` subcommand.
+    ///
+    /// Checks a memtrace CTF file and prints a report of all the problems found. If `recover` is
+    /// some path, the longest prefix of the trace that has no problem is written there as an
+    /// uncompressed trace.
+    ///
+    /// This function `std::process::exit`s with code `0` if no problem was found, `1` if problems
+    /// were found, and `2` if the trace could not be read or the recovered trace could not be
+    /// written.
+    pub fn check(target: &str, recover: Option<&str>) -> ! {
+        let res = ctf::compress::read_file(target, |_| ()).and_then(|bytes| {
+            let report = ctf::check::check(&bytes);
+            println!("{}", report);
+
+            if let Some(recover) = recover {
+                if report.valid_len == 0 {
+                    bail!("cannot recover anything from `{}`", target)
+                }
+                std::fs::write(recover, &bytes[0..report.valid_len])
+                    .chain_err(|| format!("while writing recovered trace `{}`", recover))?;
+                println!(
+                    "recovered {} of {} bytes in `{}`",
+                    report.valid_len, report.len, recover
+                );
+            }
+
+            Ok(report.is_ok())
+        });
+
+        match res {
+            Ok(true) => std::process::exit(0),
+            Ok(false) => std::process::exit(1),
+            Err(e) => exit_on_error(e),
+        }
+    }
+
+    /// Handles the `stats` subcommand.
+    ///
+    /// Loads a memtrace CTF file, or the most recent one in a directory, and prints statistics
+    /// about it as a human-readable report, or as JSON if `json`. Only the `top` allocation sites
+    /// that allocate the most bytes are reported.
+    ///
+    /// This function `std::process::exit`s with code `0` on success, and `2` if the trace could
+    /// not be loaded.
+    pub fn stats(target: &str, json: bool, top: usize) -> ! {
+        let res = charts::data::load(target)
+            .and_then(|()| charts::data::stats::TraceStats::get(top))
+            .and_then(|stats| {
+                if json {
+                    let json = serde_json::to_string_pretty(&stats)
+                        .chain_err(|| "while serializing statistics to JSON")?;
+                    println!("{}", json)
+                } else {
+                    println!("{}", stats)
+                }
+                Ok(())
+            });
+
+        match res {
+            Ok(()) => std::process::exit(0),
+            Err(e) => exit_on_error(e),
+        }
+    }
+
+    /// Handles the `leaks` subcommand.
+    ///
+    /// Loads a memtrace CTF file, or the most recent one in a directory, and prints the `top`
+    /// allocation sites suspected to leak, along with a filter capturing each of them. See
+    /// [`charts::leak`] for the criteria used.
+    ///
+    /// This function `std::process::exit`s with code `0` on success, and `2` if the trace could
+    /// not be loaded.
+    ///
+    /// [`charts::leak`]: ../../charts/leak/index.html (The leak module)
+    pub fn leaks(target: &str, top: usize) -> ! {
+        let res = charts::data::load(target)
+            .and_then(|()| charts::leak::Report::get(top))
+            .map(|report| println!("{}", report));
+
+        match res {
+            Ok(()) => std::process::exit(0),
+            Err(e) => exit_on_error(e),
+        }
+    }
+
+    /// Handles the `budget` subcommand.
+    ///
+    /// Loads a memtrace CTF file, or the most recent one in a directory, checks the memory budget
+    /// from file `budget` against it, and prints a report. See [`charts::data::budget`] for the
+    /// syntax of budget files.
+    ///
+    /// This function `std::process::exit`s with code `0` if the budget is respected, `1` if it is
+    /// exceeded, and `2` if the budget or the trace could not be loaded.
+    ///
+    /// [`charts::data::budget`]: ../../charts/data/budget/index.html (The budget module)
+    pub fn budget(budget: &str, target: &str) -> ! {
+        let res = charts::data::budget::Budget::of_file(budget).and_then(|budget| {
+            charts::data::load(target)?;
+            let report = budget.check_global()?;
+            println!("{}", report);
+            Ok(report.is_ok())
+        });
+
+        match res {
+            Ok(true) => std::process::exit(0),
+            Ok(false) => std::process::exit(1),
+            Err(e) => exit_on_error(e),
+        }
+    }
 }
@@ -96,6 +96,23 @@ pub fn main() {
             "filter generation heuristic, get help with `--filter_gen help`"
         )
 
+        // Path remapping stuff.
+        (@arg REMAP:
+            --remap +takes_value +multiple number_of_values(1) !required
+            "`<from>=<to>`, replaces prefix `<from>` of the files of the locations by `<to>`; \
+            can be used several times, the first rule that applies is used"
+        )
+        (@arg STRIP_PREFIX:
+            --strip_prefix +takes_value +multiple number_of_values(1) !required
+            "prefix to strip from the files of the locations, for instance `_build/default/`; \
+            can be used several times, tried after the `--remap` rules"
+        )
+        (@arg SOURCE_ROOT:
+            --source_root +takes_value !required
+            "directory containing the sources of the program profiled, \
+            the inspector displays the source line of the locations found there"
+        )
+
         // Server-related stuff.
 
         (@arg ADDR:
@@ -109,13 +126,81 @@ pub fn main() {
             { usize_validator }
             "the port to serve the UI at"
         )
+        (@arg TOKEN:
+            --token !required
+            "requires an access token, generated at startup and embedded in the printed URL; \
+            on by default when the address is not a loopback address"
+        )
+        (@arg NO_TOKEN:
+            --no_token !required conflicts_with[TOKEN]
+            "does not require an access token, even if the address is not a loopback address"
+        )
+        (@arg LEGACY_WS:
+            --legacy_ws !required
+            "also serves the websocket on its own port, `port + 1`, as older versions did"
+        )
 
         // Directory or CTF file.
 
         (@arg INPUT:
             !required
             default_value(default::INPUT)
-            "path to either a directory containing memthol's dump files, or a memtrace CTF file"
+            "path to either a directory containing memthol's dump files, a memtrace CTF file, \
+            or a directory containing memtrace CTF files; CTF files can be gzip or zstd compressed"
+        )
+
+        // Subcommands.
+
+        (@subcommand check =>
+            (about: "checks a memtrace CTF file and reports all the problems found")
+            (@arg RECOVER:
+                --recover +takes_value !required
+                "writes the longest prefix of the trace that has no problem to this file"
+            )
+            (@arg INPUT:
+                +required
+                "memtrace CTF file to check, can be gzip or zstd compressed"
+            )
+        )
+        (@subcommand stats =>
+            (about: "loads a memtrace CTF file and prints statistics about it, without serving the UI")
+            (@arg JSON: --json "prints the statistics as JSON")
+            (@arg TOP:
+                --top +takes_value !required
+                default_value("10")
+                { usize_validator }
+                "number of allocation sites to display, most allocated bytes first"
+            )
+            (@arg INPUT:
+                +required
+                "memtrace CTF file, or directory containing memtrace CTF files"
+            )
+        )
+        (@subcommand leaks =>
+            (about: "loads a memtrace CTF file and reports the allocation sites that look like they leak, \
+                without serving the UI")
+            (@arg TOP:
+                --top +takes_value !required
+                default_value("10")
+                { usize_validator }
+                "number of suspects to display"
+            )
+            (@arg INPUT:
+                +required
+                "memtrace CTF file, or directory containing memtrace CTF files"
+            )
+        )
+        (@subcommand budget =>
+            (about: "checks a memory budget against a memtrace CTF file, \
+                fails if the budget is exceeded")
+            (@arg BUDGET:
+                +required
+                "budget file, one `<metric> [<loc>] <= <bound>` assertion per line"
+            )
+            (@arg INPUT:
+                +required
+                "memtrace CTF file, or directory containing memtrace CTF files"
+            )
         )
     )
     .get_matches();
@@ -128,10 +213,48 @@ pub fn main() {
     };
     let log = matches.occurrences_of("LOG") > 0;
     let open = matches.occurrences_of("OPEN") > 0;
+    let legacy_ws = matches.occurrences_of("LEGACY_WS") > 0;
 
     let verb = matches.occurrences_of("VERB");
     init_logger(verb);
 
+    memthol::clap::remap(
+        matches.values_of("REMAP").map(Iterator::collect).unwrap_or_default(),
+        matches
+            .values_of("STRIP_PREFIX")
+            .map(Iterator::collect)
+            .unwrap_or_default(),
+        matches.value_of("SOURCE_ROOT"),
+    );
+
+    if let Some(matches) = matches.subcommand_matches("check") {
+        let target = matches.value_of("INPUT").expect("required argument");
+        memthol::clap::check(target, matches.value_of("RECOVER"))
+    }
+    if let Some(matches) = matches.subcommand_matches("stats") {
+        let target = matches.value_of("INPUT").expect("required argument");
+        let top = {
+            use std::str::FromStr;
+            let top = matches.value_of("TOP").expect("argument with default");
+            usize::from_str(top).expect("argument with validator")
+        };
+        memthol::clap::stats(target, matches.occurrences_of("JSON") > 0, top)
+    }
+    if let Some(matches) = matches.subcommand_matches("leaks") {
+        let target = matches.value_of("INPUT").expect("required argument");
+        let top = {
+            use std::str::FromStr;
+            let top = matches.value_of("TOP").expect("argument with default");
+            usize::from_str(top).expect("argument with validator")
+        };
+        memthol::clap::leaks(target, top)
+    }
+    if let Some(matches) = matches.subcommand_matches("budget") {
+        let budget = matches.value_of("BUDGET").expect("required argument");
+        let target = matches.value_of("INPUT").expect("required argument");
+        memthol::clap::budget(budget, target)
+    }
+
     let target = matches.value_of("INPUT").expect("argument with default");
 
     let filter_gen_args = matches
@@ -140,15 +263,36 @@ pub fn main() {
     memthol::clap::filter_gen(filter_gen_args);
 
     let path = format!("{}:{}", addr, port);
+    let loopback = memthol::auth::is_loopback(addr);
+    let token = if matches.occurrences_of("TOKEN") > 0
+        || (matches.occurrences_of("NO_TOKEN") == 0 && !loopback)
+    {
+        Some(memthol::auth::fresh_token())
+    } else {
+        None
+    };
+    let secured = token.is_some();
+    base::unwrap_or! {
+        memthol::auth::set(token.clone()), exit
+    }
+    let url = match token {
+        Some(token) => format!("http://{}/?{}={}", path, memthol::auth::QUERY_KEY, token),
+        None => format!("http://{}", path),
+    };
+
     println!("|===| Starting");
-    println!("| url: http://{}", path);
+    println!("| url: {}", url);
     println!("| target: `{}`", target);
+    if !loopback && !secured {
+        println!("| warning: serving at `{}` with no access token,", addr);
+        println!("|          anyone who can reach this address can see the profile data");
+    }
     println!("|===|");
     println!();
 
     error_handler.handle_new_errors();
 
-    let router = memthol::router::new();
+    let router = memthol::router::new(log);
 
     log::info!("starting data monitoring");
     base::unwrap_or! {
@@ -157,15 +301,17 @@ pub fn main() {
 
     error_handler.handle_new_errors();
 
-    log::info!("starting socket listeners");
-    base::unwrap_or! {
-        memthol::socket::spawn_server(addr, port + 1, log), exit
+    if legacy_ws {
+        log::info!("starting socket listeners");
+        base::unwrap_or! {
+            memthol::socket::spawn_server(addr, port + 1, log), exit
+        }
     }
 
     error_handler.handle_new_errors();
 
     if open {
-        open_in_background(&path)
+        open_in_background(url)
     }
 
     log::info!("starting gotham server");
@@ -174,8 +320,7 @@ pub fn main() {
     error_handler.error_watch_loop()
 }
 
-fn open_in_background(path: &str) {
-    let path = format!("http://{}", path);
+fn open_in_background(path: String) {
     std::thread::spawn(move || match open::that(&path) {
         Ok(status) => {
             if !status.success() {
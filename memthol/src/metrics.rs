@@ -0,0 +1,259 @@
+/*<LICENSE>
+    This file is part of Memthol.
+
+    Copyright (C) 2020 OCamlPro.
+
+    Memthol is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Memthol is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Memthol.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Prometheus-style metrics over the data memthol is serving.
+//!
+//! Served at `/metrics` in the Prometheus text exposition format, so that memthol can act as a
+//! memory telemetry exporter when watching a live run. All metrics are computed at the *current
+//! time* of the run, *i.e.* the latest timestamp in the trace, and times are in seconds since the
+//! start of the run. Per-filter metrics use the filters generated for the trace, labeled with the
+//! filter's name and key (see the [`api` module]).
+//!
+//! [`api` module]: ../api/index.html (The api module)
+
+use crate::prelude::*;
+
+use charts::{data, filter::Filters};
+use gotham::{
+    handler::HandlerFuture,
+    hyper::{
+        header::{self, HeaderValue},
+        Body, Response, StatusCode,
+    },
+    state::State,
+};
+use std::pin::Pin;
+use time::DurationExt;
+
+/// Window over which the allocation rate is computed, in seconds of run time.
+pub const RATE_WINDOW_SECS: u64 = 1;
+
+/// Prometheus metric types.
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    /// Value that can go up and down.
+    Gauge,
+    /// Value that only goes up during a run.
+    Counter,
+}
+
+base::implement! {
+    impl Display for Kind {
+        |&self, fmt| match self {
+            Self::Gauge => write!(fmt, "gauge"),
+            Self::Counter => write!(fmt, "counter"),
+        }
+    }
+}
+
+/// Metrics in the Prometheus text exposition format.
+struct Metrics {
+    /// Text of the metrics.
+    text: String,
+}
+impl Metrics {
+    /// Constructor.
+    fn new() -> Self {
+        Self {
+            text: String::with_capacity(2048),
+        }
+    }
+
+    /// Adds the header of a metric.
+    fn header(&mut self, name: &str, kind: Kind, help: &str) {
+        use std::fmt::Write;
+        let _ = writeln!(self.text, "# HELP {} {}", name, help);
+        let _ = writeln!(self.text, "# TYPE {} {}", name, kind);
+    }
+
+    /// Adds a sample of a metric.
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: impl fmt::Display) {
+        use std::fmt::Write;
+        let _ = write!(self.text, "{}", name);
+        for (idx, (label, label_value)) in labels.iter().enumerate() {
+            let sep = if idx == 0 { '{' } else { ',' };
+            let _ = write!(self.text, "{}{}=\"{}\"", sep, label, escape(label_value));
+        }
+        if !labels.is_empty() {
+            self.text.push('}')
+        }
+        let _ = writeln!(self.text, " {}", value);
+    }
+
+    /// Adds a metric with a single, unlabeled sample.
+    fn single(&mut self, name: &str, kind: Kind, help: &str, value: impl fmt::Display) {
+        self.header(name, kind, help);
+        self.sample(name, &[], value)
+    }
+}
+
+/// Escapes a label value.
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Computes the metrics, `None` if the data is not loaded yet.
+fn metrics() -> Res<Option<String>> {
+    let data = data::get()?;
+    if data.stats().is_none() {
+        return Ok(None);
+    }
+    let (filters, _) = Filters::auto_gen(&data, charts::filter::gen::get())?;
+    let keys = crate::api::line_keys(&filters);
+
+    let now = *data.current_time();
+    let window = time::SinceStart::from_secs(RATE_WINDOW_SECS);
+    let window_start = if now > window {
+        now - window
+    } else {
+        time::SinceStart::zero()
+    };
+
+    let (mut alloc_count, mut allocated_bytes) = (0u64, 0u64);
+    let (mut live_count, mut live_bytes) = (0u64, 0u64);
+    let mut window_bytes = 0u64;
+    let mut filter_live_bytes: BTMap<uid::Line, u64> = filters.uid_map(0);
+
+    for alloc in data.iter_allocs().filter(|alloc| alloc.toc <= now) {
+        let bytes = alloc.real_size as u64;
+        alloc_count += 1;
+        allocated_bytes += bytes;
+        if alloc.toc >= window_start {
+            window_bytes += bytes
+        }
+        if alloc.tod.map(|tod| tod > now).unwrap_or(true) {
+            live_count += 1;
+            live_bytes += bytes;
+            let line = filters
+                .filters()
+                .iter()
+                .find(|filter| filter.apply(&now, alloc))
+                .map(|filter| filter.spec().uid())
+                .unwrap_or_else(|| filters.catch_all().uid());
+            for line in &[line, filters.everything().uid()] {
+                if let Some(line_bytes) = filter_live_bytes.get_mut(line) {
+                    *line_bytes += bytes
+                }
+            }
+        }
+    }
+    let window_secs = (now - window_start).as_duration().as_secs_f64();
+    let rate = if window_secs > 0. {
+        window_bytes as f64 / window_secs
+    } else {
+        0.
+    };
+
+    let mut metrics = Metrics::new();
+    metrics.single(
+        "memthol_run_duration_seconds",
+        Kind::Gauge,
+        "Current time of the run.",
+        now.as_duration().as_secs_f64(),
+    );
+    metrics.single(
+        "memthol_allocations_total",
+        Kind::Counter,
+        "Number of allocations since the start of the run.",
+        alloc_count,
+    );
+    metrics.single(
+        "memthol_allocated_bytes_total",
+        Kind::Counter,
+        "Total size of the allocations since the start of the run, in bytes.",
+        allocated_bytes,
+    );
+    metrics.single(
+        "memthol_allocation_rate_bytes_per_second",
+        Kind::Gauge,
+        &format!(
+            "Bytes allocated per second over the last {} second(s) of the run.",
+            RATE_WINDOW_SECS
+        ),
+        rate,
+    );
+    metrics.single(
+        "memthol_live_allocations",
+        Kind::Gauge,
+        "Number of allocations alive at the current time of the run.",
+        live_count,
+    );
+    metrics.single(
+        "memthol_live_bytes",
+        Kind::Gauge,
+        "Size of the allocations alive at the current time of the run, in bytes.",
+        live_bytes,
+    );
+
+    let name = "memthol_filter_live_bytes";
+    metrics.header(
+        name,
+        Kind::Gauge,
+        "Size of the allocations alive at the current time of the run for each filter, in bytes.",
+    );
+    let specs = Some(filters.everything())
+        .into_iter()
+        .chain(filters.filters().iter().map(|filter| filter.spec()))
+        .chain(Some(filters.catch_all()));
+    for spec in specs {
+        let uid = spec.uid();
+        let key = keys.get(&uid).map(String::as_str).unwrap_or("");
+        let bytes = filter_live_bytes.get(&uid).cloned().unwrap_or(0);
+        metrics.sample(name, &[("filter", spec.name()), ("key", key)], bytes)
+    }
+
+    Ok(Some(metrics.text))
+}
+
+/// Answers `/metrics`.
+///
+/// Computing the metrics goes over all the allocations, so it runs on the blocking thread pool.
+pub fn handler(state: State) -> Pin<Box<HandlerFuture>> {
+    Box::pin(async move {
+        let res = match tokio::task::spawn_blocking(metrics).await {
+            Ok(res) => res,
+            Err(e) => Err(err::Error::from(format!("metrics handler failed: {}", e))),
+        };
+        Ok(respond(state, res))
+    })
+}
+
+/// Turns the metrics, if any, into a response.
+fn respond(state: State, res: Res<Option<String>>) -> (State, Response<Body>) {
+    let (status, body) = match res {
+        Ok(Some(text)) => (StatusCode::OK, text),
+        Ok(None) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "the data is not loaded yet\n".into(),
+        ),
+        Err(e) => {
+            log::error!("failed to compute metrics: {}", e.to_pretty());
+            (StatusCode::INTERNAL_SERVER_ERROR, format!("{}\n", e.to_pretty()))
+        }
+    };
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("text/plain; version=0.0.4"),
+    );
+    (state, response)
+}
@@ -39,8 +39,47 @@ pub mod net {
 
     pub use tungstenite::{protocol::CloseFrame, Message as Msg};
 
-    /// Type alias for a tungstenite websocket for a TCP stream.
-    pub type WebSocket = tungstenite::WebSocket<TcpStream>;
+    /// Stream websockets work on.
+    pub enum Stream {
+        /// Connection on the websocket port.
+        Tcp(TcpStream),
+        /// Local end of a connection upgraded by the HTTP server, see [`socket::upgrade`].
+        ///
+        /// [`socket::upgrade`]: ../../socket/upgrade/index.html (The socket::upgrade module)
+        Local(LocalStream),
+    }
+
+    /// Local end of a connection upgraded by the HTTP server, a unix socket.
+    #[cfg(unix)]
+    pub type LocalStream = std::os::unix::net::UnixStream;
+    /// Local end of a connection upgraded by the HTTP server, a loopback TCP connection.
+    #[cfg(not(unix))]
+    pub type LocalStream = TcpStream;
+    impl std::io::Read for Stream {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            match self {
+                Self::Tcp(stream) => stream.read(buf),
+                Self::Local(stream) => stream.read(buf),
+            }
+        }
+    }
+    impl std::io::Write for Stream {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            match self {
+                Self::Tcp(stream) => stream.write(buf),
+                Self::Local(stream) => stream.write(buf),
+            }
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            match self {
+                Self::Tcp(stream) => stream.flush(),
+                Self::Local(stream) => stream.flush(),
+            }
+        }
+    }
+
+    /// Type alias for a tungstenite websocket.
+    pub type WebSocket = tungstenite::WebSocket<Stream>;
 }
 
 /// Type of the result of receiving messages from the client.
@@ -63,14 +63,35 @@ pub mod handlers {
 }
 
 /// Creates the router.
-pub fn new() -> Router {
-    use gotham::router::builder::{build_simple_router, DefineSingleRoute, DrawRoutes};
-    build_simple_router(|route| {
+///
+/// All routes go through the [`auth::Guard`] middleware, which checks the access token if any. The
+/// `log` flag activates socket logging for the websockets opened on `/ws`.
+///
+/// [`auth::Guard`]: ../auth/struct.Guard.html (The Guard middleware)
+pub fn new(log: bool) -> Router {
+    use gotham::{
+        pipeline::{new_pipeline, single::single_pipeline},
+        router::builder::{build_router, DefineSingleRoute, DrawRoutes},
+    };
+    let (chain, pipelines) = single_pipeline(new_pipeline().add(crate::auth::Guard).build());
+    build_router(chain, pipelines, |route| {
         route.get("/").to(handlers::index_handler);
 
         route.get("index.html").to(handlers::index_handler);
         route.get("favicon.png").to(handlers::favicon);
         route.get("client_bg.wasm").to(handlers::client_wasm);
         route.get("client.js").to(handlers::client_js);
+        route
+            .get("ws")
+            .to(move |state| crate::socket::upgrade::handler(log, state));
+
+        route.get("/api/stats").to(crate::api::handlers::stats);
+        route.get("/api/filters").to(crate::api::handlers::filters);
+        route.get("/api/points").to(crate::api::handlers::points);
+        route.get("/api/sites").to(crate::api::handlers::sites);
+        route.get("/api/allocs/:uid").to(crate::api::handlers::alloc);
+        route.get("/api/snapshot").to(crate::api::handlers::snapshot);
+
+        route.get("/metrics").to(crate::metrics::handler);
     })
 }
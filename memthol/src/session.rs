@@ -0,0 +1,127 @@
+/*<LICENSE>
+    This file is part of Memthol.
+
+    Copyright (C) 2020 OCamlPro.
+
+    Memthol is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Memthol is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Memthol.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Client sessions.
+//!
+//! Each client connection works on a [`Charts`] session. When a client disconnects, its session is
+//! kept here for [`TTL`] so that the client can reattach it when it reconnects, typically after a
+//! page reload or a network hiccup. At most [`MAX_DETACHED`] sessions are kept, the oldest ones are
+//! dropped first. Sessions are identified by a random token the server sends to
+//! the client, which the client stores and gives back when reconnecting.
+//!
+//! [`Charts`]: ../../charts/struct.Charts.html (The Charts struct)
+//! [`TTL`]: constant.TTL.html (The TTL constant)
+//! [`MAX_DETACHED`]: constant.MAX_DETACHED.html (The MAX_DETACHED constant)
+
+use crate::prelude::*;
+
+/// Time during which the session of a disconnected client is kept.
+pub const TTL: time::Duration = time::Duration::from_secs(30 * 60);
+
+/// Maximum number of sessions of disconnected clients kept.
+pub const MAX_DETACHED: usize = 32;
+
+/// Name of the query parameter carrying the session token in websocket requests.
+pub const QUERY_KEY: &str = "session";
+
+lazy_static! {
+    /// Sessions of the disconnected clients, and the time at which they disconnected.
+    static ref DETACHED: sync::Mutex<HMap<String, (Charts, time::Instant)>> =
+        sync::Mutex::new(HMap::new());
+}
+
+/// Lock over the detached sessions.
+fn detached<'a>() -> Res<sync::MutexGuard<'a, HMap<String, (Charts, time::Instant)>>> {
+    DETACHED
+        .lock()
+        .map_err(|e| {
+            let e: err::Error = e.to_string().into();
+            e
+        })
+        .chain_err(|| "while accessing the client sessions")
+}
+
+/// Drops the expired sessions.
+fn purge(detached: &mut HMap<String, (Charts, time::Instant)>) {
+    let now = time::Instant::now();
+    detached.retain(|_, (_, since)| now.duration_since(*since) < TTL);
+}
+
+/// Generates a fresh session token.
+pub fn fresh_token() -> String {
+    format!("{:032x}", base::rand::random::<u128>())
+}
+
+/// Extracts the value of a parameter from the query of a websocket request, if any.
+///
+/// # Examples
+///
+/// ```rust
+/// use memthol::session::{query_param, QUERY_KEY};
+/// assert_eq!(query_param("session=7f3a", QUERY_KEY), Some("7f3a"));
+/// assert_eq!(query_param("foo=bar&session=7f3a", QUERY_KEY), Some("7f3a"));
+/// assert_eq!(query_param("session=7f3a&view=01ab", "view"), Some("01ab"));
+/// assert_eq!(query_param("session=", QUERY_KEY), None);
+/// assert_eq!(query_param("foo=bar", QUERY_KEY), None);
+/// ```
+pub fn query_param<'a>(query: &'a str, key: &str) -> Option<&'a str> {
+    query
+        .split('&')
+        .filter_map(|pair| {
+            let mut kv = pair.splitn(2, '=');
+            match (kv.next(), kv.next()) {
+                (Some(k), Some(value)) if k == key && !value.is_empty() => Some(value),
+                _ => None,
+            }
+        })
+        .next()
+}
+
+/// Reattaches a detached session.
+///
+/// Returns `None` if there is no such session, or if it expired.
+pub fn attach(token: &str) -> Res<Option<Charts>> {
+    let mut detached = detached()?;
+    purge(&mut detached);
+    Ok(detached.remove(token).map(|(charts, _)| charts))
+}
+
+/// Detaches a session, keeps it for [`TTL`].
+///
+/// Drops the oldest sessions if there are already [`MAX_DETACHED`] detached sessions.
+///
+/// [`TTL`]: constant.TTL.html (The TTL constant)
+/// [`MAX_DETACHED`]: constant.MAX_DETACHED.html (The MAX_DETACHED constant)
+pub fn detach(token: String, charts: Charts) -> Res<()> {
+    let mut detached = detached()?;
+    purge(&mut detached);
+    while detached.len() >= MAX_DETACHED {
+        let oldest = detached
+            .iter()
+            .min_by_key(|(_, (_, since))| *since)
+            .map(|(token, _)| token.clone());
+        if let Some(oldest) = oldest {
+            detached.remove(&oldest);
+        } else {
+            break;
+        }
+    }
+    detached.insert(token, (charts, time::Instant::now()));
+    Ok(())
+}
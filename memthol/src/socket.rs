@@ -18,9 +18,22 @@
 */
 
 //! Websockets used by the server to communicate with the clients.
+//!
+//! Clients open their websocket on the `/ws` route of the HTTP server, see the [`upgrade`] module.
+//! The server can also listen for websockets on a separate port, see [`spawn_server`].
+//!
+//! [`upgrade`]: upgrade/index.html (The upgrade module)
+//! [`spawn_server`]: fn.spawn_server.html (The spawn_server function)
 
 use crate::prelude::*;
 
+use tungstenite::{
+    handshake::server::{ErrorResponse, Request, Response},
+    http::StatusCode,
+};
+
+pub mod upgrade;
+
 /// Creates a websocket server at some address.
 fn new_server(addr: &str, port: usize) -> Res<net::TcpListener> {
     let server = net::TcpListener::bind(&format!("{}:{}", addr, port))
@@ -29,13 +42,16 @@ fn new_server(addr: &str, port: usize) -> Res<net::TcpListener> {
 }
 
 /// Spawns a `Handler` for each incoming connection request.
+///
+/// Requests for which the handler cannot be created, for instance because they do not carry the
+/// access token, are dropped.
 fn handle_requests(log: bool, server: net::TcpListener) {
     for stream in server.incoming().filter_map(Result::ok) {
-        let mut handler = base::unwrap_or! {
+        let handler = base::unwrap_or! {
             Handler::new(log, stream).chain_err(|| "while creating request handler"),
             {
                 log::error!("failed to start request handler");
-                return ()
+                continue
             }
         };
         std::thread::spawn(move || handler.run());
@@ -43,7 +59,7 @@ fn handle_requests(log: bool, server: net::TcpListener) {
     }
 }
 
-/// Spawns the server that listens for connection requests.
+/// Spawns a server that listens for websocket connection requests on a port of its own.
 pub fn spawn_server(addr: &str, port: usize, log: bool) -> Res<()> {
     let server = new_server(addr, port)?;
     std::thread::spawn(move || handle_requests(log, server));
@@ -59,6 +75,53 @@ base::new_time_stats! {
     }
 }
 
+/// Information carried by the handshake request of a websocket.
+#[derive(Debug, Clone, Default)]
+pub struct Handshake {
+    /// Token of the session to reattach, if any.
+    token: Option<String>,
+    /// Id of the view to open, if any.
+    view: Option<String>,
+}
+impl Handshake {
+    /// Constructor.
+    ///
+    /// Fails with the response to send back if the request does not carry the access token, if
+    /// any.
+    pub fn new(request: &Request) -> Result<Self, Box<ErrorResponse>> {
+        let query = request.uri().query();
+        let cookies = request
+            .headers()
+            .get(tungstenite::http::header::COOKIE)
+            .and_then(|cookies| cookies.to_str().ok());
+        let authorization = request
+            .headers()
+            .get(tungstenite::http::header::AUTHORIZATION)
+            .and_then(|authorization| authorization.to_str().ok());
+        let (status, msg) = match crate::auth::check_request(query, cookies, authorization) {
+            Ok((true, _)) => {
+                let param = |key| {
+                    query
+                        .and_then(|query| crate::session::query_param(query, key))
+                        .map(String::from)
+                };
+                return Ok(Self {
+                    token: param(crate::session::QUERY_KEY),
+                    view: param(charts::view::QUERY_KEY),
+                });
+            }
+            Ok((false, _)) => (
+                StatusCode::UNAUTHORIZED,
+                "missing or invalid access token".into(),
+            ),
+            Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_pretty()),
+        };
+        let mut error = ErrorResponse::new(Some(msg));
+        *error.status_mut() = status;
+        Err(Box::new(error))
+    }
+}
+
 /// Maintains a socket to a client and some information such as the client's IP.
 pub struct Com {
     /// IP addresse.
@@ -79,14 +142,14 @@ impl Com {
     ///
     /// The `log` flag, if `true`, makes the constructor create a log file in the current directory.
     /// It will contain a log of all the interactions with this client.
-    pub fn new(log: bool, ping_label: Vec<u8>, socket: net::WebSocket) -> Res<Self> {
+    pub fn new(
+        log: bool,
+        ping_label: Vec<u8>,
+        socket: net::WebSocket,
+        ip: net::IpAddr,
+    ) -> Res<Self> {
         let ping_msg = tungstenite::Message::Ping(ping_label);
 
-        let ip = socket
-            .get_ref()
-            .peer_addr()
-            .map_err(|e| format!("failed to retrieve client IP: {}", e))?;
-
         let log = if log {
             use std::fs::OpenOptions;
             let path = format!("log_{}", ip);
@@ -265,6 +328,8 @@ base::new_time_stats! {
 pub struct Handler {
     /// Sends/receives messages to/from the client.
     com: Com,
+    /// Token of the client's session.
+    session: String,
     /// The charts of the client.
     charts: Charts,
     /// Stores the result of receiving messages from the client.
@@ -275,6 +340,8 @@ pub struct Handler {
     frame_span: time::Duration,
     /// Label for ping messages.
     ping_label: Vec<u8>,
+    /// Last trace list sent to the client, and the trace that was active at that point.
+    traces: Option<(Vec<charts::prelude::TraceInfo>, Option<String>)>,
 
     instance_prof: HandlerProf,
     total_prof: HandlerProf,
@@ -282,9 +349,36 @@ pub struct Handler {
 }
 
 impl Handler {
-    /// Constructor from a request and a dump directory.
+    /// Constructor from a connection request.
+    ///
+    /// Performs the websocket handshake, fails if the request does not carry the access token, if
+    /// any.
     pub fn new(log: bool, stream: std::net::TcpStream) -> Res<Self> {
-        let socket = tungstenite::server::accept(stream).map_err(|e| e.to_string())?;
+        let ip = stream
+            .peer_addr()
+            .map_err(|e| format!("failed to retrieve client IP: {}", e))?;
+        let mut handshake = Handshake::default();
+        let stream = net::Stream::Tcp(stream);
+        let socket = tungstenite::server::accept_hdr(stream, |request: &Request, response| {
+            handshake = Handshake::new(request).map_err(|error| *error)?;
+            Ok::<Response, _>(response)
+        })
+        .map_err(|e| e.to_string())?;
+        Self::of_socket(log, socket, ip, handshake)
+    }
+
+    /// Constructor from a websocket on which the handshake was performed.
+    ///
+    /// If the handshake carries the id of a shared view, a new session is created from this view.
+    /// Otherwise, if it carries the token of a detached session, the session is reattached. A new
+    /// session is created in all other cases.
+    pub fn of_socket(
+        log: bool,
+        socket: net::WebSocket,
+        ip: net::IpAddr,
+        handshake: Handshake,
+    ) -> Res<Self> {
+        let Handshake { token, view } = handshake;
 
         let instance_prof = HandlerProf::new();
         let total_prof = HandlerProf::new();
@@ -294,7 +388,7 @@ impl Handler {
         //     .chain_err(|| "while splitting the client into receive/send pair")?;
 
         let ping_label = vec![6u8, 6u8, 6u8];
-        let mut com = Com::new(log, ping_label.clone(), socket)
+        let mut com = Com::new(log, ping_label.clone(), socket, ip)
             .chain_err(|| "during communicator construction")?;
 
         com.send_errors()?;
@@ -321,20 +415,37 @@ impl Handler {
 
         com.send(msg::to_client::Msg::DoneLoading)?;
 
-        let charts = time! {
-            Charts
-                ::auto_gen()
-                .chain_err(|| "during default filter generation")?,
-            |time| log::info!("done with filter generation in {}", time)
+        let shared = match view {
+            Some(id) => Self::shared_view(&mut com, &id)?,
+            None => None,
+        };
+        let restored = match (shared, token) {
+            (Some(charts), _) => Some((crate::session::fresh_token(), charts)),
+            (None, Some(token)) => crate::session::attach(&token)?.map(|charts| (token, charts)),
+            (None, None) => None,
+        };
+        let (session, charts) = if let Some(restored) = restored {
+            log::info!("restoring session of {}", com.ip());
+            restored
+        } else {
+            let charts = time! {
+                Charts
+                    ::auto_gen()
+                    .chain_err(|| "during default filter generation")?,
+                |time| log::info!("done with filter generation in {}", time)
+            };
+            (crate::session::fresh_token(), charts)
         };
 
         let slf = Handler {
             com,
+            session,
             charts,
             from_client: FromClient::new(),
             last_frame: time::Instant::now(),
             frame_span: time::Duration::from_millis(500),
             ping_label,
+            traces: None,
 
             instance_prof,
             total_prof,
@@ -346,6 +457,42 @@ impl Handler {
         Ok(slf)
     }
 
+    /// Creates the charts of a shared view.
+    ///
+    /// Returns `None` and alerts the client if the view does not exist. Also alerts the client if
+    /// the view was taken on a trace that is not the active one.
+    fn shared_view(com: &mut Com, id: &str) -> Res<Option<Charts>> {
+        let view = match charts::view::get(id)? {
+            Some(view) => view,
+            None => {
+                com.send(msg::to_client::Msg::alert(
+                    format!(
+                        "unknown view `{}`, it may have been lost when the server restarted",
+                        id
+                    ),
+                    false,
+                ))?;
+                return Ok(None);
+            }
+        };
+        let active = charts::data::traces::active()?;
+        if let Some(trace) = view.trace.as_ref() {
+            if active.as_ref() != Some(trace) {
+                com.send(msg::to_client::Msg::alert(
+                    format!(
+                        "this view was made on trace `{}`, load it from the trace list to see the same data",
+                        trace
+                    ),
+                    false,
+                ))?
+            }
+        }
+        log::info!("opening view `{}` for {}", id, com.ip());
+        Charts::of_view(&view)
+            .map(Some)
+            .chain_err(|| format!("while opening view `{}`", id))
+    }
+
     /// The client's IP address.
     pub fn ip(&self) -> &net::IpAddr {
         self.com.ip()
@@ -375,11 +522,16 @@ impl Handler {
     }
 
     /// Runs the handler.
-    pub fn run(&mut self) {
+    ///
+    /// When the connection ends, the session is detached so that the client can reattach it later.
+    pub fn run(mut self) {
         base::unwrap_or!(
             self.internal_run(),
             log::info!("lost connection with {}", self.ip())
-        )
+        );
+        if let Err(e) = crate::session::detach(self.session, self.charts) {
+            log::error!("failed to detach session: {}", e.to_pretty())
+        }
     }
 
     /// Sets the time of the last frame to now.
@@ -455,7 +607,9 @@ impl Handler {
             }
 
             // Render.
-            self.send_points(false)?
+            self.send_points(false)?;
+
+            self.send_traces()?
         }
 
         Ok(())
@@ -466,6 +620,22 @@ impl Handler {
         self.com.send_stats(&self.charts)
     }
 
+    /// Sends the traces of the trace directory to the client, if they changed.
+    fn send_traces(&mut self) -> Res<()> {
+        let traces = charts::data::traces::list()?;
+        let active = charts::data::traces::active()?;
+        let changed = self
+            .traces
+            .as_ref()
+            .map(|(prev_traces, prev_active)| prev_traces != &traces || prev_active != &active)
+            .unwrap_or(true);
+        if changed && !traces.is_empty() {
+            self.send(msg::to_client::Msg::traces(traces.clone(), active.clone()))?;
+        }
+        self.traces = Some((traces, active));
+        Ok(())
+    }
+
     /// Sends all charts to the client.
     fn send_all_charts(&mut self) -> Res<()> {
         for chart in self.charts.charts() {
@@ -495,6 +665,10 @@ impl Handler {
             self.charts.new_points(init)?
         };
 
+        // Messages produced by a restart of the run, if any, must be sent before the points.
+        self.msgs.extend(self.charts.drain_msgs());
+        self.send_all()?;
+
         if !points.is_empty() {
             time! {
                 > self.instance_prof.point_sending,
@@ -514,7 +688,13 @@ impl Handler {
     }
 
     /// Initializes a client.
+    ///
+    /// Sends the whole state of the session, so that the client can restore it if the session was
+    /// reattached.
     pub fn init(&mut self) -> Res<()> {
+        let settings = self.charts.settings().clone();
+        self.send(msg::to_client::Msg::session(self.session.clone()))?;
+        self.send(msg::to_client::Msg::settings(settings))?;
         self.send_stats()?;
 
         self.send_filters()
@@ -523,6 +703,8 @@ impl Handler {
             .chain_err(|| "while sending charts for client init")?;
         self.send_points(true)
             .chain_err(|| "while sending points for client init")?;
+        self.send_traces()
+            .chain_err(|| "while sending traces for client init")?;
 
         Ok(())
     }
@@ -0,0 +1,153 @@
+/*<LICENSE>
+    This file is part of Memthol.
+
+    Copyright (C) 2020 OCamlPro.
+
+    Memthol is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Memthol is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Memthol.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Websockets on the HTTP server.
+//!
+//! The `/ws` route upgrades HTTP requests to websockets, so that the UI works through a single
+//! port: behind port forwarding, reverse proxies, containers...
+//!
+//! The HTTP server is asynchronous while [`Handler`]s work on blocking sockets. So, the upgraded
+//! connection is bridged to one end of a pair of connected local sockets, and the handler works on
+//! the other end in a thread of its own, see [`local_pair`]. The pair must be private to memthol,
+//! so that no other process can hijack the (already authenticated) connection.
+//!
+//! [`local_pair`]: fn.local_pair.html (The local_pair function)
+//! [`Handler`]: ../struct.Handler.html (The Handler struct)
+
+use crate::prelude::*;
+
+use gotham::{
+    handler::HandlerFuture,
+    hyper::{upgrade::Upgraded, Body, HeaderMap, Method, Response, StatusCode, Uri, Version},
+    state::{client_addr, FromState, State},
+};
+use std::pin::Pin;
+use tokio::io::AsyncWriteExt;
+use tungstenite::{handshake::server::Request, protocol::Role, WebSocket};
+
+use super::{Handler, Handshake};
+
+/// Handles a request on the `/ws` route.
+///
+/// Answers with the websocket handshake response, and spawns a [`Handler`] on the upgraded
+/// connection.
+///
+/// [`Handler`]: ../struct.Handler.html (The Handler struct)
+pub fn handler(log: bool, mut state: State) -> Pin<Box<HandlerFuture>> {
+    let mut request = Request::new(());
+    *request.method_mut() = Method::borrow_from(&state).clone();
+    *request.uri_mut() = Uri::borrow_from(&state).clone();
+    *request.version_mut() = *Version::borrow_from(&state);
+    *request.headers_mut() = HeaderMap::borrow_from(&state).clone();
+
+    let handshake = match Handshake::new(&request) {
+        Ok(handshake) => handshake,
+        Err(error) => {
+            let (parts, msg) = error.into_parts();
+            let response = Response::from_parts(parts, Body::from(msg.unwrap_or_default()));
+            return Box::pin(std::future::ready(Ok((state, response))));
+        }
+    };
+    let response = match tungstenite::handshake::server::create_response(&request) {
+        Ok(response) => response,
+        Err(e) => {
+            let mut response = Response::new(Body::from(e.to_string()));
+            *response.status_mut() = StatusCode::BAD_REQUEST;
+            return Box::pin(std::future::ready(Ok((state, response))));
+        }
+    };
+
+    let ip = client_addr(&state);
+    let body = Body::take_from(&mut state);
+    tokio::spawn(async move {
+        let res = match (body.on_upgrade().await, ip) {
+            (Ok(upgraded), Some(ip)) => bridge(log, upgraded, ip, handshake).await,
+            (Ok(_), None) => Err("failed to retrieve client IP".into()),
+            (Err(e), _) => Err(e.to_string().into()),
+        };
+        if let Err(e) = res.chain_err(|| "while upgrading a request to a websocket") {
+            log::error!("{}", e.to_pretty())
+        }
+    });
+
+    let (parts, ()) = response.into_parts();
+    let response = Response::from_parts(parts, Body::empty());
+    Box::pin(std::future::ready(Ok((state, response))))
+}
+
+/// Bridges an upgraded connection to a [`Handler`] running in a thread of its own.
+///
+/// Returns when the connection closes.
+///
+/// [`Handler`]: ../struct.Handler.html (The Handler struct)
+async fn bridge(log: bool, upgraded: Upgraded, ip: net::IpAddr, handshake: Handshake) -> Res<()> {
+    let (near, far) =
+        local_pair().chain_err(|| "while creating the local end of a websocket bridge")?;
+    near.set_nonblocking(true)?;
+
+    let _ = std::thread::spawn(move || {
+        let socket = WebSocket::from_raw_socket(net::Stream::Local(far), Role::Server, None);
+        match Handler::of_socket(log, socket, ip, handshake) {
+            Ok(handler) => handler.run(),
+            Err(e) => log::error!("failed to start request handler: {}", e.to_pretty()),
+        }
+    });
+
+    #[cfg(unix)]
+    let near = tokio::net::UnixStream::from_std(near)?;
+    #[cfg(not(unix))]
+    let near = tokio::net::TcpStream::from_std(near)?;
+    let (mut near_read, mut near_write) = tokio::io::split(near);
+    let (mut up_read, mut up_write) = tokio::io::split(upgraded);
+    let to_handler = tokio::spawn(async move {
+        let _ = tokio::io::copy(&mut up_read, &mut near_write).await;
+        near_write.shutdown().await
+    });
+    let _ = tokio::io::copy(&mut near_read, &mut up_write).await;
+    up_write.shutdown().await?;
+    to_handler.await.map_err(|e| e.to_string())??;
+    Ok(())
+}
+
+/// Creates a pair of connected local sockets.
+///
+/// A pair of unix sockets, which no other process can connect to.
+#[cfg(unix)]
+fn local_pair() -> Res<(net::LocalStream, net::LocalStream)> {
+    Ok(std::os::unix::net::UnixStream::pair()?)
+}
+
+/// Creates a pair of connected local sockets.
+///
+/// There are no unix sockets on this platform, so this is a loopback TCP connection. Another
+/// process could connect to the listener before us, so the listener only accepts the connection
+/// coming from the socket created here, and is dropped right after.
+#[cfg(not(unix))]
+fn local_pair() -> Res<(net::LocalStream, net::LocalStream)> {
+    let listener = net::TcpListener::bind((std::net::Ipv4Addr::LOCALHOST, 0))?;
+    let far = net::TcpStream::connect(listener.local_addr()?)?;
+    let far_addr = far.local_addr()?;
+    loop {
+        let (near, peer) = listener.accept()?;
+        if peer == far_addr {
+            return Ok((near, far));
+        }
+        log::warn!("rejecting unexpected local connection from {}", peer)
+    }
+}